@@ -23,6 +23,7 @@ async fn main() -> Result<(), RocketError> {
                 path: Some("/".to_string()),
                 same_site: SameSite::Lax,
                 http_only: true,
+                ..SessionConfig::default()
             },
         ))
         .mount("/", routes![hit_counter, bare_route])
@@ -0,0 +1,96 @@
+//! Per-request overhead of the session fairing, and how much an `InMemory` tidy holds up
+//! concurrent writes in each `TidyMode`. Run with `cargo bench`.
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rocket::{get, http::Cookie, local::blocking::Client, routes, tokio::runtime::Runtime};
+use turbopump::{
+    fairing::SessionFairing,
+    id::{SessionIdGenerator, ThreadRngGenerator},
+    store::in_memory::{InMemory, TidyMode},
+    Session, SessionStore,
+};
+
+#[get("/")]
+fn index(session: &Session<u32>) -> &'static str {
+    session.read(|_| "ok")
+}
+
+fn client() -> Client {
+    let rocket = rocket::ignite()
+        .attach(SessionFairing::<InMemory<u32>>::init())
+        .mount("/", routes![index]);
+
+    Client::untracked(rocket).expect("valid rocket instance")
+}
+
+fn requests(c: &mut Criterion) {
+    let client = client();
+    let mut group = c.benchmark_group("request");
+
+    group.bench_function("cookieless", |b| b.iter(|| client.get("/").dispatch()));
+
+    let response = client.get("/").dispatch();
+    let session_cookie = response.cookies().get("session_id").cloned().unwrap();
+    group.bench_function("valid cookie", |b| {
+        b.iter(|| client.get("/").cookie(session_cookie.clone()).dispatch())
+    });
+
+    group.bench_function("invalid cookie", |b| {
+        b.iter(|| {
+            client
+                .get("/")
+                .cookie(Cookie::new("session_id", "not-a-session"))
+                .dispatch()
+        })
+    });
+
+    group.finish();
+}
+
+fn id_generation(c: &mut Criterion) {
+    c.bench_function("generate session id", |b| {
+        b.iter(|| ThreadRngGenerator.generate())
+    });
+}
+
+/// Times `WRITES` writes made while another thread tidies away `EXPIRED` expired sessions, so the
+/// modes can be compared by how long they make writers wait.
+fn tidy_contention(c: &mut Criterion) {
+    const EXPIRED: usize = 10_000;
+    const WRITES: usize = 1_000;
+
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("writes during tidy");
+    for &mode in &[TidyMode::Retain, TidyMode::TwoPhase] {
+        group.bench_function(BenchmarkId::from_parameter(format!("{:?}", mode)), |b| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::default();
+                for _ in 0..iters {
+                    let store = InMemory::<u32>::init().with_tidy_mode(mode);
+                    for _ in 0..EXPIRED {
+                        runtime.block_on(store.store(Session::new(-1))).unwrap();
+                    }
+
+                    let tidying = store.clone();
+                    let tidy = std::thread::spawn(move || {
+                        Runtime::new().unwrap().block_on(tidying.tidy(0)).unwrap()
+                    });
+                    let start = Instant::now();
+                    for _ in 0..WRITES {
+                        runtime.block_on(store.store(Session::new(3600))).unwrap();
+                    }
+                    elapsed += start.elapsed();
+                    tidy.join().unwrap();
+                }
+
+                elapsed
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, requests, id_generation, tidy_contention);
+criterion_main!(benches);
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use log::error;
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request, State,
+};
+use std::ops::Deref;
+
+use crate::{
+    fairing::{config::SessionConfig, SessionState},
+    store::SessionStore,
+};
+
+/// A request guard giving handlers the fairing's session store, e.g. for operator-only routes:
+///
+/// ```ignore
+/// #[get("/admin/sessions/count")]
+/// async fn count_sessions(store: StoreHandle<'_, InMemory<Data>>) -> Option<String> {
+///     store.count().await.ok().map(|count| count.to_string())
+/// }
+///
+/// #[delete("/admin/sessions")]
+/// async fn clear_sessions(store: StoreHandle<'_, InMemory<Data>>) -> Status {
+///     match store.clear().await {
+///         Ok(()) => Status::NoContent,
+///         Err(_) => Status::InternalServerError,
+///     }
+/// }
+/// ```
+///
+/// Derefs to the store, so every [`SessionStore`] method is available. `Store` must be the
+/// exact type the fairing was attached with; if no such fairing is attached, the guard fails
+/// with `500 Internal Server Error`. Routes using it are usually worth protecting with an
+/// authorization guard of their own.
+pub struct StoreHandle<'r, Store: SessionStore> {
+    store: &'r Store,
+    state: &'r SessionState<Store::SessionData>,
+}
+
+impl<Store: SessionStore> StoreHandle<'_, Store> {
+    /// The config of the fairing managing the store.
+    pub fn config(&self) -> &SessionConfig {
+        &self.state.config
+    }
+
+    /// Sweeps expired sessions from the store, with the configured clock skew tolerance.
+    pub async fn tidy(&self) -> Result<()> {
+        self.store.tidy(self.config().clock_skew_tolerance).await
+    }
+}
+
+impl<Store: SessionStore> Deref for StoreHandle<'_, Store> {
+    type Target = Store;
+
+    fn deref(&self) -> &Store {
+        self.store
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, 'r, Store: SessionStore> FromRequest<'a, 'r> for StoreHandle<'a, Store> {
+    type Error = ();
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let store = request.managed_state::<Box<Store>>();
+        let state = request.managed_state::<SessionState<Store::SessionData>>();
+        match (store, state) {
+            (Some(store), Some(state)) => Outcome::Success(StoreHandle {
+                store: &**store,
+                state,
+            }),
+            _ => {
+                error!(
+                    "`StoreHandle<{}>` used, but no session fairing with that store is attached",
+                    std::any::type_name::<Store>()
+                );
+                Outcome::Failure((Status::InternalServerError, ()))
+            }
+        }
+    }
+}
+
+/// Sweeps expired sessions from the store on demand, e.g. from an operator-only route:
+///
+/// ```ignore
+/// #[post("/admin/sessions/tidy")]
+/// async fn tidy_sessions(
+///     store: State<'_, Box<InMemory<Data>>>,
+///     state: State<'_, SessionState<Data>>,
+/// ) -> Status {
+///     match turbopump::admin::tidy(store, state).await {
+///         Ok(()) => Status::NoContent,
+///         Err(_) => Status::InternalServerError,
+///     }
+/// }
+/// ```
+///
+/// The fairing keeps the store in managed state as a `Box<Store>`, alongside its config in a
+/// `SessionState<Data>`. Stores must support concurrent `tidy` calls, so this is safe to run
+/// alongside the fairing's lottery sweep.
+pub async fn tidy<Store: SessionStore>(
+    store: State<'_, Box<Store>>,
+    state: State<'_, SessionState<Store::SessionData>>,
+) -> Result<()> {
+    store.tidy(state.config.clock_skew_tolerance).await
+}
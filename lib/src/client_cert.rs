@@ -0,0 +1,48 @@
+//! Binding sessions to the client's TLS certificate, so a stolen session cookie is useless
+//! without the certificate it was issued to.
+//!
+//! Rocket doesn't expose the peer certificate of a TLS connection to request handlers or
+//! fairings, so the certificate comes from a [`ClientCertSource`]. Deployments terminating
+//! mutual TLS at a proxy can use [`ForwardedCertificate`] to read the certificate (or its
+//! fingerprint) from a header the proxy sets, e.g. nginx's `$ssl_client_escaped_cert`.
+use rocket::Request;
+use sha2::{Digest, Sha256};
+
+/// Provides the fingerprint of the certificate the client presented. See
+/// [`SessionFairingBuilder::client_cert_source`](crate::fairing::SessionFairingBuilder::client_cert_source).
+pub trait ClientCertSource: Send + Sync + 'static {
+    /// Returns the fingerprint of the request's client certificate (see [`fingerprint`]), or
+    /// `None` if the client didn't present one.
+    fn fingerprint(&self, req: &Request<'_>) -> Option<String>;
+}
+
+/// Reads the client certificate from a header set by a proxy terminating mutual TLS.
+///
+/// The proxy must overwrite the header on every request, or clients could choose their own
+/// certificate.
+pub struct ForwardedCertificate {
+    header: String,
+}
+
+impl ForwardedCertificate {
+    pub fn new(header: impl Into<String>) -> Self {
+        Self {
+            header: header.into(),
+        }
+    }
+}
+
+impl ClientCertSource for ForwardedCertificate {
+    fn fingerprint(&self, req: &Request<'_>) -> Option<String> {
+        req.headers()
+            .get_one(&self.header)
+            .map(str::trim)
+            .filter(|cert| !cert.is_empty())
+            .map(|cert| fingerprint(cert.as_bytes()))
+    }
+}
+
+/// The SHA-256 fingerprint of `cert`, base64 (URL-safe, unpadded) encoded.
+pub fn fingerprint(cert: &[u8]) -> String {
+    base64::encode_config(Sha256::digest(cert), base64::URL_SAFE_NO_PAD)
+}
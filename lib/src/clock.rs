@@ -0,0 +1,32 @@
+use chrono::{DateTime, Utc};
+
+/// The current time, as seen by session expiry checks. Unit tests can move it forward with
+/// [`mock::advance`].
+#[cfg(not(test))]
+pub(crate) fn now() -> DateTime<Utc> {
+    Utc::now()
+}
+
+#[cfg(test)]
+pub(crate) fn now() -> DateTime<Utc> {
+    Utc::now() + mock::offset()
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use chrono::Duration;
+    use std::cell::Cell;
+
+    thread_local! {
+        static OFFSET: Cell<i64> = Cell::new(0);
+    }
+
+    pub(crate) fn offset() -> Duration {
+        Duration::seconds(OFFSET.with(Cell::get))
+    }
+
+    /// Moves the clock forward by `seconds` for the current thread (i.e. the current test).
+    pub(crate) fn advance(seconds: i64) {
+        OFFSET.with(|offset| offset.set(offset.get() + seconds));
+    }
+}
@@ -0,0 +1,85 @@
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+
+/// Transforms the session ID on its way into and out of the session cookie.
+///
+/// When a codec is in use the session cookie is a plain cookie holding the encoded value rather
+/// than one of Rocket's private (encrypted) cookies.
+pub trait CookieCodec: Send + Sync + 'static {
+    fn encode(&self, value: &str) -> String;
+
+    /// Returns the original value, or `None` if `encoded` wasn't produced by this codec. Cookies
+    /// that fail to decode are treated as if no session cookie was sent.
+    fn decode(&self, encoded: &str) -> Option<String>;
+}
+
+/// Appends an HMAC-SHA256 signature to the session ID, so forged or tampered IDs are rejected
+/// without a store lookup. The ID itself stays readable.
+pub struct HmacCodec {
+    key: Vec<u8>,
+}
+
+impl HmacCodec {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    fn mac(&self, value: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.key).expect("HMAC accepts keys of any size");
+        mac.update(value.as_bytes());
+        mac
+    }
+}
+
+impl CookieCodec for HmacCodec {
+    fn encode(&self, value: &str) -> String {
+        let signature = self.mac(value).finalize().into_bytes();
+
+        format!(
+            "{}.{}",
+            value,
+            base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    fn decode(&self, encoded: &str) -> Option<String> {
+        let split = encoded.rfind('.')?;
+        let (value, signature) = (&encoded[..split], &encoded[split + 1..]);
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD).ok()?;
+
+        self.mac(value).verify(&signature).ok()?;
+
+        Some(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_accepts_a_valid_signature() {
+        let codec = HmacCodec::new("secret");
+        let encoded = codec.encode("session-id");
+
+        assert!(encoded.starts_with("session-id."));
+        assert_eq!(codec.decode(&encoded), Some("session-id".to_string()));
+    }
+
+    #[test]
+    fn it_rejects_a_tampered_value() {
+        let codec = HmacCodec::new("secret");
+        let encoded = codec.encode("session-id");
+        let tampered = encoded.replacen("session-id", "session-ie", 1);
+
+        assert_eq!(codec.decode(&tampered), None);
+        assert_eq!(codec.decode("session-id"), None);
+    }
+
+    #[test]
+    fn it_rejects_a_signature_from_another_key() {
+        let encoded = HmacCodec::new("secret").encode("session-id");
+
+        assert_eq!(HmacCodec::new("other secret").decode(&encoded), None);
+    }
+}
@@ -10,6 +10,85 @@ pub enum SessionStoreError {
     ClearFailure(String),
     #[error("failed to destroy session, `{0}`")]
     DestroyFailure(String),
+    #[error("session not found")]
+    NotFound,
+    #[error("operation not supported by this store, `{0}`")]
+    Unsupported(String),
+    /// The store couldn't be reached, e.g. a refused or dropped connection.
+    #[error("failed to connect to the session store, `{0}`")]
+    Connection(String),
+    #[error("session store operation timed out, `{0}`")]
+    Timeout(String),
+    /// A session couldn't be encoded for the store, or decoded from it.
+    #[error("failed to serialize or deserialize session, `{0}`")]
+    Serialization(String),
+    /// Any other failure reported by the store's backend.
+    #[error("session store backend failed, `{0}`")]
+    Backend(anyhow::Error),
     #[error("an unknown session store error occurred")]
     Unknown,
 }
+
+impl SessionStoreError {
+    /// Whether the operation may succeed if retried as is, i.e. the failure was transient: only
+    /// `Connection` and `Timeout` are.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Connection(_) | Self::Timeout(_))
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ConfigError {
+    #[error("`max_age` must be greater than zero, got `{0}`")]
+    NonPositiveMaxAge(i32),
+    #[error("`lottery` must be between 0.0 and 1.0, got `{0}`")]
+    LotteryOutOfRange(f64),
+    #[error("`same_site = \"none\"` requires `secure = true`, or browsers reject the cookies")]
+    SameSiteNoneWithoutSecure,
+    #[error("cookie `{0}` has a `__Secure-` or `__Host-` prefix, which requires `secure = true`")]
+    PrefixRequiresSecure(String),
+    #[error("cookie `{0}` has the `__Host-` prefix, which forbids a `domain`")]
+    HostPrefixWithDomain(String),
+    #[error("cookie `{0}` has the `__Host-` prefix, which requires `path = \"/\"`")]
+    HostPrefixRequiresRootPath(String),
+    #[error("cookie `{attribute}` is {len} bytes, over browsers' limit of 1024 bytes")]
+    AttributeTooLong { attribute: &'static str, len: usize },
+    #[error("`lifetime.idle_timeout` must be greater than zero, got `{0}`")]
+    NonPositiveIdleTimeout(i64),
+    #[error("`lifetime.idle_timeout` ({idle}s) exceeds `lifetime.absolute_timeout` ({absolute}s)")]
+    IdleTimeoutExceedsAbsolute { idle: i64, absolute: i64 },
+    #[error(
+        "`lifetime.cookie_max_age` ({cookie_max_age}s) is shorter than `lifetime.idle_timeout` \
+        ({idle}s), so browsers drop the cookie of a session that's still alive"
+    )]
+    CookieMaxAgeBelowIdleTimeout { cookie_max_age: i64, idle: i64 },
+    #[error("`lifetime.grace` must not be negative, got `{0}`")]
+    NegativeGrace(i64),
+    #[error("`{0}` overlaps with `lifetime`, which takes precedence; set only one of them")]
+    LifetimeOverlap(&'static str),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_connection_and_timeout_errors_are_retryable() {
+        let retryable = [
+            SessionStoreError::Connection("connection reset".to_string()),
+            SessionStoreError::Timeout("no reply in 5s".to_string()),
+        ];
+        let permanent = [
+            SessionStoreError::LoadFailure("bad key".to_string()),
+            SessionStoreError::StoreFailure("too large".to_string()),
+            SessionStoreError::NotFound,
+            SessionStoreError::Unsupported("list".to_string()),
+            SessionStoreError::Serialization("invalid JSON".to_string()),
+            SessionStoreError::Backend(anyhow::anyhow!("quota exceeded")),
+            SessionStoreError::Unknown,
+        ];
+
+        assert!(retryable.iter().all(SessionStoreError::is_retryable));
+        assert!(!permanent.iter().any(SessionStoreError::is_retryable));
+    }
+}
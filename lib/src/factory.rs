@@ -0,0 +1,33 @@
+/// Creates the data of new sessions, for session types that have no sensible `Default` or
+/// whose initial state depends on the running app, e.g. a server-assigned guest ID. Closures
+/// returning the data implement it:
+///
+/// ```ignore
+/// let next_guest = AtomicU32::new(1);
+/// let fairing = SessionFairing::<InMemory<Guest>>::builder_with_factory(move || Guest {
+///     id: next_guest.fetch_add(1, Ordering::SeqCst),
+/// })
+/// .build();
+/// ```
+pub trait DataFactory<Data>: Send + Sync + 'static {
+    fn new_data(&self) -> Data;
+}
+
+impl<Data, F> DataFactory<Data> for F
+where
+    F: Fn() -> Data + Send + Sync + 'static,
+{
+    fn new_data(&self) -> Data {
+        self()
+    }
+}
+
+/// The factory used unless another is given: `Data::default()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultData;
+
+impl<Data: Default> DataFactory<Data> for DefaultData {
+    fn new_data(&self) -> Data {
+        Data::default()
+    }
+}
@@ -1,23 +1,41 @@
-use rocket::http::SameSite as RSameSite;
+use chrono::{DateTime, Duration, Utc};
+#[cfg(feature = "rocket")]
+use rocket::http::{Cookie, SameSite as RSameSite};
 use serde::{
     de::{self, Deserialize as DeserializeTrait, Visitor},
     Deserialize,
 };
-use std::fmt;
+use std::{borrow::Cow, fmt};
 
-#[derive(Clone, Debug, PartialEq)]
+use crate::{clock, error::ConfigError, store::schema::StorageFormat, SessionID};
+
+/// The cookie `SameSite` attribute. This is the one type used for it throughout the config; the
+/// builder also accepts Rocket's [`rocket::http::SameSite`], which converts both ways.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SameSite {
     Strict,
     Lax,
     None,
 }
 
-impl Into<RSameSite> for SameSite {
-    fn into(self) -> RSameSite {
-        match self {
-            Self::Strict => RSameSite::Strict,
-            Self::Lax => RSameSite::Lax,
-            Self::None => RSameSite::None,
+#[cfg(feature = "rocket")]
+impl From<SameSite> for RSameSite {
+    fn from(same_site: SameSite) -> Self {
+        match same_site {
+            SameSite::Strict => Self::Strict,
+            SameSite::Lax => Self::Lax,
+            SameSite::None => Self::None,
+        }
+    }
+}
+
+#[cfg(feature = "rocket")]
+impl From<RSameSite> for SameSite {
+    fn from(same_site: RSameSite) -> Self {
+        match same_site {
+            RSameSite::Strict => Self::Strict,
+            RSameSite::Lax => Self::Lax,
+            RSameSite::None => Self::None,
         }
     }
 }
@@ -53,25 +71,877 @@ impl<'de> DeserializeTrait<'de> for SameSite {
     }
 }
 
+/// The Chromium-specific `Priority` cookie attribute, used to decide which cookies are evicted
+/// first once a domain exceeds its cookie limit.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum CookiePriority {
+    #[serde(alias = "low")]
+    Low,
+    #[serde(alias = "medium")]
+    Medium,
+    #[serde(alias = "high")]
+    High,
+}
+
+impl fmt::Display for CookiePriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Low => write!(f, "Low"),
+            Self::Medium => write!(f, "Medium"),
+            Self::High => write!(f, "High"),
+        }
+    }
+}
+
+/// Controls when the fairing writes the request's session back to the store.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum StorePolicy {
+    /// Store the session at the end of every request.
+    #[serde(alias = "always")]
+    Always,
+    /// Only store sessions that were created or changed (e.g. via `Session::tap`) during the
+    /// request.
+    #[serde(alias = "on_change")]
+    OnChange,
+}
+
+impl Default for StorePolicy {
+    fn default() -> Self {
+        Self::Always
+    }
+}
+
+/// Controls whether activity moves a session's expiration.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum ExpirationPolicy {
+    /// Renew the session on every request (or, with [`Session::keepalive`](crate::Session::keepalive),
+    /// on demand), so it expires `max_age` after it was last used.
+    #[serde(alias = "sliding")]
+    Sliding,
+    /// Never renew the session, so it expires `max_age` (or its own lifespan, see
+    /// [`Session::set_lifespan`](crate::Session::set_lifespan)) after it was created, however
+    /// active it is.
+    #[serde(alias = "fixed")]
+    Fixed,
+}
+
+impl Default for ExpirationPolicy {
+    fn default() -> Self {
+        Self::Sliding
+    }
+}
+
+/// What the fairing does with the response when writing the session to the store fails. A
+/// session that fails to load is treated as missing, so a new one is started; the policy then
+/// decides whether the client's cookie is replaced by the new session's.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum StoreErrorPolicy {
+    /// Log the error and send the response, session cookie included, as if the write succeeded.
+    #[serde(alias = "optimistic")]
+    Optimistic,
+    /// Log the error and drop the session cookie (or header) from the response, so the client
+    /// isn't handed a session that was never persisted.
+    #[serde(alias = "strip_cookie")]
+    StripCookie,
+    /// Like `StripCookie`, but also turn the response into a `503 Service Unavailable`.
+    #[serde(alias = "service_unavailable")]
+    ServiceUnavailable,
+}
+
+impl Default for StoreErrorPolicy {
+    fn default() -> Self {
+        Self::Optimistic
+    }
+}
+
+/// Where the session cookies' `Path` comes from.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum PathStrategy {
+    /// Use the configured `path`.
+    #[serde(alias = "configured")]
+    Configured,
+    /// Use the mount point of the app's routes, e.g. `/app` when everything is mounted under
+    /// `/app`. When routes are mounted at several points, the longest path they share is used,
+    /// which is `/` for mount points like `/app` and `/api`. Falls back to the configured `path`
+    /// when no routes are mounted.
+    #[serde(alias = "derive_from_mount")]
+    DeriveFromMount,
+}
+
+impl Default for PathStrategy {
+    fn default() -> Self {
+        Self::Configured
+    }
+}
+
+/// How the session ID travels between the client and the server.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// In a private `session_id` cookie.
+    Cookie,
+    /// In the named request header, echoed back in a response header of the same name. No
+    /// cookies are set.
+    Header { name: String },
+    /// Derived from the `Authorization: Bearer <token>` header, for API clients sharing the
+    /// session machinery with browsers. A new session takes the derived ID, so the token keeps
+    /// finding it, and nothing is sent back. Requests without a token get a throwaway session.
+    ///
+    /// The token is only used as a key: it must be authenticated elsewhere (e.g. by a request
+    /// guard), since anyone presenting a token gets the session stored under it, or a fresh one.
+    Bearer {
+        #[serde(default)]
+        derivation: TokenDerivation,
+    },
+}
+
+/// How [`Transport::Bearer`] turns a bearer token into a session ID.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenDerivation {
+    /// The token's SHA-256 hash (see [`SessionID::hashed`]), so the store never holds a usable
+    /// token, and tokens of any length or alphabet fit.
+    Hash,
+    /// The token itself, for tokens that are already opaque IDs. Tokens [`SessionID::parse`]
+    /// rejects get a throwaway session.
+    Raw,
+}
+
+impl Default for TokenDerivation {
+    fn default() -> Self {
+        Self::Hash
+    }
+}
+
+impl TokenDerivation {
+    pub fn session_id(self, token: &str) -> Option<SessionID> {
+        if token.is_empty() {
+            return None;
+        }
+
+        match self {
+            Self::Hash => Some(SessionID(SessionID(token.to_string()).hashed())),
+            Self::Raw => SessionID::parse(token),
+        }
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::Cookie
+    }
+}
+
+/// How long sessions and their cookies last, in seconds, in one place. See
+/// [`SessionConfig::lifetime`].
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct LifetimeConfig {
+    /// How long a session lasts without activity; each request renews it by this much.
+    pub idle_timeout: i64,
+    /// How long a session lasts after it was created, however active it is. `None` renews it
+    /// indefinitely. An absolute timeout equal to the idle timeout behaves like
+    /// [`ExpirationPolicy::Fixed`].
+    #[serde(default)]
+    pub absolute_timeout: Option<i64>,
+    /// The session cookie's `Max-Age`, if it should differ from the idle timeout, e.g. longer so
+    /// the browser keeps presenting an expired session for the server to reclaim within `grace`.
+    #[serde(default)]
+    pub cookie_max_age: Option<i64>,
+    /// How long after it expires a session can still be reclaimed (see
+    /// `SessionConfig::reclaim_grace`).
+    #[serde(default)]
+    pub grace: i64,
+}
+
+impl Default for LifetimeConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: 3600,
+            absolute_timeout: None,
+            cookie_max_age: None,
+            grace: 0,
+        }
+    }
+}
+
+impl LifetimeConfig {
+    /// Checks that every timeout is in range and that they don't contradict each other: the
+    /// idle timeout can't exceed the absolute one, and the cookie must live at least as long as
+    /// the idle timeout.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+        let idle = self.idle_timeout;
+        if idle <= 0 {
+            errors.push(ConfigError::NonPositiveIdleTimeout(idle));
+        }
+        if let Some(absolute) = self.absolute_timeout {
+            if idle > absolute {
+                errors.push(ConfigError::IdleTimeoutExceedsAbsolute { idle, absolute });
+            }
+        }
+        if let Some(cookie_max_age) = self.cookie_max_age {
+            if cookie_max_age < idle {
+                errors.push(ConfigError::CookieMaxAgeBelowIdleTimeout {
+                    cookie_max_age,
+                    idle,
+                });
+            }
+        }
+        if self.grace < 0 {
+            errors.push(ConfigError::NegativeGrace(self.grace));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Whether a session created at `created` has outlived the absolute timeout.
+    pub fn past_absolute_timeout(&self, created: DateTime<Utc>) -> bool {
+        matches!(
+            self.absolute_timeout,
+            Some(absolute) if clock::now() >= created + Duration::seconds(absolute)
+        )
+    }
+}
+
+/// The fairing's settings, extracted from the `session` table of Rocket's figment for the active
+/// profile: keys in e.g. `[release.session]` override those in `[default.session]` when running
+/// with the `release` profile.
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct SessionConfig {
+    /// The name of the session cookie. Fairings for different session data types attached to
+    /// the same app must use distinct names, or they'll overwrite each other's cookies.
+    #[serde(default = "default_cookie_name")]
+    pub cookie_name: String,
+    /// The name of the CSRF token cookie; like `cookie_name`, it must be distinct per fairing.
+    #[serde(default = "default_xsrf_cookie_name")]
+    pub xsrf_cookie_name: String,
+    /// Names the session cookie went by before `cookie_name`, tried in order when the client
+    /// doesn't send `cookie_name`. A session found under one is re-issued under `cookie_name`
+    /// and the old cookie removed, so the cookie can be renamed without logging everyone out.
+    #[serde(default)]
+    pub legacy_cookie_names: Vec<String>,
+    /// **Migration only; off by default.** For apps moving to private (encrypted) session
+    /// cookies from plaintext ones: a session cookie that fails to decrypt is read as a plaintext
+    /// session ID, and the session it names, if the store has it, is re-issued in a private
+    /// cookie. Clients can forge plaintext cookies, so turn this off again once the old cookies
+    /// have been upgraded or expired, i.e. after `max_age`. Doesn't apply with a cookie codec or
+    /// `signing_key`.
+    #[serde(default)]
+    pub accept_plaintext_cookies: bool,
+    /// **Risky; off by default.** For clients that can't hold cookies: when the request has no
+    /// session cookie, the session ID is read from this query parameter instead (e.g.
+    /// `/cart?sid=...`). Only applies to the cookie transport; the app has to put
+    /// `session.id()` into its links itself.
+    ///
+    /// An ID in the URL is a bearer credential in plain sight: it leaks to other sites through
+    /// the `Referer` header, lands in browser history, bookmarks, proxy and server logs, and is
+    /// handed to anyone the link is shared with, who then shares the session. It also enables
+    /// session fixation, as a crafted link can plant a known ID. Keep lifespans short, set
+    /// `Referrer-Policy: no-referrer`, and prefer cookies whenever the client supports them; the
+    /// cookie is still set, so clients that accept it stop needing the parameter.
+    pub session_id_query_param: Option<String>,
+    /// Seconds a session lasts without activity, which is also the session cookie's `Max-Age`.
+    /// Superseded by `lifetime` when that's set.
     pub max_age: i32,
+    /// The session's idle and absolute timeouts, cookie `Max-Age`, and reclaim grace together,
+    /// validated against each other. Takes the place of `max_age` and `reclaim_grace`.
+    #[serde(default)]
+    pub lifetime: Option<LifetimeConfig>,
+    /// Seconds the session's expiration may drift from that of the cookie last sent for it
+    /// (e.g. as renewals slide the expiration forward, or after `max_age` changes) before the
+    /// cookie is re-issued with an up-to-date `Max-Age`.
+    #[serde(default = "default_cookie_refresh_threshold")]
+    pub cookie_refresh_threshold: i64,
     pub domain: Option<String>,
+    /// When set along with `domain` (e.g. `.example.com`), session cookies are only set for
+    /// requests whose `Host` is that domain or one of its subdomains; for any other host the
+    /// cookies are skipped and a warning is logged.
+    #[serde(default)]
+    pub validate_domain: bool,
     pub path: Option<String>,
+    #[serde(default)]
+    pub path_strategy: PathStrategy,
     pub same_site: SameSite,
     pub http_only: bool,
+    /// Sets the `Secure` attribute explicitly; when unset, Rocket's default applies.
+    pub secure: Option<bool>,
+    pub priority: Option<CookiePriority>,
+    #[serde(default)]
+    pub store_policy: StorePolicy,
+    #[serde(default)]
+    pub expiration_policy: ExpirationPolicy,
+    pub max_sessions_per_index: Option<usize>,
+    /// Caps how many sessions one client IP can create per `new_session_window`, to blunt bots
+    /// creating endless sessions by never sending the cookie back. Over the limit, requests get
+    /// a throwaway session that isn't stored or sent as a cookie.
+    ///
+    /// The client IP is Rocket's `Request::client_ip`, which trusts the `X-Real-IP` header, so
+    /// only enable this behind a proxy that sets it, or clients can pick their own IP. Clients
+    /// behind a shared NAT or proxy share a limit, and `X-Forwarded-For` is not consulted.
+    pub max_new_sessions_per_ip: Option<u32>,
+    #[serde(default = "default_new_session_window")]
+    pub new_session_window: i64,
+    #[serde(default)]
+    pub transport: Transport,
+    /// The chance, from `0.0` to `1.0`, that a request sweeps expired sessions from the store.
+    /// Also accepts a percentage string such as `"10%"`.
+    #[serde(default = "default_lottery", deserialize_with = "deserialize_lottery")]
+    pub lottery: f64,
+    /// When set, the session cookie holds the session ID signed with this key (see
+    /// `codec::HmacCodec`) instead of being a private (encrypted) cookie.
+    pub signing_key: Option<String>,
+    /// For replica nodes in a primary/replica deployment: sessions are loaded but never
+    /// created, renewed, stored, or tidied, and no session cookie is set. Requests without a
+    /// valid session get a throwaway one that is discarded with the response.
+    #[serde(default)]
+    pub read_only: bool,
+    /// For apps where most traffic is anonymous: a new session is only stored, and its cookies
+    /// only set, once the handler calls [`Session::activate`](crate::Session::activate), e.g.
+    /// on login. Until then the request gets a throwaway session, as under `read_only`.
+    #[serde(default)]
+    pub require_activation: bool,
+    /// Seconds a session may outlive its expiration before it's treated as expired, to absorb
+    /// clock skew between app nodes sharing a store.
+    #[serde(default)]
+    pub clock_skew_tolerance: i64,
+    /// Seconds after a session expires (beyond `clock_skew_tolerance`) during which a request
+    /// presenting it reclaims it: the fairing renews it instead of starting a new session, and
+    /// `tidy` leaves it in place. Unlike `clock_skew_tolerance`, which applies to every validity
+    /// check, the session counts as expired everywhere else, e.g. to `PeekSession`, `read_only`
+    /// configs, and `ExpirationPolicy::Fixed`, none of which renew. Superseded by `lifetime`
+    /// when that's set.
+    #[serde(default)]
+    pub reclaim_grace: Option<i64>,
+    #[serde(default)]
+    pub on_store_error: StoreErrorPolicy,
+    /// Path prefixes of routes that only peek at sessions through `PeekSession`: their sessions
+    /// are loaded without being renewed, created, stored, or sent back as cookies. Prefixes match
+    /// whole segments, so `/health` covers `/health/live` but not `/healthz`.
+    #[serde(default)]
+    pub peek_paths: Vec<String>,
+    /// Prepended to every key by networked stores (see `SessionStore::configure`), so apps
+    /// sharing a backend, or staging and production sharing an instance, don't collide.
+    #[serde(default = "default_key_prefix")]
+    pub key_prefix: Cow<'static, str>,
+    /// How networked stores encode sessions: `"json"`, `"bincode"`, or `"messagepack"`. Records
+    /// written in any format stay readable after switching (see `store::schema`).
+    #[serde(default)]
+    pub storage_format: StorageFormat,
+}
+
+fn default_cookie_name() -> String {
+    "session_id".to_string()
+}
+
+fn default_xsrf_cookie_name() -> String {
+    "xsrf_token".to_string()
+}
+
+pub(crate) const DEFAULT_KEY_PREFIX: &str = "turbopump:";
+
+fn default_key_prefix() -> Cow<'static, str> {
+    Cow::Borrowed(DEFAULT_KEY_PREFIX)
+}
+
+fn default_cookie_refresh_threshold() -> i64 {
+    60
 }
 
-impl SessionConfig {}
+fn default_new_session_window() -> i64 {
+    60
+}
+
+fn default_lottery() -> f64 {
+    0.01
+}
+
+fn deserialize_lottery<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct LotteryVisitor;
+
+    impl<'de> Visitor<'de> for LotteryVisitor {
+        type Value = f64;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a fraction between 0.0 and 1.0, or a percentage like `\"10%\"`")
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<f64, E>
+        where
+            E: de::Error,
+        {
+            // a value like `10` is almost certainly meant as a percentage, but would otherwise
+            // silently sweep the store on every request
+            if (0.0..=1.0).contains(&value) {
+                Ok(value)
+            } else {
+                Err(E::custom(ConfigError::LotteryOutOfRange(value)))
+            }
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<f64, E>
+        where
+            E: de::Error,
+        {
+            self.visit_f64(value as f64)
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<f64, E>
+        where
+            E: de::Error,
+        {
+            self.visit_f64(value as f64)
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<f64, E>
+        where
+            E: de::Error,
+        {
+            let percentage = value
+                .trim()
+                .strip_suffix('%')
+                .and_then(|percentage| percentage.trim().parse::<f64>().ok())
+                .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))?;
+
+            self.visit_f64(percentage / 100.0)
+        }
+    }
+
+    deserializer.deserialize_any(LotteryVisitor)
+}
+
+/// Browsers ignore cookies with a `Domain` or `Path` longer than this.
+const MAX_ATTRIBUTE_BYTES: usize = 1024;
+
+impl SessionConfig {
+    pub fn builder() -> SessionConfigBuilder {
+        SessionConfigBuilder::default()
+    }
+
+    /// How long past its expiration a session can still be renewed: the clock skew tolerance
+    /// plus the reclaim grace, if any.
+    #[cfg(feature = "rocket")]
+    pub(crate) fn reclaim_tolerance(&self) -> i64 {
+        self.clock_skew_tolerance + self.lifetime().grace
+    }
+
+    /// The timeouts the fairing applies: `lifetime` if it's set, or else those `max_age` and
+    /// `reclaim_grace` amount to.
+    pub fn lifetime(&self) -> LifetimeConfig {
+        self.lifetime.unwrap_or(LifetimeConfig {
+            idle_timeout: self.max_age.into(),
+            absolute_timeout: None,
+            cookie_max_age: None,
+            grace: self.reclaim_grace.unwrap_or(0),
+        })
+    }
+
+    /// The session cookie's `Max-Age`.
+    #[cfg(feature = "rocket")]
+    pub(crate) fn cookie_max_age(&self) -> i32 {
+        match self.lifetime {
+            Some(lifetime) => {
+                let max_age = lifetime.cookie_max_age.unwrap_or(lifetime.idle_timeout);
+                std::convert::TryFrom::try_from(max_age).unwrap_or(i32::MAX)
+            }
+            None => self.max_age,
+        }
+    }
+
+    /// Overrides the session cookie's `Max-Age`, whether it comes from `max_age` or `lifetime`.
+    pub(crate) fn set_cookie_max_age(&mut self, max_age: i32) {
+        self.max_age = max_age;
+        if let Some(lifetime) = &mut self.lifetime {
+            lifetime.cookie_max_age = Some(max_age.into());
+        }
+    }
+
+    /// Checks the config for values that are out of range or that make browsers drop the
+    /// session cookies, returning every problem found. The fairing logs them when attached, or
+    /// refuses to launch if it's strict (see
+    /// [`SessionFairingBuilder::strict`](crate::fairing::SessionFairingBuilder::strict)).
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+        match self.lifetime {
+            Some(lifetime) => {
+                if let Err(lifetime_errors) = lifetime.validate() {
+                    errors.extend(lifetime_errors);
+                }
+                if self.reclaim_grace.is_some() {
+                    errors.push(ConfigError::LifetimeOverlap("reclaim_grace"));
+                }
+                if self.expiration_policy == ExpirationPolicy::Fixed {
+                    errors.push(ConfigError::LifetimeOverlap("expiration_policy"));
+                }
+            }
+            None if self.max_age <= 0 => errors.push(ConfigError::NonPositiveMaxAge(self.max_age)),
+            None => {}
+        }
+        if !(0.0..=1.0).contains(&self.lottery) {
+            errors.push(ConfigError::LotteryOutOfRange(self.lottery));
+        }
+
+        if self.transport == Transport::Cookie {
+            let secure = self.secure == Some(true);
+            if self.same_site == SameSite::None && !secure {
+                errors.push(ConfigError::SameSiteNoneWithoutSecure);
+            }
+
+            for name in &[&self.cookie_name, &self.xsrf_cookie_name] {
+                let host_prefix = name.starts_with("__Host-");
+                if (host_prefix || name.starts_with("__Secure-")) && !secure {
+                    errors.push(ConfigError::PrefixRequiresSecure(name.to_string()));
+                }
+                if host_prefix && self.domain.is_some() {
+                    errors.push(ConfigError::HostPrefixWithDomain(name.to_string()));
+                }
+                if host_prefix && matches!(self.path.as_deref(), Some(path) if path != "/") {
+                    errors.push(ConfigError::HostPrefixRequiresRootPath(name.to_string()));
+                }
+            }
+
+            let attributes = [("domain", &self.domain), ("path", &self.path)];
+            for (attribute, value) in attributes.iter() {
+                match value {
+                    Some(value) if value.len() > MAX_ATTRIBUTE_BYTES => {
+                        errors.push(ConfigError::AttributeTooLong {
+                            attribute,
+                            len: value.len(),
+                        })
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns a copy of this config with any attributes set on `cookie_override` taking
+    /// precedence over the globally configured ones.
+    pub fn with_override(&self, cookie_override: &CookieOverride) -> Self {
+        let mut config = self.clone();
+
+        if let Some(max_age) = cookie_override.max_age {
+            config.set_cookie_max_age(max_age);
+        }
+
+        if let Some(domain) = &cookie_override.domain {
+            config.domain = Some(domain.clone());
+        }
+
+        if let Some(path) = &cookie_override.path {
+            config.path = Some(path.clone());
+        }
+
+        if let Some(same_site) = cookie_override.same_site {
+            config.same_site = same_site;
+        }
+
+        if let Some(http_only) = cookie_override.http_only {
+            config.http_only = http_only;
+        }
+
+        if let Some(secure) = cookie_override.secure {
+            config.secure = Some(secure);
+        }
+
+        if let Some(priority) = cookie_override.priority {
+            config.priority = Some(priority);
+        }
+
+        config
+    }
+}
 
 impl Default for SessionConfig {
     fn default() -> Self {
         Self {
+            cookie_name: default_cookie_name(),
+            xsrf_cookie_name: default_xsrf_cookie_name(),
+            legacy_cookie_names: Vec::new(),
+            accept_plaintext_cookies: false,
+            session_id_query_param: None,
             max_age: 3600,
+            lifetime: None,
+            cookie_refresh_threshold: default_cookie_refresh_threshold(),
             domain: None,
+            validate_domain: false,
             path: Some("/".to_string()),
-            same_site: SameSite::None,
+            path_strategy: PathStrategy::Configured,
+            same_site: SameSite::Lax,
             http_only: false,
+            secure: None,
+            priority: None,
+            store_policy: StorePolicy::Always,
+            expiration_policy: ExpirationPolicy::Sliding,
+            max_sessions_per_index: None,
+            max_new_sessions_per_ip: None,
+            new_session_window: default_new_session_window(),
+            transport: Transport::Cookie,
+            lottery: default_lottery(),
+            signing_key: None,
+            read_only: false,
+            require_activation: false,
+            clock_skew_tolerance: 0,
+            reclaim_grace: None,
+            on_store_error: StoreErrorPolicy::Optimistic,
+            peek_paths: Vec::new(),
+            key_prefix: default_key_prefix(),
+            storage_format: StorageFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SessionConfigBuilder {
+    config: SessionConfig,
+}
+
+impl SessionConfigBuilder {
+    pub fn cookie_name(mut self, cookie_name: impl Into<String>) -> Self {
+        self.config.cookie_name = cookie_name.into();
+        self
+    }
+
+    pub fn xsrf_cookie_name(mut self, xsrf_cookie_name: impl Into<String>) -> Self {
+        self.config.xsrf_cookie_name = xsrf_cookie_name.into();
+        self
+    }
+
+    pub fn legacy_cookie_name(mut self, legacy_cookie_name: impl Into<String>) -> Self {
+        self.config
+            .legacy_cookie_names
+            .push(legacy_cookie_name.into());
+        self
+    }
+
+    pub fn accept_plaintext_cookies(mut self, accept_plaintext_cookies: bool) -> Self {
+        self.config.accept_plaintext_cookies = accept_plaintext_cookies;
+        self
+    }
+
+    /// See [`SessionConfig::session_id_query_param`] for the risks before enabling this.
+    pub fn session_id_query_param(mut self, name: impl Into<String>) -> Self {
+        self.config.session_id_query_param = Some(name.into());
+        self
+    }
+
+    pub fn max_age(mut self, max_age: i32) -> Self {
+        self.config.max_age = max_age;
+        self
+    }
+
+    pub fn cookie_refresh_threshold(mut self, seconds: i64) -> Self {
+        self.config.cookie_refresh_threshold = seconds;
+        self
+    }
+
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.config.domain = Some(domain.into());
+        self
+    }
+
+    pub fn validate_domain(mut self, validate_domain: bool) -> Self {
+        self.config.validate_domain = validate_domain;
+        self
+    }
+
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.config.path = Some(path.into());
+        self
+    }
+
+    pub fn path_strategy(mut self, path_strategy: PathStrategy) -> Self {
+        self.config.path_strategy = path_strategy;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: impl Into<SameSite>) -> Self {
+        self.config.same_site = same_site.into();
+        self
+    }
+
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.config.http_only = http_only;
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.config.secure = Some(secure);
+        self
+    }
+
+    /// Copies the attributes set on `template` (`same_site`, `http_only`, `secure`, `path`,
+    /// `domain`, and `max_age`) into the config; its name and value are ignored. Setters called
+    /// afterwards override the template.
+    #[cfg(feature = "rocket")]
+    pub fn cookie_template(mut self, template: Cookie<'static>) -> Self {
+        if let Some(same_site) = template.same_site() {
+            self.config.same_site = same_site.into();
+        }
+
+        if let Some(http_only) = template.http_only() {
+            self.config.http_only = http_only;
+        }
+
+        if let Some(secure) = template.secure() {
+            self.config.secure = Some(secure);
+        }
+
+        if let Some(path) = template.path() {
+            self.config.path = Some(path.to_string());
+        }
+
+        if let Some(domain) = template.domain() {
+            self.config.domain = Some(domain.to_string());
+        }
+
+        if let Some(max_age) = template.max_age() {
+            let seconds = max_age.whole_seconds();
+            self.config.max_age = seconds.max(i32::MIN.into()).min(i32::MAX.into()) as i32;
+        }
+
+        self
+    }
+
+    pub fn priority(mut self, priority: CookiePriority) -> Self {
+        self.config.priority = Some(priority);
+        self
+    }
+
+    pub fn store_policy(mut self, store_policy: StorePolicy) -> Self {
+        self.config.store_policy = store_policy;
+        self
+    }
+
+    pub fn expiration_policy(mut self, expiration_policy: ExpirationPolicy) -> Self {
+        self.config.expiration_policy = expiration_policy;
+        self
+    }
+
+    pub fn max_sessions_per_index(mut self, max_sessions: usize) -> Self {
+        self.config.max_sessions_per_index = Some(max_sessions);
+        self
+    }
+
+    pub fn max_new_sessions_per_ip(mut self, limit: u32) -> Self {
+        self.config.max_new_sessions_per_ip = Some(limit);
+        self
+    }
+
+    pub fn new_session_window(mut self, seconds: i64) -> Self {
+        self.config.new_session_window = seconds;
+        self
+    }
+
+    pub fn transport(mut self, transport: Transport) -> Self {
+        self.config.transport = transport;
+        self
+    }
+
+    pub fn lottery(mut self, lottery: f64) -> Self {
+        self.config.lottery = lottery;
+        self
+    }
+
+    pub fn signing_key(mut self, signing_key: impl Into<String>) -> Self {
+        self.config.signing_key = Some(signing_key.into());
+        self
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.config.read_only = read_only;
+        self
+    }
+
+    pub fn require_activation(mut self, require_activation: bool) -> Self {
+        self.config.require_activation = require_activation;
+        self
+    }
+
+    pub fn clock_skew_tolerance(mut self, seconds: i64) -> Self {
+        self.config.clock_skew_tolerance = seconds;
+        self
+    }
+
+    pub fn reclaim_grace(mut self, seconds: i64) -> Self {
+        self.config.reclaim_grace = Some(seconds);
+        self
+    }
+
+    pub fn lifetime(mut self, lifetime: LifetimeConfig) -> Self {
+        self.config.lifetime = Some(lifetime);
+        self
+    }
+
+    pub fn on_store_error(mut self, on_store_error: StoreErrorPolicy) -> Self {
+        self.config.on_store_error = on_store_error;
+        self
+    }
+
+    pub fn peek_path(mut self, path: impl Into<String>) -> Self {
+        self.config.peek_paths.push(path.into());
+        self
+    }
+
+    pub fn key_prefix(mut self, key_prefix: impl Into<Cow<'static, str>>) -> Self {
+        self.config.key_prefix = key_prefix.into();
+        self
+    }
+
+    pub fn storage_format(mut self, storage_format: StorageFormat) -> Self {
+        self.config.storage_format = storage_format;
+        self
+    }
+
+    /// Builds the config, rejecting it with every problem [`SessionConfig::validate`] finds,
+    /// e.g. a `max_age` of zero or less, which would hand the client a new session on every
+    /// request.
+    pub fn try_finish(self) -> Result<SessionConfig, Vec<ConfigError>> {
+        self.config.validate()?;
+
+        Ok(self.config)
+    }
+}
+
+/// Cookie attributes a handler can override for the current response only, through
+/// [`Session::set_cookie_override`](crate::Session::set_cookie_override) or the
+/// [`RouteOverride`](crate::RouteOverride) guard.
+///
+/// Any attribute left as `None` falls back to the global [`SessionConfig`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CookieOverride {
+    pub max_age: Option<i32>,
+    pub domain: Option<String>,
+    pub path: Option<String>,
+    pub same_site: Option<SameSite>,
+    pub http_only: Option<bool>,
+    pub secure: Option<bool>,
+    pub priority: Option<CookiePriority>,
+}
+
+impl CookieOverride {
+    /// Returns this override with any attribute it leaves unset taken from `fallback`.
+    pub fn or(self, fallback: CookieOverride) -> Self {
+        Self {
+            max_age: self.max_age.or(fallback.max_age),
+            domain: self.domain.or(fallback.domain),
+            path: self.path.or(fallback.path),
+            same_site: self.same_site.or(fallback.same_site),
+            http_only: self.http_only.or(fallback.http_only),
+            secure: self.secure.or(fallback.secure),
+            priority: self.priority.or(fallback.priority),
         }
     }
 }
@@ -93,14 +963,42 @@ mod test {
             path = "/"
             same_site = "lax"
             http_only = true
+            priority = "high"
         "#;
 
         let expected_config = SessionConfig {
+            cookie_name: default_cookie_name(),
+            xsrf_cookie_name: default_xsrf_cookie_name(),
+            legacy_cookie_names: Vec::new(),
+            accept_plaintext_cookies: false,
+            session_id_query_param: None,
             max_age: 3600,
+            lifetime: None,
+            cookie_refresh_threshold: default_cookie_refresh_threshold(),
             domain: Some("example.local".to_string()),
+            validate_domain: false,
             path: Some("/".to_string()),
+            path_strategy: PathStrategy::Configured,
             same_site: SameSite::Lax,
             http_only: true,
+            secure: None,
+            priority: Some(CookiePriority::High),
+            store_policy: StorePolicy::Always,
+            expiration_policy: ExpirationPolicy::Sliding,
+            max_sessions_per_index: None,
+            max_new_sessions_per_ip: None,
+            new_session_window: default_new_session_window(),
+            transport: Transport::Cookie,
+            lottery: default_lottery(),
+            signing_key: None,
+            read_only: false,
+            require_activation: false,
+            clock_skew_tolerance: 0,
+            reclaim_grace: None,
+            on_store_error: StoreErrorPolicy::Optimistic,
+            peek_paths: Vec::new(),
+            key_prefix: default_key_prefix(),
+            storage_format: StorageFormat::Json,
         };
 
         let figment = Figment::from(Toml::string(input));
@@ -130,4 +1028,444 @@ mod test {
 
         assert!(deserialize_result.is_err());
     }
+
+    #[test]
+    fn override_takes_precedence_over_config() {
+        let config = SessionConfig {
+            same_site: SameSite::Lax,
+            ..SessionConfig::default()
+        };
+        let cookie_override = CookieOverride {
+            same_site: Some(SameSite::None),
+            ..CookieOverride::default()
+        };
+
+        let merged = config.with_override(&cookie_override);
+
+        assert_eq!(merged.same_site, SameSite::None);
+        assert_eq!(merged.max_age, config.max_age);
+        assert_eq!(merged.path, config.path);
+    }
+
+    #[test]
+    fn or_fills_unset_attributes_from_the_fallback() {
+        let session = CookieOverride {
+            max_age: Some(60),
+            ..CookieOverride::default()
+        };
+        let route = CookieOverride {
+            max_age: Some(600),
+            path: Some("/admin".to_string()),
+            ..CookieOverride::default()
+        };
+
+        let merged = session.or(route);
+        assert_eq!(merged.max_age, Some(60));
+        assert_eq!(merged.path.as_deref(), Some("/admin"));
+        assert_eq!(merged.same_site, None);
+    }
+
+    #[test]
+    fn builder_rejects_non_positive_max_age() {
+        for max_age in &[0, -1] {
+            let result = SessionConfig::builder().max_age(*max_age).try_finish();
+            assert_eq!(result, Err(vec![ConfigError::NonPositiveMaxAge(*max_age)]));
+        }
+
+        let config = SessionConfig::builder()
+            .max_age(60)
+            .same_site(SameSite::Lax)
+            .try_finish()
+            .unwrap();
+        assert_eq!(config.max_age, 60);
+        assert_eq!(config.same_site, SameSite::Lax);
+    }
+
+    #[test]
+    fn it_deserializes_a_header_transport() {
+        let input = r#"
+            [session]
+            max_age = 3600
+            same_site = "lax"
+            http_only = true
+            transport = { header = { name = "X-Session-Id" } }
+        "#;
+
+        let config: SessionConfig = Figment::from(Toml::string(input))
+            .extract_inner("session")
+            .unwrap();
+
+        assert_eq!(
+            config.transport,
+            Transport::Header {
+                name: "X-Session-Id".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn it_deserializes_a_bearer_transport() {
+        let input = r#"
+            [session]
+            max_age = 3600
+            same_site = "lax"
+            http_only = true
+            transport = { bearer = {} }
+        "#;
+
+        let config: SessionConfig = Figment::from(Toml::string(input))
+            .extract_inner("session")
+            .unwrap();
+
+        assert_eq!(
+            config.transport,
+            Transport::Bearer {
+                derivation: TokenDerivation::Hash
+            }
+        );
+    }
+
+    #[test]
+    fn bearer_tokens_derive_stable_session_ids() {
+        let hashed = TokenDerivation::Hash
+            .session_id("token with spaces!")
+            .unwrap();
+        assert_eq!(
+            TokenDerivation::Hash.session_id("token with spaces!"),
+            Some(hashed.clone())
+        );
+        assert!(!hashed.0.contains("token"));
+        assert!(SessionID::parse(&hashed.0).is_some());
+
+        assert_eq!(
+            TokenDerivation::Raw.session_id("abc123"),
+            Some(SessionID("abc123".to_string()))
+        );
+        assert_eq!(TokenDerivation::Raw.session_id("token with spaces!"), None);
+        assert_eq!(TokenDerivation::Hash.session_id(""), None);
+    }
+
+    #[test]
+    fn it_deserializes_a_storage_format() {
+        let input = r#"
+            [session]
+            max_age = 3600
+            same_site = "lax"
+            http_only = true
+            storage_format = "messagepack"
+        "#;
+
+        let config: SessionConfig = Figment::from(Toml::string(input))
+            .extract_inner("session")
+            .unwrap();
+
+        assert_eq!(config.storage_format, StorageFormat::MessagePack);
+    }
+
+    #[test]
+    fn it_deserializes_an_expiration_policy() {
+        let input = r#"
+            [session]
+            max_age = 3600
+            same_site = "lax"
+            http_only = true
+            expiration_policy = "fixed"
+        "#;
+
+        let config: SessionConfig = Figment::from(Toml::string(input))
+            .extract_inner("session")
+            .unwrap();
+
+        assert_eq!(config.expiration_policy, ExpirationPolicy::Fixed);
+        assert_eq!(
+            SessionConfig::default().expiration_policy,
+            ExpirationPolicy::Sliding
+        );
+    }
+
+    #[test]
+    fn profile_specific_values_win() {
+        let input = r#"
+            [default.session]
+            max_age = 3600
+            http_only = true
+            same_site = "lax"
+            secure = false
+
+            [release.session]
+            same_site = "none"
+            secure = true
+        "#;
+        let config = |profile: &str| {
+            Figment::from(Toml::string(input).nested())
+                .select(profile)
+                .extract_inner::<SessionConfig>("session")
+                .unwrap()
+        };
+
+        let debug = config("debug");
+        assert_eq!(debug.same_site, SameSite::Lax);
+        assert_eq!(debug.secure, Some(false));
+
+        let release = config("release");
+        assert_eq!(release.same_site, SameSite::None);
+        assert_eq!(release.secure, Some(true));
+        assert_eq!(release.max_age, 3600);
+    }
+
+    #[test]
+    fn it_deserializes_lottery_fractions_and_percentages() {
+        let lottery = |value: &str| {
+            let input = format!(
+                r#"
+                [session]
+                max_age = 3600
+                same_site = "lax"
+                http_only = true
+                lottery = {}
+                "#,
+                value
+            );
+
+            Figment::from(Toml::string(&input))
+                .extract_inner::<SessionConfig>("session")
+                .map(|config| config.lottery)
+                .map_err(Box::new)
+        };
+
+        assert_eq!(lottery("0.1").unwrap(), 0.1);
+        assert_eq!(lottery(r#""10%""#).unwrap(), 0.1);
+        assert!(lottery("1.5").is_err());
+        assert!(lottery("10").is_err());
+        assert!(lottery(r#""ten""#).is_err());
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        assert_eq!(SessionConfig::default().validate(), Ok(()));
+
+        let config = SessionConfig {
+            max_age: 0,
+            same_site: SameSite::None,
+            secure: Some(false),
+            cookie_name: "__Host-session".to_string(),
+            xsrf_cookie_name: "__Secure-xsrf".to_string(),
+            domain: Some("x".repeat(1025)),
+            path: Some("/app".to_string()),
+            ..SessionConfig::default()
+        };
+
+        assert_eq!(
+            config.validate(),
+            Err(vec![
+                ConfigError::NonPositiveMaxAge(0),
+                ConfigError::SameSiteNoneWithoutSecure,
+                ConfigError::PrefixRequiresSecure("__Host-session".to_string()),
+                ConfigError::HostPrefixWithDomain("__Host-session".to_string()),
+                ConfigError::HostPrefixRequiresRootPath("__Host-session".to_string()),
+                ConfigError::PrefixRequiresSecure("__Secure-xsrf".to_string()),
+                ConfigError::AttributeTooLong {
+                    attribute: "domain",
+                    len: 1025,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn max_age_and_reclaim_grace_amount_to_a_lifetime() {
+        let config = SessionConfig::builder()
+            .max_age(900)
+            .reclaim_grace(60)
+            .try_finish()
+            .unwrap();
+
+        assert_eq!(
+            config.lifetime(),
+            LifetimeConfig {
+                idle_timeout: 900,
+                absolute_timeout: None,
+                cookie_max_age: None,
+                grace: 60,
+            }
+        );
+    }
+
+    #[test]
+    fn a_consistent_lifetime_validates() {
+        let lifetime = LifetimeConfig {
+            idle_timeout: 900,
+            absolute_timeout: Some(8 * 3600),
+            cookie_max_age: Some(1200),
+            grace: 300,
+        };
+        let config = SessionConfig::builder()
+            .same_site(SameSite::Lax)
+            .lifetime(lifetime)
+            .try_finish()
+            .unwrap();
+
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.lifetime(), lifetime);
+        assert_eq!(config.reclaim_grace, None);
+    }
+
+    #[test]
+    fn contradictory_lifetimes_are_reported() {
+        let lifetime = LifetimeConfig {
+            idle_timeout: 7200,
+            absolute_timeout: Some(3600),
+            cookie_max_age: Some(60),
+            grace: -1,
+        };
+        let config = SessionConfig {
+            same_site: SameSite::Lax,
+            lifetime: Some(lifetime),
+            reclaim_grace: Some(30),
+            expiration_policy: ExpirationPolicy::Fixed,
+            ..SessionConfig::default()
+        };
+
+        assert_eq!(
+            config.validate(),
+            Err(vec![
+                ConfigError::IdleTimeoutExceedsAbsolute {
+                    idle: 7200,
+                    absolute: 3600,
+                },
+                ConfigError::CookieMaxAgeBelowIdleTimeout {
+                    cookie_max_age: 60,
+                    idle: 7200,
+                },
+                ConfigError::NegativeGrace(-1),
+                ConfigError::LifetimeOverlap("reclaim_grace"),
+                ConfigError::LifetimeOverlap("expiration_policy"),
+            ])
+        );
+        assert_eq!(
+            SessionConfig::builder()
+                .lifetime(LifetimeConfig {
+                    idle_timeout: 0,
+                    grace: -1,
+                    ..LifetimeConfig::default()
+                })
+                .try_finish(),
+            Err(vec![
+                ConfigError::NonPositiveIdleTimeout(0),
+                ConfigError::NegativeGrace(-1),
+            ])
+        );
+    }
+
+    #[test]
+    fn validate_ignores_cookie_attributes_without_cookies() {
+        let config = SessionConfig {
+            transport: Transport::Header {
+                name: "X-Session-Id".to_string(),
+            },
+            ..SessionConfig::default()
+        };
+
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn builder_rejects_what_validate_reports() {
+        assert_eq!(
+            SessionConfig::builder()
+                .same_site(SameSite::None)
+                .try_finish(),
+            Err(vec![ConfigError::SameSiteNoneWithoutSecure])
+        );
+        assert!(SessionConfig::builder()
+            .same_site(SameSite::None)
+            .secure(true)
+            .try_finish()
+            .is_ok());
+    }
+
+    #[test]
+    fn builder_rejects_out_of_range_lottery() {
+        let result = SessionConfig::builder().lottery(1.5).try_finish();
+        assert_eq!(result, Err(vec![ConfigError::LotteryOutOfRange(1.5)]));
+
+        let config = SessionConfig::builder().lottery(0.1).try_finish().unwrap();
+        assert_eq!(config.lottery, 0.1);
+    }
+
+    #[test]
+    #[cfg(feature = "rocket")]
+    fn same_site_agrees_across_builder_deserializer_and_cookie() {
+        let cases = [
+            ("strict", SameSite::Strict, RSameSite::Strict),
+            ("lax", SameSite::Lax, RSameSite::Lax),
+            ("none", SameSite::None, RSameSite::None),
+        ];
+
+        for (name, same_site, rocket_same_site) in cases.iter().cloned() {
+            let input = format!(
+                "[session]\nmax_age = 3600\nhttp_only = true\nsame_site = \"{}\"",
+                name
+            );
+            let deserialized: SessionConfig = Figment::from(Toml::string(&input))
+                .extract_inner("session")
+                .unwrap();
+            let built = SessionConfig::builder()
+                .same_site(same_site)
+                .try_finish()
+                .unwrap();
+            let built_from_rocket = SessionConfig::builder()
+                .same_site(rocket_same_site)
+                .try_finish()
+                .unwrap();
+
+            assert_eq!(deserialized.same_site, same_site);
+            assert_eq!(built.same_site, same_site);
+            assert_eq!(built_from_rocket.same_site, same_site);
+            assert_eq!(RSameSite::from(same_site), rocket_same_site);
+
+            let cookie = crate::util::make_cookie(&deserialized, "session_id", String::new());
+            assert_eq!(cookie.same_site(), Some(rocket_same_site));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rocket")]
+    fn builder_takes_attributes_from_a_cookie_template() {
+        let template = Cookie::build("ignored", "")
+            .same_site(RSameSite::Strict)
+            .http_only(true)
+            .secure(true)
+            .path("/app")
+            .domain("example.local")
+            .max_age(time::Duration::minutes(30))
+            .finish();
+
+        let config = SessionConfig::builder()
+            .cookie_template(template)
+            .path("/override")
+            .try_finish()
+            .unwrap();
+
+        assert_eq!(config.same_site, SameSite::Strict);
+        assert!(config.http_only);
+        assert_eq!(config.secure, Some(true));
+        assert_eq!(config.path, Some("/override".to_string()));
+        assert_eq!(config.domain, Some("example.local".to_string()));
+        assert_eq!(config.max_age, 1800);
+    }
+
+    #[test]
+    #[cfg(feature = "rocket")]
+    fn cookie_template_clamps_an_out_of_range_max_age() {
+        let template = Cookie::build("ignored", "")
+            .max_age(time::Duration::weeks(100_000))
+            .finish();
+
+        let config = SessionConfig::builder()
+            .cookie_template(template)
+            .try_finish()
+            .unwrap();
+        assert_eq!(config.max_age, i32::MAX);
+    }
 }
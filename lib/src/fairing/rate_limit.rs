@@ -0,0 +1,85 @@
+use chrono::{DateTime, Duration, Utc};
+use std::{collections::HashMap, net::IpAddr, sync::Mutex};
+
+use crate::clock;
+
+/// The most client addresses tracked at once, so a flood from many addresses can't grow the
+/// limiter without bound.
+const MAX_TRACKED_IPS: usize = 10_000;
+
+/// Counts the sessions created per client IP in fixed windows, for
+/// `SessionConfig::max_new_sessions_per_ip`.
+pub(crate) struct CreationLimiter {
+    limit: u32,
+    window: Duration,
+    windows: Mutex<HashMap<IpAddr, (DateTime<Utc>, u32)>>,
+}
+
+impl CreationLimiter {
+    pub(crate) fn new(limit: u32, window: i64) -> Self {
+        Self {
+            limit,
+            window: Duration::seconds(window),
+            windows: Default::default(),
+        }
+    }
+
+    /// Records a session created for `ip`, or returns `false` if `ip` has already created its
+    /// limit of sessions in the current window.
+    pub(crate) fn allow(&self, ip: IpAddr) -> bool {
+        let now = clock::now();
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= MAX_TRACKED_IPS && !windows.contains_key(&ip) {
+            let window = self.window;
+            windows.retain(|_, (start, _)| now - *start < window);
+            if windows.len() >= MAX_TRACKED_IPS {
+                // more addresses than can be tracked are creating sessions; start over rather
+                // than refuse every new address
+                windows.clear();
+            }
+        }
+
+        let (start, count) = windows.entry(ip).or_insert((now, 0));
+        if now - *start >= self.window {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= self.limit {
+            return false;
+        }
+
+        *count += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::mock;
+
+    #[test]
+    fn it_throttles_each_ip_within_a_window() {
+        let limiter = CreationLimiter::new(2, 60);
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let other: IpAddr = "203.0.113.8".parse().unwrap();
+
+        assert!(limiter.allow(ip));
+        assert!(limiter.allow(ip));
+        assert!(!limiter.allow(ip));
+        assert!(limiter.allow(other));
+
+        mock::advance(60);
+        assert!(limiter.allow(ip));
+    }
+
+    #[test]
+    fn it_tracks_a_bounded_number_of_ips() {
+        let limiter = CreationLimiter::new(1, 60);
+        for n in 0..MAX_TRACKED_IPS as u32 + 1 {
+            assert!(limiter.allow(IpAddr::from(n.to_be_bytes())));
+        }
+
+        assert!(limiter.windows.lock().unwrap().len() <= MAX_TRACKED_IPS);
+    }
+}
@@ -0,0 +1,1179 @@
+use std::{
+    convert::TryFrom,
+    io::Cursor,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use log::{error, warn};
+use rand::Rng;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    figment,
+    http::Status,
+    Data, Request, Response, Rocket,
+};
+
+use crate::{
+    client_cert::ClientCertSource,
+    codec::{CookieCodec, HmacCodec},
+    factory::{DataFactory, DefaultData},
+    fairing::config::{
+        CookieOverride, ExpirationPolicy, PathStrategy, SessionConfig, StoreErrorPolicy,
+        StorePolicy, TokenDerivation, Transport,
+    },
+    fairing::rate_limit::CreationLimiter,
+    id::{SessionIdGenerator, ThreadRngGenerator},
+    observer::SessionObserver,
+    session::{Peeked, RouteCookieOverride, SessionCached},
+    store::SessionStore,
+    transform::DataTransform,
+    util::{
+        common_path_prefix, host_within_domain, make_cookie, make_expired_cookie, path_within,
+        private_cookie_exists, set_cookie_header, take_set_cookie,
+    },
+    Session, SessionID,
+};
+
+/// A cookie that scripts can read, set alongside the `HttpOnly` session cookie with a value
+/// derived from the session, e.g. a "signed in" flag for a single-page app or the CSRF token for
+/// it to echo in a header. See [`SessionFairingBuilder::companion_cookie`].
+///
+/// The companion is neither encrypted nor signed, and any script on the page can read it, so it
+/// must never carry secrets (least of all the session ID), and the server must never trust it:
+/// it's only a hint for client-side code, while the session cookie stays authoritative.
+pub struct CompanionCookie<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    name: String,
+    value: Arc<dyn Fn(&Session<Data>) -> Option<String> + Send + Sync>,
+    attributes: CookieOverride,
+}
+
+impl<Data> CompanionCookie<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    /// A companion named `name` holding `value(session)`, or removed from the client when that's
+    /// `None`. It has the session cookie's attributes, except that it's never `HttpOnly`.
+    pub fn new(
+        name: impl Into<String>,
+        value: impl Fn(&Session<Data>) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            value: Arc::new(value),
+            attributes: CookieOverride::default(),
+        }
+    }
+
+    /// Gives the companion attributes of its own in place of the session cookie's. Its
+    /// `http_only` is ignored.
+    pub fn with_attributes(mut self, attributes: CookieOverride) -> Self {
+        self.attributes = attributes;
+        self
+    }
+}
+
+/// A session ID read from one of the client's session cookies.
+struct IncomingCookie<'c> {
+    session_id: SessionID,
+    /// The name of the cookie, `cookie_name` or one of the `legacy_cookie_names`.
+    name: &'c str,
+    /// Read from a plaintext cookie under `accept_plaintext_cookies`.
+    plaintext: bool,
+}
+
+/// Records in the request's local cache that the client's session failed to load, so the
+/// response can apply `on_store_error` instead of replacing a session that may still exist.
+/// Keyed by the session data type, like the cached session, so side-by-side fairings each get
+/// their own.
+struct LoadFailed<Data>(AtomicBool, PhantomData<fn() -> Data>);
+
+impl<Data: Send + Sync + 'static> LoadFailed<Data> {
+    fn slot(req: &Request<'_>) -> &AtomicBool {
+        &req.local_cache(|| Self(AtomicBool::new(false), PhantomData))
+            .0
+    }
+}
+
+/// Loads the request's session before the request is routed and stores it once the response is
+/// ready.
+///
+/// Attach it before any fairing that reads the session in its own `on_request`: Rocket runs
+/// request fairings in the order they were attached, so a fairing attached earlier sees no
+/// session yet.
+pub struct SessionFairing<Store: SessionStore> {
+    config: Option<SessionConfig>,
+    codec: Option<Arc<dyn CookieCodec>>,
+    id_generator: Option<Arc<dyn SessionIdGenerator>>,
+    observer: Option<Arc<dyn SessionObserver>>,
+    client_cert_source: Option<Arc<dyn ClientCertSource>>,
+    companion_cookie: Option<CompanionCookie<Store::SessionData>>,
+    transform: Option<Arc<dyn DataTransform<Store::SessionData>>>,
+    data_factory: Arc<dyn DataFactory<Store::SessionData>>,
+    strict: bool,
+    store: Mutex<Option<Store>>,
+    /// Set when another fairing for the same store or session data type was attached first, in
+    /// which case this one steps aside.
+    duplicate: AtomicBool,
+}
+
+/// Builds a [`SessionFairing`] with any combination of options; see [`SessionFairing::builder`].
+pub struct SessionFairingBuilder<Store: SessionStore> {
+    config: Option<SessionConfig>,
+    codec: Option<Arc<dyn CookieCodec>>,
+    id_generator: Option<Arc<dyn SessionIdGenerator>>,
+    observer: Option<Arc<dyn SessionObserver>>,
+    client_cert_source: Option<Arc<dyn ClientCertSource>>,
+    companion_cookie: Option<CompanionCookie<Store::SessionData>>,
+    transform: Option<Arc<dyn DataTransform<Store::SessionData>>>,
+    data_factory: Arc<dyn DataFactory<Store::SessionData>>,
+    strict: bool,
+    store: Option<Store>,
+}
+
+impl<Store: SessionStore> SessionFairingBuilder<Store> {
+    /// Uses `config` instead of extracting the `session` table from Rocket's figment.
+    pub fn config(mut self, config: SessionConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Uses `store` instead of one created with `Store::init()`.
+    pub fn store(mut self, store: Store) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Encodes the session cookie's value with `codec`. Takes precedence over the config's
+    /// `signing_key`.
+    pub fn codec(mut self, codec: impl CookieCodec) -> Self {
+        self.codec = Some(Arc::new(codec));
+        self
+    }
+
+    /// Generates session IDs and CSRF tokens with `generator` instead of `rand::thread_rng()`.
+    pub fn id_generator(mut self, generator: impl SessionIdGenerator) -> Self {
+        self.id_generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// Notifies `observer` as sessions are created and destroyed.
+    pub fn observer(mut self, observer: impl SessionObserver) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Binds new sessions to the client certificate reported by `source`, and treats a request
+    /// presenting a different certificate (or none) as having no session. Sessions started
+    /// without a certificate stay unbound until a request presents one.
+    pub fn client_cert_source(mut self, source: impl ClientCertSource) -> Self {
+        self.client_cert_source = Some(Arc::new(source));
+        self
+    }
+
+    /// Sets `companion` alongside the session cookie, re-issuing it whenever its value changes
+    /// and removing it along with the session cookie.
+    pub fn companion_cookie(mut self, companion: CompanionCookie<Store::SessionData>) -> Self {
+        self.companion_cookie = Some(companion);
+        self
+    }
+
+    /// Runs `transform`'s hooks on session data as it's loaded from and stored to the store.
+    pub fn transform(mut self, transform: impl DataTransform<Store::SessionData>) -> Self {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Creates the data of new sessions with `factory` instead of `Default::default()`.
+    pub fn data_factory(mut self, factory: impl DataFactory<Store::SessionData>) -> Self {
+        self.data_factory = Arc::new(factory);
+        self
+    }
+
+    /// Refuses to launch when the config fails [`SessionConfig::validate`], or when another
+    /// fairing for the same store or session data type is already attached, rather than only
+    /// logging the problems.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    pub fn build(self) -> SessionFairing<Store> {
+        SessionFairing {
+            config: self.config,
+            codec: self.codec,
+            id_generator: self.id_generator,
+            observer: self.observer,
+            client_cert_source: self.client_cert_source,
+            companion_cookie: self.companion_cookie,
+            transform: self.transform,
+            data_factory: self.data_factory,
+            strict: self.strict,
+            store: Mutex::new(self.store),
+            duplicate: AtomicBool::new(false),
+        }
+    }
+}
+
+/// The config and cookie codec of the fairing managing sessions of type `Data`, kept in managed
+/// state. Keyed by the data type so fairings for different session types can be attached to the
+/// same app.
+pub struct SessionState<Data> {
+    pub config: SessionConfig,
+    codec: Option<Arc<dyn CookieCodec>>,
+    pub(crate) id_generator: Arc<dyn SessionIdGenerator>,
+    client_cert_source: Option<Arc<dyn ClientCertSource>>,
+    transform: Option<Arc<dyn DataTransform<Data>>>,
+    pub(crate) data_factory: Arc<dyn DataFactory<Data>>,
+    creation_limiter: Option<CreationLimiter>,
+    data: PhantomData<fn() -> Data>,
+}
+
+impl<Store: SessionStore> SessionFairing<Store>
+where
+    Store::SessionData: Default,
+{
+    pub fn init() -> Self {
+        Self::builder().build()
+    }
+
+    /// Like [`SessionFairing::init`], but reads the `session` table from Rocket's default figment
+    /// (`Rocket.toml` and `ROCKET_` environment variables, as used by `rocket::ignite()`) up
+    /// front, returning the error if it's missing or invalid instead of failing when attached.
+    /// The fairing is [strict](SessionFairingBuilder::strict).
+    pub fn init_strict() -> Result<Self, figment::Error> {
+        let config = rocket::Config::figment().extract_inner::<SessionConfig>("session")?;
+
+        Ok(Self::builder().config(config).strict().build())
+    }
+
+    pub fn with_config(config: SessionConfig) -> Self {
+        Self::builder().config(config).build()
+    }
+
+    pub fn builder() -> SessionFairingBuilder<Store> {
+        Self::builder_with_factory(DefaultData)
+    }
+}
+
+impl<Store: SessionStore> SessionFairing<Store> {
+    /// Like [`SessionFairing::builder`], but creates the data of new sessions with `factory`, for
+    /// session data types without a `Default` impl.
+    pub fn builder_with_factory(
+        factory: impl DataFactory<Store::SessionData>,
+    ) -> SessionFairingBuilder<Store> {
+        SessionFairingBuilder {
+            config: None,
+            codec: None,
+            id_generator: None,
+            observer: None,
+            client_cert_source: None,
+            companion_cookie: None,
+            transform: None,
+            data_factory: Arc::new(factory),
+            strict: false,
+            store: None,
+        }
+    }
+
+    /// Encodes the session cookie's value with `codec`. Takes precedence over the config's
+    /// `signing_key`.
+    pub fn with_codec(mut self, codec: impl CookieCodec) -> Self {
+        self.codec = Some(Arc::new(codec));
+        self
+    }
+
+    /// Uses `store` instead of one created with `Store::init()`, e.g. to configure it first.
+    pub fn with_store(self, store: Store) -> Self {
+        *self.store.lock().unwrap() = Some(store);
+        self
+    }
+
+    /// Generates session IDs and CSRF tokens with `generator` instead of `rand::thread_rng()`.
+    pub fn with_id_generator(mut self, generator: impl SessionIdGenerator) -> Self {
+        self.id_generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// The store and state put in managed state by `on_attach`. If they're missing, e.g. because
+    /// the fairing's callbacks are invoked without it having been attached, logs how to fix it
+    /// and returns `None`, so the request goes ahead without session handling.
+    fn managed<'r>(
+        req: &'r Request<'_>,
+    ) -> Option<(&'r Store, &'r SessionState<Store::SessionData>)> {
+        let store = req.managed_state::<Box<Store>>();
+        let state = req.managed_state::<SessionState<Store::SessionData>>();
+        match (store, state) {
+            (Some(store), Some(state)) => Some((&**store, state)),
+            _ => {
+                error!(
+                    "no session store of type `{store}` is managed, so the session fairing \
+                    can't handle this request; attach it with \
+                    `.attach(SessionFairing::<{store}>::init())` before any fairing that uses \
+                    the session",
+                    store = std::any::type_name::<Store>()
+                );
+                None
+            }
+        }
+    }
+
+    /// Reads the session ID from the session cookie or, failing that, the first legacy cookie
+    /// that holds a well-formed one (see [`SessionID::parse`]).
+    fn session_id_from_cookie<'c>(
+        req: &Request<'_>,
+        config: &'c SessionConfig,
+        codec: Option<&dyn CookieCodec>,
+    ) -> Option<IncomingCookie<'c>> {
+        let jar = req.cookies();
+        std::iter::once(&config.cookie_name)
+            .chain(&config.legacy_cookie_names)
+            .find_map(|name| {
+                let (value, plaintext) = match codec {
+                    Some(codec) => (
+                        jar.get(name)
+                            .and_then(|cookie| codec.decode(cookie.value())),
+                        false,
+                    ),
+                    None => match jar.get_private(name) {
+                        Some(cookie) => (Some(cookie.value().to_string()), false),
+                        None if config.accept_plaintext_cookies => {
+                            (jar.get(name).map(|cookie| cookie.value().to_string()), true)
+                        }
+                        None => (None, false),
+                    },
+                };
+
+                value
+                    .and_then(|value| SessionID::parse(&value))
+                    .map(|session_id| IncomingCookie {
+                        session_id,
+                        name: name.as_str(),
+                        plaintext,
+                    })
+            })
+    }
+
+    fn session_id_from_header(req: &Request<'_>, name: &str) -> Option<SessionID> {
+        req.headers().get_one(name).and_then(SessionID::parse)
+    }
+
+    fn session_id_from_bearer(req: &Request<'_>, derivation: TokenDerivation) -> Option<SessionID> {
+        let authorization = req.headers().get_one("Authorization")?;
+        let mut parts = authorization.splitn(2, ' ');
+        match (parts.next(), parts.next()) {
+            (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") => {
+                derivation.session_id(token.trim())
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads the session ID from the `session_id_query_param`, if one is configured. Session IDs
+    /// are alphanumeric, so the value is used as is rather than percent-decoded.
+    fn session_id_from_query(req: &Request<'_>, config: &SessionConfig) -> Option<SessionID> {
+        let name = config.session_id_query_param.as_deref()?;
+        req.uri()
+            .query()?
+            .split('&')
+            .find_map(|pair| {
+                let mut pair = pair.splitn(2, '=');
+                match (pair.next(), pair.next()) {
+                    (Some(key), Some(value)) if key == name => Some(value),
+                    _ => None,
+                }
+            })
+            .and_then(SessionID::parse)
+    }
+
+    /// Resumes the session identified by `session_id`, renewing it, or starts a new one if
+    /// there's no such session or it has expired.
+    ///
+    /// Under `StorePolicy::OnChange` the renewal is persisted right away with
+    /// `SessionStore::load_and_renew`, since an unchanged session isn't written back; otherwise
+    /// it's renewed locally and persisted when the session is stored. Under
+    /// `ExpirationPolicy::Fixed` it isn't renewed at all. A session expired within the
+    /// reclaim grace is renewed like any other, while one past the absolute timeout is treated as
+    /// expired. A session that fails to load is treated as missing (see [`Self::loaded`]).
+    async fn init_session(
+        req: &Request<'_>,
+        store: &Store,
+        state: &SessionState<Store::SessionData>,
+        session_id: Option<SessionID>,
+    ) -> Session<Store::SessionData> {
+        let config = &state.config;
+        let lifetime = config.lifetime();
+        let lifespan = lifetime.idle_timeout;
+        let tolerance = config.clock_skew_tolerance;
+        let session = match session_id {
+            Some(session_id)
+                if config.read_only || config.expiration_policy == ExpirationPolicy::Fixed =>
+            {
+                let loaded = store.load(session_id.clone()).await;
+                Self::loaded(req, &session_id, loaded)
+                    .and_then(|session| session.validate(tolerance))
+            }
+            Some(session_id) if config.store_policy == StorePolicy::OnChange => {
+                let loaded = store
+                    .load_and_renew(&session_id, lifespan, config.reclaim_tolerance())
+                    .await;
+                Self::loaded(req, &session_id, loaded)
+            }
+            Some(session_id) => {
+                let loaded = store.load(session_id.clone()).await;
+                Self::loaded(req, &session_id, loaded).and_then(|session| {
+                    let lifespan = session.lifespan().unwrap_or(lifespan);
+                    session.extend_if_valid(lifespan, config.reclaim_tolerance())
+                })
+            }
+            None => None,
+        };
+
+        let client_cert = Self::client_cert(req, state);
+        let mut session = session
+            .filter(|session| !lifetime.past_absolute_timeout(session.created()))
+            .filter(|session| Self::client_cert_matches(session, client_cert.as_ref()))
+            .map(|session| Self::transform_on_load(state, session))
+            .unwrap_or_else(|| Self::new_session(req, state));
+        if let Some(Some(fingerprint)) = client_cert {
+            if session.client_cert().is_none() && !config.read_only {
+                session.bind_client_cert(fingerprint);
+            }
+        }
+
+        session
+    }
+
+    /// The session a load returned, or `None` if the load failed, logging the error and recording
+    /// it for `on_response`.
+    fn loaded(
+        req: &Request<'_>,
+        session_id: &SessionID,
+        loaded: anyhow::Result<Option<Session<Store::SessionData>>>,
+    ) -> Option<Session<Store::SessionData>> {
+        loaded.unwrap_or_else(|error| {
+            warn!(
+                "failed to load session {}: {}",
+                session_id.redacted(),
+                error
+            );
+            LoadFailed::<Store::SessionData>::slot(req).store(true, Ordering::SeqCst);
+
+            None
+        })
+    }
+
+    /// Starts a new session, or a throwaway one if the client IP is over its
+    /// `max_new_sessions_per_ip`.
+    fn new_session(
+        req: &Request<'_>,
+        state: &SessionState<Store::SessionData>,
+    ) -> Session<Store::SessionData> {
+        let config = &state.config;
+        let mut session = Session::with_generator_and_data(
+            config.lifetime().idle_timeout,
+            &*state.id_generator,
+            state.data_factory.new_data(),
+        );
+        if let (Some(limiter), Some(ip)) = (&state.creation_limiter, req.client_ip()) {
+            if !config.read_only && !limiter.allow(ip) {
+                warn!(
+                    "client {} is creating sessions too quickly; serving it a throwaway session",
+                    ip
+                );
+                session.make_ephemeral();
+            }
+        }
+
+        session
+    }
+
+    /// The fingerprint of the request's client certificate, or `None` if no
+    /// [`ClientCertSource`] is configured.
+    fn client_cert(
+        req: &Request<'_>,
+        state: &SessionState<Store::SessionData>,
+    ) -> Option<Option<String>> {
+        state
+            .client_cert_source
+            .as_ref()
+            .map(|source| source.fingerprint(req))
+    }
+
+    /// Whether the session may be used by a request presenting `client_cert` (see
+    /// [`Self::client_cert`]): always if the session is unbound or no source is configured.
+    fn client_cert_matches(
+        session: &Session<Store::SessionData>,
+        client_cert: Option<&Option<String>>,
+    ) -> bool {
+        match (session.client_cert(), client_cert) {
+            (Some(bound), Some(presented)) if presented.as_deref() != Some(bound) => {
+                warn!(
+                    "session {} is bound to a different client certificate; starting a new session",
+                    session.id().redacted()
+                );
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether the request's `Host` falls under the configured cookie domain, or `true` if
+    /// `validate_domain` isn't set.
+    fn host_matches_domain(req: &Request<'_>, config: &SessionConfig) -> bool {
+        match &config.domain {
+            Some(domain) if config.validate_domain => req
+                .headers()
+                .get_one("Host")
+                .map_or(false, |host| host_within_domain(host, domain)),
+            _ => true,
+        }
+    }
+
+    fn is_peek(req: &Request<'_>, config: &SessionConfig) -> bool {
+        let path = req.uri().path();
+        config
+            .peek_paths
+            .iter()
+            .any(|prefix| path_within(path, prefix))
+    }
+
+    /// Loads the request's session, if it has a valid one, without renewing it.
+    async fn peek_session(
+        req: &Request<'_>,
+        store: &Store,
+        state: &SessionState<Store::SessionData>,
+    ) -> Option<Session<Store::SessionData>> {
+        let config = &state.config;
+        let session_id = match &config.transport {
+            Transport::Cookie => Self::session_id_from_cookie(req, config, state.codec.as_deref())
+                .map(|incoming| incoming.session_id)
+                .or_else(|| Self::session_id_from_query(req, config)),
+            Transport::Header { name } => Self::session_id_from_header(req, name),
+            Transport::Bearer { derivation } => Self::session_id_from_bearer(req, *derivation),
+        }?;
+
+        let client_cert = Self::client_cert(req, state);
+        match store.load(session_id).await {
+            Ok(session) => session
+                .filter(|session| Self::client_cert_matches(session, client_cert.as_ref()))?
+                .validate(config.clock_skew_tolerance)
+                .map(|session| Self::transform_on_load(state, session)),
+            Err(error) => {
+                warn!("failed to peek at session: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Applies the configured [`DataTransform::on_load`] to a session loaded from the store.
+    fn transform_on_load(
+        state: &SessionState<Store::SessionData>,
+        session: Session<Store::SessionData>,
+    ) -> Session<Store::SessionData> {
+        if let Some(transform) = &state.transform {
+            session.transform(|data| transform.on_load(data));
+        }
+
+        session
+    }
+
+    /// Whether the session's expiration has drifted from that of the client's cookie by more
+    /// than `cookie_refresh_threshold`, e.g. because renewals have slid it forward, so the
+    /// cookie would expire before the session (or long after it) unless it's re-issued.
+    fn cookie_outdated(config: &SessionConfig, session: &Session<Store::SessionData>) -> bool {
+        session
+            .cookie_expiration()
+            .map_or(true, |cookie_expiration| {
+                let drift = session.expiration() - cookie_expiration;
+                drift.num_seconds().abs() > config.cookie_refresh_threshold
+            })
+    }
+
+    fn add_cookies(
+        req: &Request<'_>,
+        config: &SessionConfig,
+        codec: Option<&dyn CookieCodec>,
+        session: &mut Session<Store::SessionData>,
+        incoming: Option<&IncomingCookie<'_>>,
+    ) {
+        // a non-positive idle timeout means the session is already expired, so setting a cookie
+        // would only hand the client a new session on every request
+        if config.lifetime().idle_timeout <= 0 || config.read_only || session.is_ephemeral() {
+            return;
+        }
+
+        if !Self::host_matches_domain(req, config) {
+            warn!(
+                "request host {:?} is outside the session cookie domain {:?}; not setting \
+                session cookies",
+                req.headers().get_one("Host"),
+                config.domain
+            );
+            return;
+        }
+
+        let cookie_name = &config.cookie_name;
+        let xsrf_cookie_name = &config.xsrf_cookie_name;
+        let jar = req.cookies();
+        // (re-)issue the session cookie unless the client already holds an up-to-date one for
+        // this session
+        let current = incoming.map_or(false, |incoming| {
+            incoming.session_id == *session.id()
+                && incoming.name == cookie_name
+                && !incoming.plaintext
+        });
+        if !current || Self::cookie_outdated(config, session) {
+            let id = session.id().to_string();
+            match codec {
+                Some(codec) => jar.add(make_cookie(config, cookie_name, codec.encode(&id))),
+                None => jar.add_private(make_cookie(config, cookie_name, id)),
+            }
+            session.cookie_issued();
+        }
+
+        // a session read from a legacy cookie has just been re-issued under the current name
+        if let Some(incoming) = incoming.filter(|incoming| incoming.name != cookie_name) {
+            let cookie = make_cookie(config, incoming.name, String::new());
+            match codec {
+                Some(_) => jar.remove(cookie),
+                None => jar.remove_private(cookie),
+            }
+        }
+
+        if !private_cookie_exists(jar, xsrf_cookie_name) {
+            jar.add_private(make_cookie(
+                config,
+                xsrf_cookie_name,
+                session.csrf_token().to_string(),
+            ));
+        }
+    }
+
+    fn finish_cookies<'r>(
+        req: &'r Request<'_>,
+        res: &mut Response<'r>,
+        config: &SessionConfig,
+        session: &Session<Store::SessionData>,
+    ) {
+        // A handler-provided override or session lifespan takes precedence over the global
+        // config, so re-issue the session cookie with the merged attributes. The (already
+        // encrypted) value is reused from the cookie set by this response or, failing that, the
+        // one sent by the client, unless the handler set its own. Attributes the cookie jar
+        // can't express (i.e. `Priority`) are applied the same way.
+        if !Self::host_matches_domain(req, config) {
+            return;
+        }
+
+        let cookie_override = Self::cookie_override(req, session);
+        let lifespan = session.lifespan();
+        let value = session.cookie_value_override();
+        let overridden = cookie_override.is_some() || lifespan.is_some() || value.is_some();
+        if overridden || config.priority.is_some() {
+            let mut config = config.clone();
+            if let Some(lifespan) = lifespan {
+                config.set_cookie_max_age(i32::try_from(lifespan).unwrap_or(i32::MAX));
+            }
+            if let Some(cookie_override) = &cookie_override {
+                config = config.with_override(cookie_override);
+            }
+            let cookie_name = config.cookie_name.as_str();
+            let mut cookie = take_set_cookie(res, cookie_name);
+            if cookie.is_none() && overridden {
+                cookie = req.cookies().get(cookie_name).cloned();
+            }
+
+            if let Some(value) = value.or_else(|| cookie.map(|cookie| cookie.value().to_string())) {
+                let cookie = make_cookie(&config, cookie_name, value);
+                res.adjoin_raw_header("Set-Cookie", set_cookie_header(&config, &cookie));
+            }
+        }
+    }
+
+    /// The session's cookie override layered over the route's (see `RouteOverride`), if either
+    /// is set.
+    fn cookie_override(
+        req: &Request<'_>,
+        session: &Session<Store::SessionData>,
+    ) -> Option<CookieOverride> {
+        let route = req.local_cache(RouteCookieOverride::default).get();
+        match (session.cookie_override(), route) {
+            (Some(session), Some(route)) => Some(session.or(route)),
+            (session, route) => session.or(route),
+        }
+    }
+
+    /// Replaces any session cookies set by this response with ones telling the client to delete
+    /// them.
+    fn expire_cookies(
+        &self,
+        req: &Request<'_>,
+        res: &mut Response<'_>,
+        config: &SessionConfig,
+        session: &Session<Store::SessionData>,
+    ) {
+        let config = match Self::cookie_override(req, session) {
+            Some(cookie_override) => config.with_override(&cookie_override),
+            None => config.clone(),
+        };
+
+        for name in &[&config.cookie_name, &config.xsrf_cookie_name] {
+            take_set_cookie(res, name);
+            let cookie = make_expired_cookie(&config, name);
+            res.adjoin_raw_header("Set-Cookie", set_cookie_header(&config, &cookie));
+        }
+
+        if let Some(companion) = &self.companion_cookie {
+            if req.cookies().get(&companion.name).is_some() {
+                let config = config.with_override(&companion.attributes);
+                let cookie = make_expired_cookie(&config, &companion.name);
+                res.adjoin_raw_header("Set-Cookie", set_cookie_header(&config, &cookie));
+            }
+        }
+    }
+
+    /// Sets the companion cookie, if there's one, when its value differs from the client's.
+    fn set_companion_cookie(
+        &self,
+        req: &Request<'_>,
+        res: &mut Response<'_>,
+        config: &SessionConfig,
+        session: &Session<Store::SessionData>,
+    ) {
+        let companion = match &self.companion_cookie {
+            Some(companion) if Self::host_matches_domain(req, config) => companion,
+            _ => return,
+        };
+
+        let value = (companion.value)(session);
+        let current = req.cookies().get(&companion.name);
+        if current.map(|cookie| cookie.value()) == value.as_deref() {
+            return;
+        }
+
+        let mut config = config.clone();
+        if let Some(lifespan) = session.lifespan() {
+            config.set_cookie_max_age(i32::try_from(lifespan).unwrap_or(i32::MAX));
+        }
+        let mut config = config.with_override(&companion.attributes);
+        config.http_only = false;
+        let cookie = match value {
+            Some(value) => make_cookie(&config, &companion.name, value),
+            None => make_expired_cookie(&config, &companion.name),
+        };
+        res.adjoin_raw_header("Set-Cookie", set_cookie_header(&config, &cookie));
+    }
+}
+
+#[async_trait]
+impl<Store: SessionStore> Fairing for SessionFairing<Store> {
+    fn info(&self) -> Info {
+        Info {
+            name: "Turbopump (session management)",
+            kind: Kind::Attach | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+        // Both the store and the session state are keyed by type, so a second fairing would
+        // silently share the first one's, and both would handle every request.
+        if rocket.state::<Box<Store>>().is_some()
+            || rocket.state::<SessionState<Store::SessionData>>().is_some()
+        {
+            let message = format!(
+                "a session fairing for `{}` (or another store of the same session data type) \
+                is already attached; attaching more than one is unsupported",
+                std::any::type_name::<Store>()
+            );
+            if self.strict {
+                error!("{}", message);
+                return Err(rocket);
+            }
+
+            warn!("{}; ignoring this one and its config", message);
+            self.duplicate.store(true, Ordering::SeqCst);
+            return Ok(rocket);
+        }
+
+        let mut config = if let Some(config) = self.config.clone() {
+            config
+        } else {
+            rocket
+                .figment()
+                .extract_inner::<SessionConfig>("session")
+                .expect("unable to extract session config")
+        };
+        if config.path_strategy == PathStrategy::DeriveFromMount {
+            if let Some(path) = common_path_prefix(rocket.routes().map(|route| route.base())) {
+                config.path = Some(path);
+            }
+        }
+        if let Err(errors) = config.validate() {
+            for error in &errors {
+                if self.strict {
+                    error!("invalid session config: {}", error);
+                } else {
+                    warn!("invalid session config: {}", error);
+                }
+            }
+
+            if self.strict {
+                return Err(rocket);
+            }
+        }
+
+        let codec = self.codec.clone().or_else(|| {
+            config
+                .signing_key
+                .as_ref()
+                .map(|key| Arc::new(HmacCodec::new(key.as_bytes())) as Arc<dyn CookieCodec>)
+        });
+
+        let creation_limiter = config
+            .max_new_sessions_per_ip
+            .map(|limit| CreationLimiter::new(limit, config.new_session_window));
+
+        let mut store = self
+            .store
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or_else(Store::init);
+        store.configure(&config);
+
+        // Store the SessionStore in managed state
+        Ok(rocket.manage(Box::new(store) as Box<Store>).manage(
+            SessionState::<Store::SessionData> {
+                config,
+                codec,
+                id_generator: self
+                    .id_generator
+                    .clone()
+                    .unwrap_or_else(|| Arc::new(ThreadRngGenerator)),
+                client_cert_source: self.client_cert_source.clone(),
+                transform: self.transform.clone(),
+                data_factory: self.data_factory.clone(),
+                creation_limiter,
+                data: PhantomData,
+            },
+        ))
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data) {
+        if self.duplicate.load(Ordering::SeqCst) {
+            return;
+        }
+        let (store, state) = match Self::managed(req) {
+            Some(managed) => managed,
+            None => {
+                // `&Session` guards get a throwaway session, as there's no state to make one from
+                let mut session = Session::with_generator_and_data(
+                    0,
+                    &ThreadRngGenerator,
+                    self.data_factory.new_data(),
+                );
+                session.make_ephemeral();
+                req.local_cache(|| session);
+                SessionCached::<Store::SessionData>::mark(req);
+
+                return;
+            }
+        };
+        if Self::is_peek(req, &state.config) {
+            let session = Self::peek_session(req, store, state).await;
+            req.local_cache(|| Peeked {
+                session,
+                peeked: true,
+            });
+
+            return;
+        }
+
+        req.local_cache_async(async {
+            let config = &state.config;
+
+            match &config.transport {
+                Transport::Cookie => {
+                    let codec = state.codec.as_deref();
+                    let incoming = Self::session_id_from_cookie(req, config, codec);
+                    let session_id = incoming
+                        .as_ref()
+                        .map(|incoming| incoming.session_id.clone())
+                        .or_else(|| Self::session_id_from_query(req, config));
+                    let mut session = Self::init_session(req, store, state, session_id).await;
+                    session.set_cookie_names(config);
+                    Self::add_cookies(req, config, codec, &mut session, incoming.as_ref());
+
+                    session
+                }
+                Transport::Header { name } => {
+                    let session_id = Self::session_id_from_header(req, name);
+
+                    Self::init_session(req, store, state, session_id).await
+                }
+                Transport::Bearer { derivation } => {
+                    let session_id = Self::session_id_from_bearer(req, *derivation);
+                    let mut session =
+                        Self::init_session(req, store, state, session_id.clone()).await;
+                    // a new session is stored under the token, without one there's no key
+                    match session_id {
+                        Some(session_id) if session.is_new() => session.set_id(session_id),
+                        Some(_) => {}
+                        None => session.make_ephemeral(),
+                    }
+
+                    session
+                }
+            }
+        })
+        .await;
+        SessionCached::<Store::SessionData>::mark(req);
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        if self.duplicate.load(Ordering::SeqCst) {
+            return;
+        }
+        let (store, state) = match Self::managed(req) {
+            Some(managed) => managed,
+            None => return,
+        };
+        let config = &state.config;
+        if config.read_only || Self::is_peek(req, config) {
+            return;
+        }
+
+        // Store the session before finalizing the response
+        let lifetime = config.lifetime();
+        let session: &Session<Store::SessionData> = req.local_cache(|| {
+            Session::with_generator_and_data(
+                lifetime.idle_timeout,
+                &*state.id_generator,
+                state.data_factory.new_data(),
+            )
+        });
+        if session.is_ephemeral() {
+            return;
+        }
+
+        // the cookies were set while the request was coming in, before the handler could
+        // activate the session
+        if config.require_activation && !session.is_activated() {
+            if config.transport == Transport::Cookie {
+                take_set_cookie(res, &config.cookie_name);
+                take_set_cookie(res, &config.xsrf_cookie_name);
+            }
+
+            return;
+        }
+
+        if session.should_destroy() {
+            match store.destroy(session.clone()).await {
+                Ok(()) => {
+                    if let Some(observer) = &self.observer {
+                        observer.session_destroyed(session.id());
+                    }
+                }
+                Err(error) => warn!(
+                    "failed to destroy session {}: {}",
+                    session.id().redacted(),
+                    error
+                ),
+            }
+
+            if config.transport == Transport::Cookie {
+                self.expire_cookies(req, res, config, session);
+            }
+
+            return;
+        }
+
+        let mut stored = true;
+        let lifespan = session.lifespan();
+        let load_failed = LoadFailed::<Store::SessionData>::slot(req).load(Ordering::SeqCst);
+        if load_failed && config.on_store_error != StoreErrorPolicy::Optimistic {
+            // the client's session may still exist once the store recovers, so keep its cookie
+            stored = false;
+        } else if config.store_policy == StorePolicy::Always
+            || session.is_new()
+            || session.is_dirty()
+        {
+            let mut updated = session.clone();
+            if config.expiration_policy == ExpirationPolicy::Fixed {
+                if let Some(lifespan) = lifespan {
+                    // a fixed window runs from creation, whenever the lifespan was set
+                    updated.renew_to(session.created() + chrono::Duration::seconds(lifespan));
+                }
+            } else if let Some(lifespan) = lifespan {
+                // the lifespan may have been set by this request's handler
+                updated.renew(lifespan);
+            } else if session.is_kept_alive() {
+                updated.renew_extending(lifetime.idle_timeout);
+            }
+            if let Some(absolute) = lifetime.absolute_timeout {
+                let deadline = session.created() + chrono::Duration::seconds(absolute);
+                if updated.expiration() > deadline {
+                    updated.renew_to(deadline);
+                }
+            }
+
+            if let Some(transform) = &state.transform {
+                let mut data = updated.read(Clone::clone);
+                transform.before_store(&mut data);
+                updated = updated.with_data(data);
+            }
+
+            if let Err(error) = store.store(updated).await {
+                warn!(
+                    "failed to store session {}: {}",
+                    session.id().redacted(),
+                    error
+                );
+                stored = false;
+            } else if session.is_new() {
+                if let Some(observer) = &self.observer {
+                    observer.session_created(session.id());
+                }
+            }
+        }
+
+        if stored {
+            if let Some(key) = session.index_key() {
+                if let Err(error) = store
+                    .index(&key, session.id(), config.max_sessions_per_index)
+                    .await
+                {
+                    warn!(
+                        "failed to index session {}: {}",
+                        session.id().redacted(),
+                        error
+                    );
+                    stored = false;
+                }
+            }
+        }
+
+        if stored || config.on_store_error == StoreErrorPolicy::Optimistic {
+            match &config.transport {
+                Transport::Cookie => {
+                    Self::finish_cookies(req, res, config, session);
+                    self.set_companion_cookie(req, res, config, session);
+                }
+                Transport::Header { name } => {
+                    res.set_raw_header(name.clone(), session.id().to_string());
+                }
+                Transport::Bearer { .. } => {}
+            }
+        } else {
+            // the client's cookie would reference a session that was never persisted
+            if config.transport == Transport::Cookie {
+                take_set_cookie(res, &config.cookie_name);
+            }
+
+            if config.on_store_error == StoreErrorPolicy::ServiceUnavailable {
+                res.set_status(Status::ServiceUnavailable);
+                res.set_sized_body(0, Cursor::new(""));
+            }
+        }
+
+        // skips the RNG altogether when sweeping is disabled
+        if config.lottery > 0.0 && rand::thread_rng().gen::<f64>() < config.lottery {
+            if let Err(error) = store.tidy(config.reclaim_tolerance()).await {
+                warn!("failed to tidy the session store: {}", error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        clock::mock,
+        fairing::config::{LifetimeConfig, SessionConfigBuilder},
+        store::in_memory::InMemory,
+    };
+    use rocket::{get, local::blocking::Client, routes};
+
+    #[get("/")]
+    fn count(session: &Session<u32>) -> String {
+        session.tap(|count| *count += 1);
+        session.read(|count| count.to_string())
+    }
+
+    /// Requests `/` once per gap, moving mock time forward by the gap after each request, and
+    /// returns the counts.
+    fn counts(config: SessionConfig, gaps: &[i64]) -> Vec<String> {
+        let rocket = rocket::ignite()
+            .attach(SessionFairing::<InMemory<u32>>::with_config(config))
+            .mount("/", routes![count]);
+        let client = Client::tracked(rocket).expect("valid rocket instance");
+
+        gaps.iter()
+            .map(|gap| {
+                let count = client.get("/").dispatch().into_string().unwrap();
+                mock::advance(*gap);
+                count
+            })
+            .collect()
+    }
+
+    fn config() -> SessionConfigBuilder {
+        SessionConfig::builder().max_age(60)
+    }
+
+    #[test]
+    fn a_fixed_session_expires_on_schedule_despite_activity() {
+        let config = config()
+            .expiration_policy(ExpirationPolicy::Fixed)
+            .try_finish()
+            .unwrap();
+
+        assert_eq!(counts(config, &[40, 40, 40]), vec!["1", "2", "1"]);
+    }
+
+    #[test]
+    fn a_sliding_session_keeps_extending_with_activity() {
+        let config = config()
+            .expiration_policy(ExpirationPolicy::Sliding)
+            .try_finish()
+            .unwrap();
+
+        assert_eq!(counts(config, &[40, 40, 40]), vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn a_session_expired_within_the_grace_is_reclaimed() {
+        for store_policy in &[StorePolicy::Always, StorePolicy::OnChange] {
+            let config = config()
+                .store_policy(*store_policy)
+                .reclaim_grace(30)
+                .try_finish()
+                .unwrap();
+
+            // expired 10 seconds before the second request, then 40 before the third
+            assert_eq!(counts(config, &[70, 100, 0]), vec!["1", "2", "1"]);
+        }
+    }
+
+    #[test]
+    fn an_active_session_ends_at_the_absolute_timeout() {
+        let config = config()
+            .lifetime(LifetimeConfig {
+                idle_timeout: 60,
+                absolute_timeout: Some(100),
+                ..LifetimeConfig::default()
+            })
+            .try_finish()
+            .unwrap();
+
+        assert_eq!(counts(config, &[40, 40, 40, 0]), vec!["1", "2", "3", "1"]);
+    }
+}
@@ -0,0 +1,170 @@
+use rand::RngCore;
+use std::sync::Mutex;
+
+use crate::util;
+
+/// Generates session IDs and CSRF tokens.
+pub trait SessionIdGenerator: Send + Sync + 'static {
+    fn generate(&self) -> String;
+}
+
+/// The default generator: 40 random alphanumeric characters from `rand::thread_rng()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadRngGenerator;
+
+impl SessionIdGenerator for ThreadRngGenerator {
+    fn generate(&self) -> String {
+        util::random_string(&mut rand::thread_rng())
+    }
+}
+
+/// Generates IDs from the given RNG, e.g. a seeded one for reproducible tests or a vetted CSPRNG
+/// where one is required. The RNG must be cryptographically secure in production, since session
+/// IDs are bearer credentials.
+pub struct RngGenerator<R> {
+    rng: Mutex<R>,
+}
+
+impl<R: RngCore + Send + 'static> RngGenerator<R> {
+    pub fn new(rng: R) -> Self {
+        Self {
+            rng: Mutex::new(rng),
+        }
+    }
+}
+
+impl<R: RngCore + Send + 'static> SessionIdGenerator for RngGenerator<R> {
+    fn generate(&self) -> String {
+        util::random_string(&mut *self.rng.lock().unwrap())
+    }
+}
+
+/// Crockford's base32 alphabet, as used by ULIDs: sorts the same as the values it encodes.
+#[cfg(feature = "ulid")]
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Generates ULID-style IDs that sort by creation time, for stores that benefit from key locality
+/// (e.g. B-tree indexes). Requires the `ulid` feature.
+///
+/// Each ID is 40 characters of Crockford base32: a 10-character millisecond timestamp, a
+/// 4-character counter ordering the IDs created within the same millisecond, and 26 random
+/// characters (130 bits) from `rand::thread_rng()`. Unlike a monotonic ULID, the random part is
+/// fresh for every ID, so one ID doesn't help guess the next.
+///
+/// The ID discloses when the session was created (and roughly how busy the generator was), to
+/// anyone who sees it.
+#[cfg(feature = "ulid")]
+#[derive(Debug, Default)]
+pub struct UlidGenerator {
+    last: Mutex<(i64, u32)>,
+}
+
+#[cfg(feature = "ulid")]
+impl UlidGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The millisecond timestamp and counter for the next ID, never going backwards even if the
+    /// clock does.
+    fn next_stamp(&self) -> (i64, u32) {
+        let now = crate::clock::now().timestamp_millis();
+        let mut last = self.last.lock().unwrap();
+        *last = if now > last.0 {
+            (now, 0)
+        } else {
+            // the counter has 20 bits; past that, borrow the next millisecond
+            match last.1 + 1 {
+                counter if counter < 1 << 20 => (last.0, counter),
+                _ => (last.0 + 1, 0),
+            }
+        };
+
+        *last
+    }
+}
+
+#[cfg(feature = "ulid")]
+fn push_base32(id: &mut String, value: u64, chars: u32) {
+    for i in (0..chars).rev() {
+        id.push(CROCKFORD[((value >> (i * 5)) & 0x1f) as usize] as char);
+    }
+}
+
+#[cfg(feature = "ulid")]
+impl SessionIdGenerator for UlidGenerator {
+    fn generate(&self) -> String {
+        let (millis, counter) = self.next_stamp();
+        let mut id = String::with_capacity(40);
+        push_base32(&mut id, millis as u64, 10);
+        push_base32(&mut id, counter.into(), 4);
+
+        let mut rng = rand::thread_rng();
+        // 12 characters per u64, so three draws cover the 26 random characters
+        for chars in &[12, 12, 2] {
+            push_base32(&mut id, rng.next_u64(), *chars);
+        }
+
+        id
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn seeded_generators_produce_the_same_sequence() {
+        let first = RngGenerator::new(StdRng::seed_from_u64(42));
+        let second = RngGenerator::new(StdRng::seed_from_u64(42));
+
+        let ids: Vec<String> = (0..3).map(|_| first.generate()).collect();
+        assert_eq!(ids, (0..3).map(|_| second.generate()).collect::<Vec<_>>());
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(ids[0].len(), 40);
+        assert!(ids[0].chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn generated_ids_draw_from_the_whole_alphabet() {
+        let generator = RngGenerator::new(StdRng::seed_from_u64(7));
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..100 {
+            let id = generator.generate();
+            assert_eq!(id.len(), 40);
+            seen.extend(id.chars());
+        }
+
+        assert_eq!(seen.len(), 62);
+        assert!(seen.iter().all(char::is_ascii_alphanumeric));
+    }
+
+    #[test]
+    #[cfg(feature = "ulid")]
+    fn ulids_sort_in_creation_order_and_stay_unique() {
+        let generator = UlidGenerator::new();
+        let ids: Vec<String> = (0..10_000).map(|_| generator.generate()).collect();
+
+        let mut sorted = ids.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted, ids);
+        assert!(ids.iter().all(|id| id.len() == 40));
+        assert!(ids.iter().all(|id| crate::SessionID::parse(id).is_some()));
+    }
+
+    #[test]
+    #[cfg(feature = "ulid")]
+    fn ulids_start_with_the_creation_time() {
+        let before = crate::clock::now().timestamp_millis();
+        let id = UlidGenerator::new().generate();
+        let millis = id[..10].bytes().fold(0, |millis, c| {
+            let digit = CROCKFORD.iter().position(|&d| d == c).unwrap() as i64;
+            millis << 5 | digit
+        });
+
+        assert!(millis >= before);
+        assert!(millis <= crate::clock::now().timestamp_millis());
+    }
+}
@@ -0,0 +1,155 @@
+//! Adopting sessions written by other session libraries, so users stay signed in across a
+//! migration to Turbopump. Requires the `interop` feature.
+//!
+//! # actix-session
+//!
+//! Supported: the `RedisSessionStore` / `RedisActorSessionStore` layout of actix-session 0.6
+//! and later, with the default cookie settings:
+//!
+//! - The session cookie is named `id` and holds the 64-character alphanumeric session key,
+//!   encrypted with the `cookie` crate's private jar (`CookieContentSecurity::Private`).
+//! - The Redis key is the session key itself (the default `cache_keygen`), with the session's
+//!   remaining lifetime as its TTL.
+//! - The Redis value is the session state, a JSON object mapping each key to a *string holding
+//!   that value's JSON*, e.g. `{"user_id":"42","name":"\"ferris\""}`.
+//!
+//! To migrate, copy each key's value and TTL from Redis into the new store with
+//! [`import_actix_session`], then accept the old cookie by adding `id` to
+//! `SessionConfig::legacy_cookie_names` and configuring Rocket's `secret_key` with the same
+//! 64-byte key actix-web used: Rocket's private cookies are the same `cookie` crate encryption,
+//! so the old cookie decrypts to the session key, and the fairing re-issues it under the
+//! current cookie name. Signed (`CookieContentSecurity::Signed`) cookies and custom
+//! `cache_keygen`s aren't supported.
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::{error::SessionStoreError, session::Session, store::SessionStore, SessionID};
+
+/// Decodes actix-session's session state into a JSON object. Values that aren't valid JSON are
+/// kept as strings.
+pub fn decode_actix_state(state: &[u8]) -> serde_json::Result<Map<String, Value>> {
+    let state: HashMap<String, String> = serde_json::from_slice(state)?;
+
+    Ok(state
+        .into_iter()
+        .map(|(key, value)| {
+            let value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+            (key, value)
+        })
+        .collect())
+}
+
+/// Builds a session from an actix-session record: its session key, the state stored under it,
+/// and the key's remaining TTL in seconds. The state is deserialized into `Data` from the JSON
+/// object returned by [`decode_actix_state`].
+pub fn adopt_actix_session<Data>(session_key: &str, state: &[u8], ttl: i64) -> Result<Session<Data>>
+where
+    Data: Clone + Send + Sync + DeserializeOwned + 'static,
+{
+    let state = decode_actix_state(state).map_err(|e| {
+        SessionStoreError::Serialization(format!("invalid actix-session state: {}", e))
+    })?;
+    let data: Data = serde_json::from_value(Value::Object(state)).map_err(|e| {
+        SessionStoreError::Serialization(format!("unexpected actix-session state: {}", e))
+    })?;
+
+    Ok(Session::with_id_and_data(
+        SessionID(session_key.to_string()),
+        data,
+        ttl,
+    ))
+}
+
+/// Adopts an actix-session record (see [`adopt_actix_session`]) and stores it in `store`.
+/// Records that have already expired are skipped. Returns whether the session was stored.
+pub async fn import_actix_session<Store>(
+    store: &Store,
+    session_key: &str,
+    state: &[u8],
+    ttl: i64,
+) -> Result<bool>
+where
+    Store: SessionStore,
+    Store::SessionData: DeserializeOwned,
+{
+    if ttl <= 0 {
+        return Ok(false);
+    }
+
+    let session = adopt_actix_session(session_key, state, ttl)?;
+    store.store(session).await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::in_memory::InMemory;
+    use serde::Deserialize;
+
+    // as written by actix-session's `RedisSessionStore` after
+    // `session.insert("user_id", 42)`, `session.insert("name", "ferris")`, and
+    // `session.insert("roles", vec!["admin"])`
+    const SESSION_KEY: &str = "Xr3Ua1h8GdvyJ8Wwx0kwW6aKeD2pD9ZV0rvgqL1u8GSNd5JnYlG0M2eQkWQ7hTbF";
+    const STATE: &str = r#"{"user_id":"42","name":"\"ferris\"","roles":"[\"admin\"]"}"#;
+
+    #[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+    struct User {
+        user_id: u32,
+        name: String,
+        roles: Vec<String>,
+    }
+
+    #[test]
+    fn it_decodes_actix_session_state() {
+        let state = decode_actix_state(STATE.as_bytes()).unwrap();
+
+        assert_eq!(state["user_id"], 42);
+        assert_eq!(state["name"], "ferris");
+        assert_eq!(state["roles"], serde_json::json!(["admin"]));
+    }
+
+    #[test]
+    fn it_keeps_non_json_values_as_strings() {
+        let state = decode_actix_state(br#"{"flash":"not json"}"#).unwrap();
+
+        assert_eq!(state["flash"], "not json");
+    }
+
+    #[tokio::test]
+    async fn it_imports_an_actix_session_under_its_session_key() {
+        let store = InMemory::<User>::init();
+        assert!(
+            import_actix_session(&store, SESSION_KEY, STATE.as_bytes(), 600)
+                .await
+                .unwrap()
+        );
+
+        let session = store.load(SESSION_KEY.into()).await.unwrap().unwrap();
+        let expected = User {
+            user_id: 42,
+            name: "ferris".to_string(),
+            roles: vec!["admin".to_string()],
+        };
+        assert_eq!(session.read(Clone::clone), expected);
+        assert!(session.is_valid(0));
+        assert_ne!(session.csrf_token().to_string(), SESSION_KEY);
+    }
+
+    #[tokio::test]
+    async fn it_skips_expired_and_malformed_records() {
+        let store = InMemory::<User>::init();
+        assert!(
+            !import_actix_session(&store, SESSION_KEY, STATE.as_bytes(), 0)
+                .await
+                .unwrap()
+        );
+        assert!(import_actix_session(&store, SESSION_KEY, b"[]", 600)
+            .await
+            .is_err());
+        assert!(store.load(SESSION_KEY.into()).await.unwrap().is_none());
+    }
+}
@@ -1,12 +1,27 @@
 pub use anyhow::Error;
 
+#[cfg(feature = "rocket")]
+pub mod admin;
+#[cfg(feature = "rocket")]
+pub mod client_cert;
+mod clock;
+pub mod codec;
 pub mod error;
+pub mod factory;
 pub mod fairing;
+pub mod id;
+#[cfg(feature = "interop")]
+pub mod interop;
+pub mod observer;
+pub mod payload;
 pub mod session;
 pub mod store;
+pub mod transform;
 pub mod types;
 mod util;
 
-pub use session::Session;
+#[cfg(feature = "rocket")]
+pub use session::{MaybeSession, PeekSession, RouteOverride, SessionData, SessionDataMut};
+pub use session::{Session, SessionRef, SessionSummary};
 pub use store::SessionStore;
 pub use types::*;
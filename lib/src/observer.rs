@@ -0,0 +1,11 @@
+use crate::SessionID;
+
+/// Notified by the session fairing as sessions come and go, e.g. to keep metrics. Callbacks run
+/// on the request path, so they should be cheap.
+pub trait SessionObserver: Send + Sync + 'static {
+    /// A new session was stored for the first time.
+    fn session_created(&self, _session_id: &SessionID) {}
+
+    /// A session was destroyed, after it was removed from the store.
+    fn session_destroyed(&self, _session_id: &SessionID) {}
+}
@@ -0,0 +1,115 @@
+//! Codecs serializing session data to bytes, used by the persistent stores' storage formats
+//! (see [`StorageFormat`](crate::store::schema::StorageFormat)).
+//!
+//! [`JsonPayload`] is always available; [`BincodePayload`] requires the `bincode` feature and
+//! [`MessagePackPayload`] the `messagepack` one. For typical session data (IDs, short strings,
+//! flags) bincode comes out at roughly half the size of JSON, since it drops field names and
+//! quoting: the `Cart` in this module's tests encodes to 46 bytes with bincode and 90 with JSON.
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Serializes session data to bytes and back.
+pub trait PayloadCodec: Send + Sync + 'static {
+    fn encode<Data: Serialize>(&self, data: &Data) -> Result<Vec<u8>>;
+    fn decode<Data: DeserializeOwned>(&self, bytes: &[u8]) -> Result<Data>;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonPayload;
+
+impl PayloadCodec for JsonPayload {
+    fn encode<Data: Serialize>(&self, data: &Data) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(data)?)
+    }
+
+    fn decode<Data: DeserializeOwned>(&self, bytes: &[u8]) -> Result<Data> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// A compact binary encoding. Unlike JSON it isn't self-describing, so adding, removing, or
+/// reordering fields of `Data` makes existing payloads undecodable.
+#[cfg(feature = "bincode")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BincodePayload;
+
+#[cfg(feature = "bincode")]
+impl PayloadCodec for BincodePayload {
+    fn encode<Data: Serialize>(&self, data: &Data) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(data)?)
+    }
+
+    fn decode<Data: DeserializeOwned>(&self, bytes: &[u8]) -> Result<Data> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// A compact, self-describing binary encoding; structs are written as maps, keeping their field
+/// names.
+#[cfg(feature = "messagepack")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MessagePackPayload;
+
+#[cfg(feature = "messagepack")]
+impl PayloadCodec for MessagePackPayload {
+    fn encode<Data: Serialize>(&self, data: &Data) -> Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec_named(data)?)
+    }
+
+    fn decode<Data: DeserializeOwned>(&self, bytes: &[u8]) -> Result<Data> {
+        Ok(rmp_serde::from_read_ref(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Cart {
+        user_id: u64,
+        items: Vec<(u32, u16)>,
+        currency: String,
+        guest: bool,
+    }
+
+    fn cart() -> Cart {
+        Cart {
+            user_id: 4_815_162_342,
+            items: vec![(1001, 2), (2002, 1), (3003, 5)],
+            currency: "EUR".to_string(),
+            guest: false,
+        }
+    }
+
+    #[test]
+    fn json_payloads_round_trip() {
+        let bytes = JsonPayload.encode(&cart()).unwrap();
+
+        assert_eq!(JsonPayload.decode::<Cart>(&bytes).unwrap(), cart());
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_payloads_round_trip_smaller_than_json() {
+        let bytes = BincodePayload.encode(&cart()).unwrap();
+
+        assert_eq!(BincodePayload.decode::<Cart>(&bytes).unwrap(), cart());
+        assert!(bytes.len() < JsonPayload.encode(&cart()).unwrap().len());
+    }
+
+    #[cfg(feature = "messagepack")]
+    #[test]
+    fn messagepack_payloads_round_trip() {
+        let bytes = MessagePackPayload.encode(&cart()).unwrap();
+
+        assert_eq!(MessagePackPayload.decode::<Cart>(&bytes).unwrap(), cart());
+    }
+
+    #[test]
+    fn malformed_payloads_fail_to_decode() {
+        assert!(JsonPayload.decode::<Cart>(b"not json").is_err());
+        assert!(JsonPayload.decode::<Cart>(b"{}").is_err());
+    }
+}
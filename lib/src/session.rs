@@ -1,52 +1,211 @@
-use rocket::{
-    request::{FromRequest, Outcome},
-    Request,
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock, RwLockReadGuard,
+    },
 };
 
-use std::sync::{Arc, RwLock};
+#[cfg(feature = "rocket")]
+mod guards;
 
-use crate::{util, CsrfToken, SessionID};
+#[cfg(feature = "rocket")]
+pub use guards::{MaybeSession, PeekSession, RouteOverride, SessionData, SessionDataMut};
+#[cfg(feature = "rocket")]
+pub(crate) use guards::{Peeked, RouteCookieOverride, SessionCached};
+
+use crate::{
+    clock,
+    fairing::config::{CookieOverride, SessionConfig},
+    id::{SessionIdGenerator, ThreadRngGenerator},
+    types::redact,
+    CsrfToken, SessionID,
+};
 
-#[derive(Debug, Default)]
 pub struct Session<Data>
 where
-    Data: Clone + Default + Send + Sync + 'static,
+    Data: Clone + Send + Sync + 'static,
 {
     id: SessionID,
     token: CsrfToken,
-    should_destroy: bool,
+    created: DateTime<Utc>,
+    expiration: DateTime<Utc>,
+    last_accessed: DateTime<Utc>,
+    version: u64,
+    lifespan: RwLock<Option<i64>>,
+    client_cert: Option<String>,
+    cookie_expiration: Option<DateTime<Utc>>,
+    should_destroy: AtomicBool,
+    is_new: bool,
+    ephemeral: bool,
+    dirty: AtomicBool,
+    keepalive: AtomicBool,
+    activated: AtomicBool,
+    cookie_override: RwLock<Option<CookieOverride>>,
+    cookie_value: RwLock<Option<String>>,
+    /// The owning fairing's `cookie_name` and `xsrf_cookie_name`, once it hands the session out.
+    cookie_names: Option<(String, String)>,
+    index_key: RwLock<Option<String>>,
+    checkpoint: RwLock<Option<(Data, bool)>>,
 
     inner_data: Arc<RwLock<Data>>,
 }
 
 impl<Data> Clone for Session<Data>
 where
-    Data: Clone + Default + Send + Sync + 'static,
+    Data: Clone + Send + Sync + 'static,
 {
     fn clone(&self) -> Self {
         Self {
             id: self.id.clone(),
             token: self.token.clone(),
+            created: self.created,
+            expiration: self.expiration,
+            last_accessed: self.last_accessed,
+            version: self.version,
+            lifespan: RwLock::new(self.lifespan()),
+            client_cert: self.client_cert.clone(),
+            cookie_expiration: self.cookie_expiration,
+            cookie_names: self.cookie_names.clone(),
             inner_data: self.inner_data.clone(),
+            // kept so stores can tell the session is being destroyed
+            should_destroy: AtomicBool::new(self.should_destroy()),
 
-            should_destroy: false,
+            is_new: false,
+            ephemeral: false,
+            dirty: AtomicBool::new(false),
+            keepalive: AtomicBool::new(false),
+            activated: AtomicBool::new(false),
+            cookie_override: Default::default(),
+            cookie_value: Default::default(),
+            index_key: Default::default(),
+            checkpoint: Default::default(),
         }
     }
 }
 
+// Session IDs and CSRF tokens are secrets, so only a prefix of each makes it into the output.
+impl<Data> fmt::Debug for Session<Data>
+where
+    Data: Clone + Send + Sync + fmt::Debug + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("id", &self.id.redacted())
+            .field("token", &redact(&self.token.0))
+            .field("created", &self.created)
+            .field("expiration", &self.expiration)
+            .field("last_accessed", &self.last_accessed)
+            .field("version", &self.version)
+            .field("lifespan", &self.lifespan)
+            .field("client_cert", &self.client_cert)
+            .field("cookie_expiration", &self.cookie_expiration)
+            .field("should_destroy", &self.should_destroy)
+            .field("is_new", &self.is_new)
+            .field("ephemeral", &self.ephemeral)
+            .field("dirty", &self.dirty)
+            .field("keepalive", &self.keepalive)
+            .field("activated", &self.activated)
+            .field("cookie_override", &self.cookie_override)
+            .field("cookie_value", &self.cookie_value.read().unwrap().is_some())
+            .field("cookie_names", &self.cookie_names)
+            .field("index_key", &self.index_key)
+            .field("inner_data", &self.inner_data)
+            .finish()
+    }
+}
+
 impl<Data> Session<Data>
 where
     Data: Clone + Default + Send + Sync + 'static,
 {
-    pub fn new() -> Self {
-        let id = util::random_string();
-        let token = util::random_string();
+    pub fn new(lifespan: i64) -> Self {
+        Self::with_generator(lifespan, &ThreadRngGenerator)
+    }
+
+    /// Like [`Session::new`], but with the ID and CSRF token taken from `generator`.
+    pub fn with_generator(lifespan: i64, generator: &dyn SessionIdGenerator) -> Self {
+        Self::with_generator_and_data(lifespan, generator, Data::default())
+    }
+
+    /// Like [`Session::new`], but keeping an ID issued elsewhere, e.g. when adopting sessions
+    /// from another session library (see [`interop`](crate::interop)), or a fixed one for test
+    /// fixtures. Outside of tests, the ID must be as hard to guess as a generated one.
+    pub fn with_id(session_id: SessionID, lifespan: i64) -> Self {
+        Self::with_id_and_data(session_id, Data::default(), lifespan)
+    }
+}
+
+impl<Data> Session<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    /// Like [`Session::with_generator`], starting out with `data` instead of `Data::default()`,
+    /// so `Data` needn't implement `Default`.
+    pub fn with_generator_and_data(
+        lifespan: i64,
+        generator: &dyn SessionIdGenerator,
+        data: Data,
+    ) -> Self {
+        let id = generator.generate();
+        let token = generator.generate();
+        let now = clock::now();
 
         Self {
             id: SessionID(id),
             token: CsrfToken(token),
-            inner_data: Default::default(),
-            should_destroy: false,
+            created: now,
+            expiration: now + Duration::seconds(lifespan),
+            last_accessed: now,
+            version: 0,
+            lifespan: Default::default(),
+            client_cert: None,
+            cookie_expiration: None,
+            inner_data: Arc::new(RwLock::new(data)),
+            should_destroy: AtomicBool::new(false),
+            is_new: true,
+            ephemeral: false,
+            dirty: AtomicBool::new(false),
+            keepalive: AtomicBool::new(false),
+            activated: AtomicBool::new(false),
+            cookie_override: Default::default(),
+            cookie_value: Default::default(),
+            cookie_names: None,
+            index_key: Default::default(),
+            checkpoint: Default::default(),
+        }
+    }
+
+    /// Like [`Session::with_id`], starting out with `data` instead of `Data::default()`.
+    pub fn with_id_and_data(session_id: SessionID, data: Data, lifespan: i64) -> Self {
+        let mut session = Self::with_generator_and_data(lifespan, &ThreadRngGenerator, data);
+        session.id = session_id;
+        session
+    }
+
+    /// Changes the data without marking the session dirty, for the fairing's
+    /// [`DataTransform`](crate::transform::DataTransform) hooks.
+    #[cfg(feature = "rocket")]
+    pub(crate) fn transform(&self, f: impl FnOnce(&mut Data)) {
+        f(&mut self.inner_data.write().unwrap());
+    }
+
+    /// Swaps the session's ID, for stores that persist it under a different key (see
+    /// [`HashedStore`](crate::store::hashed::HashedStore)), or to key a new session by its
+    /// bearer token.
+    pub(crate) fn set_id(&mut self, session_id: SessionID) {
+        self.id = session_id;
+    }
+
+    /// A copy of the session that doesn't share its data with this one, unlike a clone.
+    pub(crate) fn with_data(&self, data: Data) -> Self {
+        Self {
+            inner_data: Arc::new(RwLock::new(data)),
+            ..self.clone()
         }
     }
 
@@ -54,42 +213,585 @@ where
         &self.id
     }
 
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    pub fn expiration(&self) -> DateTime<Utc> {
+        self.expiration
+    }
+
+    /// When the session was last resumed by a request (see [`Session::renew`]).
+    pub fn last_accessed(&self) -> DateTime<Utc> {
+        self.last_accessed
+    }
+
+    /// The version of the session as last written to the store; `0` if it never was. See
+    /// [`SessionStore::store_if_unchanged`](crate::SessionStore::store_if_unchanged).
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Sets the session's version. Meant for store implementations, which bump it on every
+    /// write.
+    pub fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+
+    pub fn expired(&self) -> bool {
+        !self.is_valid(0)
+    }
+
+    /// Whether the session is still valid, allowing it to outlive its expiration by
+    /// `tolerance` seconds to absorb clock skew between nodes.
+    pub fn is_valid(&self, tolerance: i64) -> bool {
+        clock::now() < self.expiration + Duration::seconds(tolerance)
+    }
+
+    /// Returns the session if it's still valid (see [`Session::is_valid`]).
+    pub fn validate(self, tolerance: i64) -> Option<Self> {
+        if self.is_valid(tolerance) {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the session with its expiration extended to at least `lifespan` seconds from now
+    /// (see [`Session::renew_extending`]) if it's still valid, or `None` if it has already
+    /// expired.
+    pub fn extend_if_valid(self, lifespan: i64, tolerance: i64) -> Option<Self> {
+        self.validate(tolerance).map(|mut session| {
+            session.renew_extending(lifespan);
+            session
+        })
+    }
+
+    /// Moves the session's expiration to `lifespan` seconds from now. Like the other renewals,
+    /// does nothing once the session is [invalidated](Session::invalidate).
+    pub fn renew(&mut self, lifespan: i64) {
+        if self.should_destroy() {
+            return;
+        }
+
+        let now = clock::now();
+        self.last_accessed = now;
+        self.expiration = now + Duration::seconds(lifespan);
+    }
+
+    /// Moves the session's expiration to `expiration`, e.g. to match an OAuth token's `exp`. An
+    /// expiration that isn't in the future expires the session immediately, like
+    /// [`Session::force_expire`], regardless of any clock skew tolerance. Later renewals with
+    /// a lifespan, such as the fairing's on each request, may move it again.
+    pub fn renew_to(&mut self, expiration: DateTime<Utc>) {
+        if self.should_destroy() {
+            return;
+        }
+
+        let now = clock::now();
+        self.last_accessed = now;
+        if expiration > now {
+            self.expiration = expiration;
+        } else {
+            self.force_expire();
+        }
+    }
+
+    /// Like [`Session::renew`], but never moves the expiration earlier, so a long-lived session
+    /// (e.g. "remember me") isn't cut short by a renewal with the default lifespan.
+    pub fn renew_extending(&mut self, lifespan: i64) {
+        if self.should_destroy() {
+            return;
+        }
+
+        let now = clock::now();
+        self.last_accessed = now;
+        self.expiration = self.expiration.max(now + Duration::seconds(lifespan));
+    }
+
+    /// Expires the session now while keeping its data, e.g. to log a user out but keep the record
+    /// for auditing until the next `tidy`. The expiration is set to the Unix epoch, so the
+    /// session is expired regardless of any clock skew tolerance.
+    pub fn force_expire(&mut self) {
+        self.expiration = std::time::UNIX_EPOCH.into();
+    }
+
+    /// The session's metadata without its data, safe to log or show in support tooling.
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            id: self.id.redacted(),
+            created: self.created,
+            expiration: self.expiration,
+            last_accessed: self.last_accessed,
+            is_valid: self.is_valid(0),
+        }
+    }
+
+    /// The full session ID. Prefer the `Debug` output or [`SessionID::redacted`] for logging.
+    pub fn id_str(&self) -> &str {
+        &self.id.0
+    }
+
     pub fn csrf_token(&self) -> &CsrfToken {
         &self.token
     }
 
+    /// The session cookie's name and value. The name is the owning fairing's `cookie_name`, or
+    /// the default `session_id` for a session no fairing has handed out.
     pub fn cookie_value(&self) -> (&str, &SessionID) {
-        ("session_id", self.id())
+        let name = match &self.cookie_names {
+            Some((cookie_name, _)) => cookie_name.as_str(),
+            None => "session_id",
+        };
+
+        (name, self.id())
     }
 
+    /// Like [`Session::cookie_value`], for the CSRF token cookie (`xsrf_cookie_name`).
     pub fn token_value(&self) -> (&str, &CsrfToken) {
-        ("xsrf_token", self.csrf_token())
+        let name = match &self.cookie_names {
+            Some((_, xsrf_cookie_name)) => xsrf_cookie_name.as_str(),
+            None => "xsrf_token",
+        };
+
+        (name, self.csrf_token())
+    }
+
+    #[cfg(feature = "rocket")]
+    pub(crate) fn set_cookie_names(&mut self, config: &SessionConfig) {
+        self.cookie_names = Some((config.cookie_name.clone(), config.xsrf_cookie_name.clone()));
     }
 
     pub fn should_destroy(&self) -> bool {
-        self.should_destroy
+        self.should_destroy.load(Ordering::SeqCst)
+    }
+
+    /// Destroys the session (e.g. on logout) once the response is sent: it's removed from the
+    /// store and the client is told to delete its session cookies. This can't be undone: changes
+    /// made to the data before or after are never stored, and renewals do nothing.
+    pub fn invalidate(&self) {
+        self.should_destroy.store(true, Ordering::SeqCst);
+    }
+
+    /// Gives this session its own lifespan in seconds, e.g. shorter for a guest checkout or
+    /// longer for an admin, in place of the config's `max_age`. The lifespan is stored with the
+    /// session: the fairing applies it to the session's expiration and cookie `Max-Age` from the
+    /// current response on, and renews the session by it on later requests.
+    pub fn set_lifespan(&self, lifespan: i64) {
+        *self.lifespan.write().unwrap() = Some(lifespan);
+        self.mark_dirty();
+    }
+
+    /// The lifespan set with [`Session::set_lifespan`], if any.
+    pub fn lifespan(&self) -> Option<i64> {
+        *self.lifespan.read().unwrap()
+    }
+
+    /// The fingerprint of the TLS client certificate the session is bound to, if any (see
+    /// [`client_cert`](crate::client_cert)).
+    pub fn client_cert(&self) -> Option<&str> {
+        self.client_cert.as_deref()
+    }
+
+    /// Binds the session to the client certificate with the given fingerprint.
+    #[cfg(feature = "rocket")]
+    pub(crate) fn bind_client_cert(&mut self, fingerprint: String) {
+        self.client_cert = Some(fingerprint);
+        self.mark_dirty();
+    }
+
+    /// When the session cookie last sent to the client expires, or `None` if none was sent
+    /// since this was tracked.
+    #[cfg(feature = "rocket")]
+    pub(crate) fn cookie_expiration(&self) -> Option<DateTime<Utc>> {
+        self.cookie_expiration
+    }
+
+    /// Records that a session cookie expiring along with the session was just sent.
+    #[cfg(feature = "rocket")]
+    pub(crate) fn cookie_issued(&mut self) {
+        self.cookie_expiration = Some(self.expiration);
+        self.mark_dirty();
+    }
+
+    /// Overrides the session cookie's attributes for the current response only. Attributes set
+    /// on the override take precedence over the global `SessionConfig`; the override is not
+    /// persisted with the session.
+    pub fn set_cookie_override(&self, cookie_override: CookieOverride) {
+        *self.cookie_override.write().unwrap() = Some(cookie_override);
+    }
+
+    pub fn cookie_override(&self) -> Option<CookieOverride> {
+        self.cookie_override.read().unwrap().clone()
+    }
+
+    /// Sends `value` as the session cookie's value in the current response, verbatim: it isn't
+    /// encrypted or passed through the fairing's `CookieCodec`, e.g. for a gateway that expects
+    /// `<tenant>.<session id>`. The store is still keyed by the session's ID.
+    ///
+    /// Getting the value back to the session is up to you: the fairing must be configured with
+    /// a `CookieCodec` whose `decode` returns this session's ID for `value`, or the next request
+    /// won't find the session (without a codec, the fairing only reads private cookies). Like
+    /// [`Session::set_cookie_override`], it only applies to this response; when the cookie is
+    /// re-issued later, it's encoded as usual unless the value is set again.
+    pub fn set_cookie_value(&self, value: impl Into<String>) {
+        *self.cookie_value.write().unwrap() = Some(value.into());
+    }
+
+    /// The value set with [`Session::set_cookie_value`], if any.
+    pub fn cookie_value_override(&self) -> Option<String> {
+        self.cookie_value.read().unwrap().clone()
+    }
+
+    /// Whether the session was created during the current request rather than loaded from the
+    /// store.
+    pub fn is_new(&self) -> bool {
+        self.is_new
+    }
+
+    /// Whether the session is a throwaway one that won't be stored or sent to the client, e.g.
+    /// because the client IP has created too many sessions (see
+    /// `SessionConfig::max_new_sessions_per_ip`).
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    #[cfg(feature = "rocket")]
+    pub(crate) fn make_ephemeral(&mut self) {
+        self.ephemeral = true;
+    }
+
+    /// Whether the session's data was changed during the current request. Under
+    /// `StorePolicy::OnChange` only new or dirty sessions are written back to the store.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::SeqCst)
+    }
+
+    /// Forces the session to be written back to the store under `StorePolicy::OnChange`, e.g.
+    /// after mutating its data through a handle that bypasses [`Session::tap`].
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    /// Renews the session once the response is sent, by its lifespan (see
+    /// [`Session::set_lifespan`]) or the config's `max_age`, and writes it back even under
+    /// `StorePolicy::OnChange`. Meant for a route that single-page apps ping to keep the session
+    /// alive without doing anything else:
+    ///
+    /// ```ignore
+    /// #[post("/session/keepalive")]
+    /// fn keepalive(session: &Session<Data>) -> Status {
+    ///     session.keepalive();
+    ///     Status::NoContent
+    /// }
+    /// ```
+    ///
+    /// The session cookie is re-issued once its expiry falls behind (see
+    /// `SessionConfig::cookie_refresh_threshold`). Routes under `peek_paths` never renew, and
+    /// neither does anything under `ExpirationPolicy::Fixed`.
+    pub fn keepalive(&self) {
+        self.keepalive.store(true, Ordering::SeqCst);
+        self.mark_dirty();
+    }
+
+    #[cfg(feature = "rocket")]
+    pub(crate) fn is_kept_alive(&self) -> bool {
+        self.keepalive.load(Ordering::SeqCst)
+    }
+
+    /// Under `SessionConfig::require_activation`, persists the request's new session and sends
+    /// its cookie, e.g. once the user logs in. Sessions loaded from the store are already
+    /// active, and without `require_activation` every session is, so this does nothing then.
+    pub fn activate(&self) {
+        self.activated.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the session was stored before this request or activated during it (see
+    /// [`Session::activate`]).
+    pub fn is_activated(&self) -> bool {
+        !self.is_new || self.activated.load(Ordering::SeqCst)
+    }
+
+    /// Skips writing the session back under `StorePolicy::OnChange`, e.g. after rolling back a
+    /// speculative change. New sessions are written regardless.
+    pub fn mark_clean(&self) {
+        self.dirty.store(false, Ordering::SeqCst);
+    }
+
+    /// Files the session under `key` (e.g. `user:42`) in the store's secondary index once the
+    /// response is sent, enforcing `max_sessions_per_index` for that key.
+    pub fn set_index(&self, key: impl Into<String>) {
+        *self.index_key.write().unwrap() = Some(key.into());
+    }
+
+    #[cfg(feature = "rocket")]
+    pub(crate) fn index_key(&self) -> Option<String> {
+        self.index_key.read().unwrap().clone()
+    }
+
+    /// Snapshots the session's data so that changes made after this point can be undone with
+    /// [`Session::rollback`]. Replaces any earlier checkpoint.
+    pub fn checkpoint(&self) {
+        let data = self.inner_data.read().unwrap().clone();
+        *self.checkpoint.write().unwrap() = Some((data, self.is_dirty()));
+    }
+
+    /// Restores the data (and dirty flag) captured by the last [`Session::checkpoint`], so the
+    /// changes made since aren't persisted at the end of the request. Does nothing if there's no
+    /// checkpoint.
+    pub fn rollback(&self) {
+        if let Some((data, dirty)) = self.checkpoint.write().unwrap().take() {
+            *self.inner_data.write().unwrap() = data;
+            self.dirty.store(dirty, Ordering::SeqCst);
+        }
     }
 
     pub fn tap<T>(&self, f: impl FnOnce(&mut Data) -> T) -> T {
+        self.dirty.store(true, Ordering::SeqCst);
         f(&mut self.inner_data.write().unwrap())
     }
+
+    /// Like [`Session::tap`], but with read-only access, so the session isn't marked as changed.
+    pub fn read<T>(&self, f: impl FnOnce(&Data) -> T) -> T {
+        f(&self.inner_data.read().unwrap())
+    }
+
+    /// Borrows the session data without cloning it, e.g. to read a large structure in place.
+    ///
+    /// The returned guard holds the data's read lock until it's dropped, so [`Session::tap`] on
+    /// the same session blocks (or deadlocks, on the same thread) in the meantime. Don't hold it
+    /// across an `.await`: drop it first, or use [`Session::read`], which can't outlive its
+    /// closure.
+    pub fn borrow(&self) -> SessionRef<'_, Data> {
+        SessionRef(self.inner_data.read().unwrap())
+    }
+}
+
+impl<Data> Session<Data>
+where
+    Data: Clone + PartialEq + Send + Sync + 'static,
+{
+    /// Whether the session's data equals `other`. Comparing against a snapshot taken when the
+    /// session was loaded detects changes however they were made, including through handles
+    /// that bypass [`Session::tap`] and so never mark the session dirty:
+    ///
+    /// ```ignore
+    /// let snapshot = session.read(Data::clone);
+    /// // ... handle the request ...
+    /// if !session.data_eq(&snapshot) {
+    ///     store.store(session.clone()).await?;
+    /// }
+    /// ```
+    ///
+    /// A `tap` that leaves the data as it was still marks the session dirty, but compares equal.
+    pub fn data_eq(&self, other: &Data) -> bool {
+        *self.inner_data.read().unwrap() == *other
+    }
+}
+
+impl<Data> Session<Data>
+where
+    Data: Clone + Send + Sync + Serialize + 'static,
+{
+    /// A hash of the session's data, for deriving an `ETag` so handlers rendering
+    /// session-dependent content can answer `If-None-Match` with `304 Not Modified`:
+    ///
+    /// ```ignore
+    /// if let Ok(hash) = session.data_hash() {
+    ///     if if_none_match == Some(&format!("\"{:x}\"", hash)) {
+    ///         return Status::NotModified;
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// It's the SHA-256 of the data's JSON, so it's the same across processes and restarts as
+    /// long as the data serializes deterministically: `HashMap`s don't, use `BTreeMap`s instead.
+    /// Fails if the data can't be written as JSON, e.g. a map with non-string keys.
+    pub fn data_hash(&self) -> serde_json::Result<u64> {
+        let json = serde_json::to_vec(&*self.inner_data.read().unwrap())?;
+        let digest = Sha256::digest(&json);
+
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        Ok(u64::from_be_bytes(bytes))
+    }
 }
 
-#[async_trait::async_trait]
-impl<'a, 'r, Data> FromRequest<'a, 'r> for &'a Session<Data>
+/// Read-only access to a session's data, returned by [`Session::borrow`]. Holds the data's read
+/// lock until dropped.
+pub struct SessionRef<'a, Data>(RwLockReadGuard<'a, Data>);
+
+impl<Data> Deref for SessionRef<'_, Data> {
+    type Target = Data;
+
+    fn deref(&self) -> &Data {
+        &self.0
+    }
+}
+
+impl<Data> Default for Session<Data>
 where
     Data: Clone + Default + Send + Sync + 'static,
 {
-    type Error = ();
+    fn default() -> Self {
+        Self::new(SessionConfig::default().max_age.into())
+    }
+}
 
-    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
-        Outcome::Success(request.local_cache(Session::new))
+/// Key/value helpers for sessions holding a JSON object, built on Rocket's `serde` re-exports so
+/// the stored types only need to implement Rocket's `Serialize`/`Deserialize`. Requires the
+/// `json` feature.
+#[cfg(feature = "json")]
+impl Session<rocket::serde::json::Value> {
+    /// Deserializes the value stored under `key`, or returns `None` if there's no such value or
+    /// it doesn't deserialize into a `T`.
+    pub fn get_json<T: rocket::serde::DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.read(|data| data.get(key).cloned())
+            .and_then(|value| rocket::serde::json::from_value(value).ok())
+    }
+
+    /// Serializes `value` and stores it under `key`, replacing the session data with an empty
+    /// object first if it isn't an object yet.
+    pub fn set_json<T: rocket::serde::Serialize>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), rocket::serde::json::serde_json::Error> {
+        use rocket::serde::json::Value;
+
+        let value = rocket::serde::json::serde_json::to_value(value)?;
+        self.tap(|data| {
+            if !data.is_object() {
+                *data = Value::Object(Default::default());
+            }
+
+            if let Value::Object(map) = data {
+                map.insert(key.to_string(), value);
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// A session's metadata, see [`Session::summary`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SessionSummary {
+    /// The redacted session ID (see [`SessionID::redacted`]).
+    pub id: String,
+    pub created: DateTime<Utc>,
+    pub expiration: DateTime<Utc>,
+    pub last_accessed: DateTime<Utc>,
+    pub is_valid: bool,
+}
+
+impl fmt::Display for SessionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "session {} (created {}, last accessed {}, expires {}, {})",
+            self.id,
+            self.created,
+            self.last_accessed,
+            self.expiration,
+            if self.is_valid { "valid" } else { "expired" }
+        )
+    }
+}
+
+/// The persisted parts of a session, used by stores that serialize sessions.
+#[derive(Serialize)]
+struct SessionRecordRef<'a, Data> {
+    id: &'a SessionID,
+    token: &'a CsrfToken,
+    created: DateTime<Utc>,
+    expiration: DateTime<Utc>,
+    last_accessed: DateTime<Utc>,
+    version: u64,
+    lifespan: Option<i64>,
+    client_cert: Option<&'a str>,
+    cookie_expiration: Option<DateTime<Utc>>,
+    data: &'a Data,
+}
+
+#[derive(Deserialize)]
+struct SessionRecord<Data> {
+    id: SessionID,
+    token: CsrfToken,
+    created: DateTime<Utc>,
+    expiration: DateTime<Utc>,
+    last_accessed: DateTime<Utc>,
+    #[serde(default)]
+    version: u64,
+    #[serde(default)]
+    lifespan: Option<i64>,
+    #[serde(default)]
+    client_cert: Option<String>,
+    #[serde(default)]
+    cookie_expiration: Option<DateTime<Utc>>,
+    data: Data,
+}
+
+impl<Data> Serialize for Session<Data>
+where
+    Data: Clone + Send + Sync + Serialize + 'static,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = self.inner_data.read().unwrap();
+
+        SessionRecordRef {
+            id: &self.id,
+            token: &self.token,
+            created: self.created,
+            expiration: self.expiration,
+            last_accessed: self.last_accessed,
+            version: self.version,
+            lifespan: self.lifespan(),
+            client_cert: self.client_cert(),
+            cookie_expiration: self.cookie_expiration,
+            data: &*data,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, Data> Deserialize<'de> for Session<Data>
+where
+    Data: Clone + Send + Sync + Deserialize<'de> + 'static,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let record = SessionRecord::<Data>::deserialize(deserializer)?;
+
+        Ok(Self {
+            id: record.id,
+            token: record.token,
+            created: record.created,
+            expiration: record.expiration,
+            last_accessed: record.last_accessed,
+            version: record.version,
+            lifespan: RwLock::new(record.lifespan),
+            client_cert: record.client_cert,
+            cookie_expiration: record.cookie_expiration,
+            should_destroy: AtomicBool::new(false),
+            is_new: false,
+            ephemeral: false,
+            dirty: AtomicBool::new(false),
+            keepalive: AtomicBool::new(false),
+            activated: AtomicBool::new(false),
+            cookie_override: Default::default(),
+            cookie_value: Default::default(),
+            cookie_names: None,
+            index_key: Default::default(),
+            checkpoint: Default::default(),
+            inner_data: Arc::new(RwLock::new(record.data)),
+        })
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::clock::mock;
 
     #[test]
     fn tap_mutates_session() {
@@ -109,4 +811,316 @@ mod test {
         assert_eq!(1, count);
         assert_eq!(1, session.inner_data.read().unwrap().count);
     }
+
+    #[test]
+    fn new_session_expires_after_lifespan() {
+        let session = Session::<()>::new(3600);
+        assert!(!session.expired());
+        assert!(session.expiration() > Utc::now() + Duration::seconds(3500));
+
+        let session = Session::<()>::new(-1);
+        assert!(session.expired());
+    }
+
+    #[test]
+    fn renew_moves_expiration_forward() {
+        let mut session = Session::<()>::new(-1);
+        assert!(session.expired());
+
+        session.renew(3600);
+
+        assert!(!session.expired());
+    }
+
+    #[test]
+    fn renew_to_sets_an_absolute_expiration() {
+        let mut session = Session::<()>::new(3600);
+        let end_of_day = Utc::now() + Duration::hours(8);
+
+        session.renew_to(end_of_day);
+        assert_eq!(session.expiration(), end_of_day);
+        assert!(!session.expired());
+    }
+
+    #[test]
+    fn renew_to_a_past_time_expires_the_session() {
+        let mut session = Session::<()>::new(3600);
+
+        session.renew_to(Utc::now() - Duration::hours(1));
+        assert!(session.expired());
+        // not even a generous clock skew tolerance keeps it alive
+        assert!(!session.is_valid(3600));
+    }
+
+    #[test]
+    fn force_expire_ignores_clock_skew_tolerance() {
+        let mut session = Session::<()>::new(3600);
+        session.force_expire();
+
+        assert!(session.expired());
+        assert!(session.clone().validate(3600).is_none());
+    }
+
+    #[test]
+    fn lifespan_is_kept_across_clones_and_serialization() {
+        let session = Session::<()>::new(3600);
+        assert_eq!(session.lifespan(), None);
+
+        session.set_lifespan(60);
+        assert!(session.is_dirty());
+        assert_eq!(session.clone().lifespan(), Some(60));
+
+        let json = serde_json::to_string(&session).unwrap();
+        let loaded: Session<()> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.lifespan(), Some(60));
+    }
+
+    #[test]
+    #[cfg(feature = "rocket")]
+    fn client_cert_binding_is_kept_across_clones_and_serialization() {
+        let mut session = Session::<()>::new(3600);
+        assert_eq!(session.client_cert(), None);
+
+        session.bind_client_cert("fingerprint".to_string());
+        assert!(session.is_dirty());
+        assert_eq!(session.clone().client_cert(), Some("fingerprint"));
+
+        let json = serde_json::to_string(&session).unwrap();
+        let loaded: Session<()> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.client_cert(), Some("fingerprint"));
+    }
+
+    #[test]
+    fn renew_extending_never_shortens_the_session() {
+        let hour = Duration::hours(1);
+        let mut extended = Session::<()>::new(3600);
+        let mut shortened = extended.clone();
+
+        extended.renew_extending(1800);
+        shortened.renew(1800);
+
+        assert!(extended.expiration() - clock::now() > hour - Duration::seconds(5));
+        assert!(shortened.expiration() - clock::now() <= Duration::minutes(30));
+    }
+
+    #[test]
+    fn an_invalidated_session_is_never_renewed() {
+        let mut session = Session::<()>::new(60);
+        let expiration = session.expiration();
+        session.invalidate();
+
+        session.renew(3600);
+        session.renew_extending(3600);
+        session.renew_to(clock::now() + Duration::hours(1));
+        assert_eq!(session.expiration(), expiration);
+    }
+
+    #[test]
+    fn debug_output_redacts_the_id() {
+        let session = Session::<()>::new(3600);
+        let output = format!("{:?}", session);
+
+        assert!(!output.contains(session.id_str()));
+        assert!(!output.contains(&session.csrf_token().0));
+        assert!(output.contains(&session.id_str()[..6]));
+    }
+
+    #[test]
+    fn tap_marks_the_session_dirty_but_read_does_not() {
+        let session = Session::<u32>::new(3600);
+        assert!(session.is_new());
+
+        assert_eq!(session.read(|count| *count), 0);
+        assert!(!session.is_dirty());
+
+        session.tap(|count| *count += 1);
+        assert!(session.is_dirty());
+        assert!(!session.clone().is_dirty());
+        assert!(!session.clone().is_new());
+    }
+
+    #[test]
+    fn data_eq_detects_changes_against_a_snapshot() {
+        let session = Session::<Vec<u32>>::new(3600);
+        let snapshot = session.read(Vec::clone);
+        let should_store = |session: &Session<Vec<u32>>| !session.data_eq(&snapshot);
+
+        session.tap(|data| data.clear());
+        assert!(session.data_eq(&snapshot));
+        assert!(!should_store(&session));
+
+        session.tap(|data| data.push(1));
+        assert!(!session.data_eq(&snapshot));
+        assert!(should_store(&session));
+    }
+
+    #[test]
+    fn data_hash_follows_the_data() {
+        let session = Session::<Vec<u32>>::new(3600);
+        let other = Session::<Vec<u32>>::new(3600);
+        session.tap(|data| data.push(1));
+        other.tap(|data| data.push(1));
+        // only the data counts, not the ID or timestamps
+        assert_eq!(session.data_hash().unwrap(), other.data_hash().unwrap());
+
+        let before = session.data_hash().unwrap();
+        session.tap(|data| data.push(2));
+        assert_ne!(session.data_hash().unwrap(), before);
+
+        session.tap(|data| {
+            data.pop();
+        });
+        assert_eq!(session.data_hash().unwrap(), before);
+    }
+
+    #[test]
+    fn data_hash_fails_for_data_json_cant_hold() {
+        let session = Session::<std::collections::BTreeMap<(u8, u8), u8>>::new(3600);
+        session.tap(|data| data.insert((1, 2), 3));
+
+        assert!(session.data_hash().is_err());
+    }
+
+    #[test]
+    fn borrow_reads_in_place_and_releases_the_lock() {
+        let session = Session::<Vec<u64>>::new(3600);
+        session.tap(|data| data.extend(0..100_000));
+        session.mark_clean();
+
+        let data = session.borrow();
+        assert_eq!(data.len(), 100_000);
+        assert_eq!(data.as_ptr(), session.read(|data| data.as_ptr()));
+        assert!(session.inner_data.try_write().is_err());
+        drop(data);
+
+        assert!(session.inner_data.try_write().is_ok());
+        assert!(!session.is_dirty());
+    }
+
+    #[test]
+    fn dirty_flag_can_be_set_manually() {
+        let session = Session::<u32>::new(3600);
+
+        session.mark_dirty();
+        assert!(session.is_dirty());
+
+        session.tap(|count| *count += 1);
+        session.mark_clean();
+        assert!(!session.is_dirty());
+    }
+
+    #[test]
+    fn with_id_keeps_the_given_id() {
+        let id = SessionID("fixture".to_string());
+        let session = Session::<u32>::with_id(id.clone(), 60);
+
+        assert_eq!(session.id(), &id);
+        assert!(session.is_new());
+        assert!(!session.expired());
+        assert!(!session.is_dirty());
+
+        let session = Session::with_id_and_data(id.clone(), 7, 60);
+        assert_eq!(session.id(), &id);
+        assert_eq!(session.read(|data| *data), 7);
+        assert!(session.is_new());
+    }
+
+    #[test]
+    fn keepalive_marks_the_session_dirty() {
+        let session = Session::<()>::new(60);
+        session.keepalive();
+
+        assert!(session.is_dirty());
+    }
+
+    #[test]
+    fn extend_if_valid_renews_a_valid_session() {
+        let session = Session::<()>::new(60);
+        mock::advance(30);
+
+        let session = session
+            .extend_if_valid(60, 0)
+            .expect("session is still valid");
+        mock::advance(45);
+
+        assert!(!session.expired());
+    }
+
+    #[test]
+    fn extend_if_valid_rejects_an_expired_session() {
+        let session = Session::<()>::new(60);
+        mock::advance(61);
+
+        assert!(session.extend_if_valid(60, 0).is_none());
+    }
+
+    #[test]
+    fn is_valid_allows_for_clock_skew() {
+        let session = Session::<()>::new(60);
+        mock::advance(65);
+
+        assert!(!session.is_valid(0));
+        assert!(session.is_valid(10));
+        assert!(session.clone().validate(10).is_some());
+
+        mock::advance(10);
+        assert!(!session.is_valid(10));
+    }
+
+    #[test]
+    fn serialization_round_trips_persisted_fields() {
+        let session = Session::<u32>::new(3600);
+        session.tap(|count| *count = 7);
+
+        let json = serde_json::to_string(&session).unwrap();
+        let restored: Session<u32> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.id(), session.id());
+        assert_eq!(restored.csrf_token().0, session.csrf_token().0);
+        assert_eq!(restored.expiration(), session.expiration());
+        assert_eq!(restored.read(|count| *count), 7);
+        assert!(!restored.is_new());
+    }
+
+    #[test]
+    fn summary_excludes_data_and_redacts_the_id() {
+        #[derive(Clone, Default, Serialize)]
+        struct Secret {
+            password: String,
+        }
+
+        let session = Session::<Secret>::new(3600);
+        session.tap(|secret| secret.password = "hunter2".to_string());
+
+        let summary = session.summary();
+        let json = serde_json::to_string(&summary).unwrap();
+        let display = summary.to_string();
+
+        assert_eq!(summary.id, session.id().redacted());
+        assert!(summary.is_valid);
+        for output in &[json, display] {
+            assert!(!output.contains("hunter2"));
+            assert!(!output.contains(session.id_str()));
+        }
+    }
+
+    #[test]
+    fn rollback_restores_the_checkpoint() {
+        let session = Session::<u32>::new(3600);
+        session.tap(|count| *count = 1);
+        session.mark_clean();
+
+        session.checkpoint();
+        session.tap(|count| *count = 2);
+        assert!(session.is_dirty());
+
+        session.rollback();
+        assert_eq!(session.read(|count| *count), 1);
+        assert!(!session.is_dirty());
+
+        // the checkpoint is consumed
+        session.tap(|count| *count = 3);
+        session.rollback();
+        assert_eq!(session.read(|count| *count), 3);
+    }
 }
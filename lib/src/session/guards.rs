@@ -0,0 +1,317 @@
+//! Request guards for the session of the current request.
+use log::error;
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    try_outcome, Request,
+};
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{
+    fairing::{config::CookieOverride, SessionState},
+    session::Session,
+};
+
+#[async_trait::async_trait]
+impl<'a, 'r, Data> FromRequest<'a, 'r> for &'a Session<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type Error = ();
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let state = request.managed_state::<SessionState<Data>>();
+        if state.is_none() && !SessionCached::<Data>::is_marked(request) {
+            error!(
+                "no session fairing for `{}` is attached, so there's no session to hand out",
+                std::any::type_name::<Data>()
+            );
+            return Outcome::Failure((Status::InternalServerError, ()));
+        }
+
+        let session = request.local_cache(|| {
+            let state = state.expect("the fairing caches a session when there's no state");
+            let mut session = Session::with_generator_and_data(
+                state.config.lifetime().idle_timeout,
+                &*state.id_generator,
+                state.data_factory.new_data(),
+            );
+            session.set_cookie_names(&state.config);
+
+            session
+        });
+
+        Outcome::Success(session)
+    }
+}
+
+/// A request guard for routes that should behave differently when the request has no session.
+/// Unlike `&Session`, which falls back to a throwaway session, this holds `None` when the fairing
+/// didn't establish a session for the request:
+///
+/// - the route is under one of `SessionConfig::peek_paths` (use [`PeekSession`] there), or no
+///   fairing for `Data` is attached;
+/// - the config is `read_only` and the request didn't carry a valid session;
+/// - the session is a throwaway one (see [`Session::is_ephemeral`]).
+pub struct MaybeSession<'r, Data>(pub Option<&'r Session<Data>>)
+where
+    Data: Clone + Send + Sync + 'static;
+
+#[async_trait::async_trait]
+impl<'a, 'r, Data> FromRequest<'a, 'r> for MaybeSession<'a, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type Error = ();
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        if !SessionCached::<Data>::is_marked(request) {
+            return Outcome::Success(MaybeSession(None));
+        }
+
+        let session: &Session<Data> =
+            request.local_cache(|| unreachable!("the fairing marks the session once cached"));
+        let read_only = match request.managed_state::<SessionState<Data>>() {
+            Some(state) => state.config.read_only,
+            None => false,
+        };
+
+        let established = !session.is_ephemeral() && !(read_only && session.is_new());
+        Outcome::Success(MaybeSession(if established { Some(session) } else { None }))
+    }
+}
+
+/// Marks that the fairing cached the request's session in `on_request`, so guards can tell a
+/// session it loaded or started apart from having to make one up.
+pub(crate) struct SessionCached<Data>(AtomicBool, PhantomData<fn() -> Data>);
+
+impl<Data: Send + Sync + 'static> SessionCached<Data> {
+    pub(crate) fn mark(request: &Request<'_>) {
+        request
+            .local_cache(|| Self(AtomicBool::new(false), PhantomData))
+            .0
+            .store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_marked(request: &Request<'_>) -> bool {
+        request
+            .local_cache(|| Self(AtomicBool::new(false), PhantomData))
+            .0
+            .load(Ordering::SeqCst)
+    }
+}
+
+/// The cookie attributes set through [`RouteOverride`], kept in the request's local cache.
+#[derive(Default)]
+pub(crate) struct RouteCookieOverride(Mutex<Option<CookieOverride>>);
+
+impl RouteCookieOverride {
+    pub(crate) fn get(&self) -> Option<CookieOverride> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// A request guard for overriding session cookie attributes (`max_age`, `path`, `SameSite`,
+/// etc.) for the current response, e.g. a stricter `SameSite` across an admin area, without
+/// touching the session itself.
+///
+/// Attributes are resolved in increasing order of precedence from the global `SessionConfig`,
+/// the session's own lifespan (see [`Session::set_lifespan`]), this override, and finally an
+/// override set with [`Session::set_cookie_override`].
+pub struct RouteOverride<'r>(&'r RouteCookieOverride);
+
+impl RouteOverride<'_> {
+    /// Replaces any override previously set for this response.
+    pub fn set(&self, cookie_override: CookieOverride) {
+        *(self.0).0.lock().unwrap() = Some(cookie_override);
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, 'r> FromRequest<'a, 'r> for RouteOverride<'a> {
+    type Error = ();
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RouteOverride(
+            request.local_cache(RouteCookieOverride::default),
+        ))
+    }
+}
+
+/// The session loaded for a peek route (see `SessionConfig::peek_paths`), kept in the request's
+/// local cache.
+pub(crate) struct Peeked<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    pub(crate) session: Option<Session<Data>>,
+    pub(crate) peeked: bool,
+}
+
+/// A request guard for inspecting the request's session without side effects, for routes under
+/// one of `SessionConfig::peek_paths`. Unlike `&Session`, which always yields a session, renewing
+/// it or starting a new one, this holds `None` when the request has no valid session, and the
+/// fairing neither renews, stores, nor sends a cookie for it.
+///
+/// Fails with `500 Internal Server Error` on routes outside `peek_paths`, where the session has
+/// already been renewed or created by the time the guard runs.
+pub struct PeekSession<'r, Data>(pub Option<&'r Session<Data>>)
+where
+    Data: Clone + Send + Sync + 'static;
+
+#[async_trait::async_trait]
+impl<'a, 'r, Data> FromRequest<'a, 'r> for PeekSession<'a, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type Error = ();
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let peeked: &Peeked<Data> = request.local_cache(|| Peeked {
+            session: None,
+            peeked: false,
+        });
+
+        if peeked.peeked {
+            Outcome::Success(PeekSession(peeked.session.as_ref()))
+        } else {
+            error!("`PeekSession` used on a route outside the session config's `peek_paths`");
+            Outcome::Failure((Status::InternalServerError, ()))
+        }
+    }
+}
+
+/// A request guard giving read-only access to the request's session data through `Deref`.
+///
+/// The guard holds a snapshot taken when the guard is resolved rather than a lock, so it's safe
+/// to hold across an `.await`; changes made through [`Session::tap`] after that point aren't
+/// visible through it.
+pub struct SessionData<'r, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    session: &'r Session<Data>,
+    data: Data,
+}
+
+impl<'r, Data> SessionData<'r, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    pub fn session(&self) -> &'r Session<Data> {
+        self.session
+    }
+}
+
+impl<'r, Data> Deref for SessionData<'r, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type Target = Data;
+
+    fn deref(&self) -> &Data {
+        &self.data
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, 'r, Data> FromRequest<'a, 'r> for SessionData<'a, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type Error = ();
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let session = try_outcome!(<&'a Session<Data>>::from_request(request).await);
+        let data = session.read(Data::clone);
+
+        Outcome::Success(SessionData { session, data })
+    }
+}
+
+/// A request guard giving mutable access to the request's session data through `DerefMut`.
+///
+/// Like [`SessionData`] it works on a copy of the data, which is written back to the session
+/// (and marks it as changed) when the guard is dropped. That write replaces any changes made
+/// through [`Session::tap`] while the guard was alive, so don't mix the two in one handler.
+pub struct SessionDataMut<'r, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    session: &'r Session<Data>,
+    data: Data,
+}
+
+impl<'r, Data> Deref for SessionDataMut<'r, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type Target = Data;
+
+    fn deref(&self) -> &Data {
+        &self.data
+    }
+}
+
+impl<'r, Data> DerefMut for SessionDataMut<'r, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    fn deref_mut(&mut self) -> &mut Data {
+        &mut self.data
+    }
+}
+
+impl<'r, Data> Drop for SessionDataMut<'r, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    fn drop(&mut self) {
+        let data = std::mem::take(&mut self.data);
+        self.session.tap(|inner| *inner = data);
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, 'r, Data> FromRequest<'a, 'r> for SessionDataMut<'a, Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type Error = ();
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let session = try_outcome!(<&'a Session<Data>>::from_request(request).await);
+        let data = session.read(Data::clone);
+
+        Outcome::Success(SessionDataMut { session, data })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn session_data_mut_writes_back_on_drop() {
+        let session = Session::<u32>::new(3600);
+
+        {
+            let mut data = SessionDataMut {
+                session: &session,
+                data: session.read(u32::clone),
+            };
+            *data += 5;
+            assert_eq!(session.read(|count| *count), 0);
+        }
+
+        assert_eq!(session.read(|count| *count), 5);
+        assert!(session.is_dirty());
+    }
+}
@@ -0,0 +1,317 @@
+//! A store that keeps an audit trail of every session it destroys, clears, or force-expires,
+//! e.g. for compliance.
+//!
+//! Records only ever hold the session ID's hash (see [`SessionID::hashed`]), never the ID itself.
+//! Sessions removed by `tidy`, or evicted by `index` to make room under `max_sessions`, aren't
+//! audited: they had expired or were pushed out, not ended by anyone.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::{io::Write, sync::Mutex};
+
+use crate::{
+    clock, fairing::config::SessionConfig, session::Session, store::SessionStore, SessionCursor,
+    SessionID,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Destroyed,
+    /// Expired with [`Session::force_expire`] or [`SessionStore::expire`], keeping the record.
+    ForceExpired,
+    /// Removed by [`SessionStore::retain`].
+    Removed,
+    /// Every session was removed by [`SessionStore::clear`].
+    Cleared,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub action: AuditAction,
+    /// The hash of the session's ID, or `None` for [`AuditAction::Cleared`].
+    pub session: Option<String>,
+}
+
+impl AuditRecord {
+    fn new(action: AuditAction, session_id: Option<&SessionID>) -> Self {
+        Self {
+            timestamp: clock::now(),
+            action,
+            session: session_id.map(SessionID::hashed),
+        }
+    }
+}
+
+type Sink = Box<dyn Fn(&AuditRecord) + Send + Sync>;
+
+pub struct AuditStore<Inner> {
+    inner: Inner,
+    sink: Sink,
+}
+
+impl<Inner> AuditStore<Inner> {
+    /// Passes every audit record to `sink` once the store operation it records has succeeded.
+    pub fn new(inner: Inner, sink: impl Fn(&AuditRecord) + Send + Sync + 'static) -> Self {
+        Self {
+            inner,
+            sink: Box::new(sink),
+        }
+    }
+
+    /// Writes every audit record to `writer` as a line of JSON.
+    pub fn to_writer(inner: Inner, writer: impl Write + Send + 'static) -> Self {
+        let writer = Mutex::new(writer);
+
+        Self::new(inner, move |record| {
+            let mut writer = writer.lock().unwrap();
+            let written = serde_json::to_writer(&mut *writer, record)
+                .map_err(Into::into)
+                .and_then(|()| writeln!(writer))
+                .and_then(|()| writer.flush());
+            if let Err(error) = written {
+                warn!("failed to write session audit record: {}", error);
+            }
+        })
+    }
+
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    fn audit(&self, action: AuditAction, session_id: Option<&SessionID>) {
+        (self.sink)(&AuditRecord::new(action, session_id));
+    }
+}
+
+#[async_trait::async_trait]
+impl<Inner: SessionStore> SessionStore for AuditStore<Inner> {
+    type SessionData = Inner::SessionData;
+
+    /// Logs audit records as JSON at the `info` level.
+    fn init() -> Self {
+        Self::new(Inner::init(), |record| {
+            if let Ok(record) = serde_json::to_string(record) {
+                info!(target: "turbopump::audit", "{}", record);
+            }
+        })
+    }
+
+    fn configure(&mut self, config: &SessionConfig) {
+        self.inner.configure(config);
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Self::SessionData>>> {
+        self.inner.load(session_id).await
+    }
+
+    async fn store(&self, session: Session<Self::SessionData>) -> Result<()> {
+        let force_expired = session.expiration() == DateTime::<Utc>::from(std::time::UNIX_EPOCH);
+        let id = session.id().clone();
+        self.inner.store(session).await?;
+
+        if force_expired {
+            self.audit(AuditAction::ForceExpired, Some(&id));
+        }
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.inner.clear().await?;
+        self.audit(AuditAction::Cleared, None);
+        Ok(())
+    }
+
+    async fn destroy(&self, session: Session<Self::SessionData>) -> Result<()> {
+        let id = session.id().clone();
+        self.inner.destroy(session).await?;
+        self.audit(AuditAction::Destroyed, Some(&id));
+        Ok(())
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        self.inner.tidy(tolerance).await
+    }
+
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<()> {
+        self.inner.renew(session_id, lifespan).await
+    }
+
+    async fn load_and_renew(
+        &self,
+        session_id: &SessionID,
+        lifespan: i64,
+        tolerance: i64,
+    ) -> Result<Option<Session<Self::SessionData>>> {
+        self.inner
+            .load_and_renew(session_id, lifespan, tolerance)
+            .await
+    }
+
+    async fn expire(&self, session_id: &SessionID) -> Result<()> {
+        self.inner.expire(session_id).await?;
+        self.audit(AuditAction::ForceExpired, Some(session_id));
+        Ok(())
+    }
+
+    async fn expires_at(&self, session_id: &SessionID) -> Result<Option<DateTime<Utc>>> {
+        self.inner.expires_at(session_id).await
+    }
+
+    async fn store_if_unchanged(
+        &self,
+        session: Session<Self::SessionData>,
+        expected_version: u64,
+    ) -> Result<bool> {
+        self.inner
+            .store_if_unchanged(session, expected_version)
+            .await
+    }
+
+    async fn index(
+        &self,
+        key: &str,
+        session_id: &SessionID,
+        max_sessions: Option<usize>,
+    ) -> Result<()> {
+        self.inner.index(key, session_id, max_sessions).await
+    }
+
+    async fn retain<Predicate>(&self, predicate: Predicate) -> Result<usize>
+    where
+        Predicate: Fn(&SessionID, &Session<Self::SessionData>) -> bool + Send + Sync,
+    {
+        let removed_ids = Mutex::new(Vec::new());
+        let removed = self
+            .inner
+            .retain(|id: &SessionID, session: &Session<Self::SessionData>| {
+                let keep = predicate(id, session);
+                if !keep {
+                    removed_ids.lock().unwrap().push(id.clone());
+                }
+                keep
+            })
+            .await?;
+
+        for id in removed_ids.into_inner().unwrap() {
+            self.audit(AuditAction::Removed, Some(&id));
+        }
+        Ok(removed)
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.inner.count().await
+    }
+
+    async fn approx_size_bytes(&self) -> Result<Option<u64>> {
+        self.inner.approx_size_bytes().await
+    }
+
+    async fn list(
+        &self,
+        cursor: Option<SessionCursor>,
+        limit: usize,
+    ) -> Result<(Vec<SessionID>, Option<SessionCursor>)> {
+        self.inner.list(cursor, limit).await
+    }
+
+    async fn export(&self) -> Result<Vec<Session<Self::SessionData>>> {
+        self.inner.export().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::in_memory::InMemory;
+    use std::sync::Arc;
+
+    fn recording_store() -> (AuditStore<InMemory<()>>, Arc<Mutex<Vec<AuditRecord>>>) {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&records);
+        let store = AuditStore::new(InMemory::init(), move |record: &AuditRecord| {
+            sink.lock().unwrap().push(record.clone())
+        });
+
+        (store, records)
+    }
+
+    #[tokio::test]
+    async fn a_destroy_is_audited_with_the_hashed_id() {
+        let (store, records) = recording_store();
+        let session = Session::new(3600);
+        let id = session.id().clone();
+        store.store(session.clone()).await.unwrap();
+        session.invalidate();
+        store.destroy(session).await.unwrap();
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].action, AuditAction::Destroyed);
+        assert_eq!(records[0].session, Some(id.hashed()));
+        assert!(!records[0].session.as_ref().unwrap().contains(&id.0));
+    }
+
+    #[tokio::test]
+    async fn expirations_removals_and_clears_are_audited() {
+        let (store, records) = recording_store();
+        let expired = Session::new(3600);
+        let removed = Session::new(3600);
+        store.store(expired.clone()).await.unwrap();
+        store.store(removed.clone()).await.unwrap();
+
+        store.expire(expired.id()).await.unwrap();
+        let mut force_expired = expired.clone();
+        force_expired.force_expire();
+        store.store(force_expired).await.unwrap();
+        store
+            .retain(|id: &SessionID, _: &Session<()>| id != removed.id())
+            .await
+            .unwrap();
+        store.clear().await.unwrap();
+
+        let records = records.lock().unwrap();
+        let actions: Vec<_> = records.iter().map(|record| record.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::ForceExpired,
+                AuditAction::ForceExpired,
+                AuditAction::Removed,
+                AuditAction::Cleared,
+            ]
+        );
+        assert_eq!(records[2].session, Some(removed.id().hashed()));
+        assert_eq!(records[3].session, None);
+    }
+
+    #[test]
+    fn records_are_written_as_json_lines() {
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let store = AuditStore::to_writer(InMemory::<()>::init(), buffer.clone());
+        let id = SessionID("session-id".to_string());
+        store.audit(AuditAction::Destroyed, Some(&id));
+
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let record: serde_json::Value = serde_json::from_str(written.trim_end()).unwrap();
+        assert!(written.ends_with('\n'));
+        assert_eq!(record["action"], "destroyed");
+        assert_eq!(record["session"], id.hashed());
+        assert!(!written.contains("session-id"));
+    }
+}
@@ -0,0 +1,310 @@
+//! A session store backed by [etcd](https://etcd.io), for deployments where the app nodes
+//! already share an etcd cluster (e.g. a service mesh).
+//!
+//! Each session is stored as JSON under `<key_prefix><id>`, attached to a lease whose
+//! TTL matches the session's remaining lifespan, so etcd deletes expired sessions on its own and
+//! [`SessionStore::tidy`] is a no-op.
+//!
+//! Operational caveats:
+//!
+//! - The cluster is read from the comma-separated `ETCD_ENDPOINTS` environment variable, and the
+//!   connection is made lazily on first use.
+//! - The key prefix is the session config's `key_prefix` unless one was set with
+//!   [`EtcdStore::with_key_prefix`]. `clear` deletes every key under it, so apps sharing a
+//!   cluster need distinct prefixes.
+//! - Every `store` grants a new lease; the previous lease is left to expire on its own. With
+//!   `StorePolicy::Always` that's one lease per request, so prefer `StorePolicy::OnChange`.
+//! - Lease TTLs are enforced by the etcd leader's clock, not the app node's, and etcd may keep a
+//!   key for a little while after its lease runs out. `load` checks the session's own
+//!   expiration too, so such stragglers are never resumed.
+//! - Values are limited by etcd's request size (1.5 MiB by default).
+//! - Sessions are tagged with a schema version; see [`EtcdStore::with_migrator`].
+//! - Sessions are written in the session config's `storage_format` unless one was set with
+//!   [`EtcdStore::with_storage_format`]; sessions in any format can be read.
+use anyhow::Result;
+use etcd_client::{Client, DeleteOptions, GetOptions, PutOptions};
+use futures::lock::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{borrow::Cow, env, marker::PhantomData, sync::Arc};
+
+use crate::{
+    clock,
+    error::SessionStoreError,
+    fairing::config::{SessionConfig, DEFAULT_KEY_PREFIX},
+    session::Session,
+    store::{
+        check_data_size,
+        schema::{self, Migrator, StorageFormat},
+        SessionStore,
+    },
+    SessionID,
+};
+
+pub struct EtcdStore<Data> {
+    endpoints: Vec<String>,
+    client: Mutex<Option<Client>>,
+    key_prefix: Option<Cow<'static, str>>,
+    max_data_bytes: Option<usize>,
+    migrator: Option<Arc<dyn Migrator<Data>>>,
+    storage_format: Option<StorageFormat>,
+    data: PhantomData<Data>,
+}
+
+impl<Data> EtcdStore<Data> {
+    pub fn with_endpoints(endpoints: Vec<String>) -> Self {
+        Self {
+            endpoints,
+            client: Mutex::new(None),
+            key_prefix: None,
+            max_data_bytes: None,
+            migrator: None,
+            storage_format: None,
+            data: PhantomData,
+        }
+    }
+
+    /// Stores sessions under `key_prefix`, taking precedence over the session config's.
+    pub fn with_key_prefix(mut self, key_prefix: impl Into<Cow<'static, str>>) -> Self {
+        self.key_prefix = Some(key_prefix.into());
+        self
+    }
+
+    /// Fails writes of sessions that serialize to more than `max_data_bytes` with a
+    /// `StoreFailure` instead of storing them.
+    pub fn with_max_data_bytes(mut self, max_data_bytes: usize) -> Self {
+        self.max_data_bytes = Some(max_data_bytes);
+        self
+    }
+
+    /// Upgrades sessions stored under an older schema version with `migrator` as they're loaded
+    /// (see [`schema`]), and tags stored sessions with its current version.
+    pub fn with_migrator(mut self, migrator: impl Migrator<Data>) -> Self {
+        self.migrator = Some(Arc::new(migrator));
+        self
+    }
+
+    /// Writes sessions in `storage_format`, taking precedence over the session config's.
+    pub fn with_storage_format(mut self, storage_format: StorageFormat) -> Self {
+        self.storage_format = Some(storage_format);
+        self
+    }
+
+    async fn client(&self) -> Result<Client> {
+        let mut client = self.client.lock().await;
+        if client.is_none() {
+            *client = Some(Client::connect(&self.endpoints, None).await?);
+        }
+
+        Ok(client.clone().unwrap())
+    }
+
+    fn prefix(&self) -> &str {
+        self.key_prefix.as_deref().unwrap_or(DEFAULT_KEY_PREFIX)
+    }
+
+    fn key(&self, session_id: &SessionID) -> String {
+        format!("{}{}", self.prefix(), session_id)
+    }
+}
+
+impl<Data> EtcdStore<Data>
+where
+    Data: Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    fn encode(&self, session: &Session<Data>) -> Result<Vec<u8>> {
+        let version = self
+            .migrator
+            .as_ref()
+            .map_or(0, |migrator| migrator.current_version());
+
+        let format = self.storage_format.unwrap_or_default();
+        schema::encode_as(session, version, format)
+            .map_err(|e| SessionStoreError::Serialization(e.to_string()).into())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Option<Session<Data>>> {
+        schema::decode(bytes, self.migrator.as_deref())
+            .map_err(|e| SessionStoreError::Serialization(e.to_string()).into())
+    }
+}
+
+#[async_trait::async_trait]
+impl<Data> SessionStore for EtcdStore<Data>
+where
+    Data: Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    type SessionData = Data;
+
+    fn init() -> Self {
+        let endpoints = env::var("ETCD_ENDPOINTS").unwrap_or_else(|_| "localhost:2379".into());
+
+        Self::with_endpoints(endpoints.split(',').map(|e| e.trim().to_string()).collect())
+    }
+
+    fn configure(&mut self, config: &SessionConfig) {
+        if self.key_prefix.is_none() {
+            self.key_prefix = Some(config.key_prefix.clone());
+        }
+        if self.storage_format.is_none() {
+            self.storage_format = Some(config.storage_format);
+        }
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Self::SessionData>>> {
+        let mut client = self.client().await?;
+        let response = client
+            .get(self.key(&session_id), None)
+            .await
+            .map_err(|e| SessionStoreError::LoadFailure(e.to_string()))?;
+
+        let session = match response.kvs().first() {
+            Some(kv) => self.decode(kv.value())?,
+            None => return Ok(None),
+        };
+
+        // the lease may have run out without etcd having removed the key yet
+        Ok(session.filter(|session| !session.expired()))
+    }
+
+    async fn store(&self, session: Session<Self::SessionData>) -> Result<()> {
+        let ttl = (session.expiration() - clock::now()).num_seconds();
+        if ttl <= 0 {
+            return Ok(());
+        }
+
+        let value = self.encode(&session)?;
+        check_data_size(value.len(), self.max_data_bytes)?;
+
+        // The data and its lease go out in a single put, so they can't be torn apart. If the put
+        // fails, the lease is left to expire unused.
+        let mut client = self.client().await?;
+        let lease = client
+            .lease_grant(ttl, None)
+            .await
+            .map_err(|e| SessionStoreError::StoreFailure(e.to_string()))?;
+        client
+            .put(
+                self.key(session.id()),
+                value,
+                Some(PutOptions::new().with_lease(lease.id())),
+            )
+            .await
+            .map_err(|e| SessionStoreError::StoreFailure(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<()> {
+        let mut client = self.client().await?;
+        client
+            .delete(self.prefix(), Some(DeleteOptions::new().with_prefix()))
+            .await
+            .map_err(|e| SessionStoreError::ClearFailure(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn destroy(&self, session: Session<Self::SessionData>) -> Result<()> {
+        let key = self.key(session.id());
+        let mut client = self.client().await?;
+        let response = client
+            .get(key.as_str(), None)
+            .await
+            .map_err(|e| SessionStoreError::DestroyFailure(e.to_string()))?;
+        let lease = match response.kvs().first() {
+            Some(kv) => kv.lease(),
+            None => return Err(SessionStoreError::NotFound.into()),
+        };
+
+        // revoking the lease deletes the key as well, but delete it explicitly in case it was
+        // stored without one
+        if lease != 0 {
+            client
+                .lease_revoke(lease)
+                .await
+                .map_err(|e| SessionStoreError::DestroyFailure(e.to_string()))?;
+        }
+        client
+            .delete(key, None)
+            .await
+            .map_err(|e| SessionStoreError::DestroyFailure(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Keeps the expired session under its existing lease, so the record lives on until the
+    /// session would have expired anyway. `store` can't be used, since it skips expired sessions.
+    async fn expire(&self, session_id: &SessionID) -> Result<()> {
+        let key = self.key(session_id);
+        let mut client = self.client().await?;
+        let response = client
+            .get(key.as_str(), None)
+            .await
+            .map_err(|e| SessionStoreError::LoadFailure(e.to_string()))?;
+        let (mut session, lease) = match response.kvs().first() {
+            Some(kv) => match self.decode(kv.value())? {
+                Some(session) => (session, kv.lease()),
+                None => return Err(SessionStoreError::NotFound.into()),
+            },
+            None => return Err(SessionStoreError::NotFound.into()),
+        };
+
+        session.force_expire();
+        let value = self.encode(&session)?;
+        let options = if lease != 0 {
+            Some(PutOptions::new().with_lease(lease))
+        } else {
+            None
+        };
+        client
+            .put(key, value, options)
+            .await
+            .map_err(|e| SessionStoreError::StoreFailure(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn tidy(&self, _tolerance: i64) -> Result<()> {
+        // etcd removes sessions itself when their leases expire
+        Ok(())
+    }
+
+    async fn export(&self) -> Result<Vec<Session<Self::SessionData>>> {
+        let mut client = self.client().await?;
+        let response = client
+            .get(self.prefix(), Some(GetOptions::new().with_prefix()))
+            .await
+            .map_err(|e| SessionStoreError::LoadFailure(e.to_string()))?;
+
+        let mut sessions = Vec::new();
+        for kv in response.kvs() {
+            // sessions that can't be migrated are left out, as `load` treats them as missing
+            sessions.extend(self.decode(kv.value())?);
+        }
+
+        Ok(sessions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keys_use_the_configured_prefix() {
+        let config = SessionConfig::builder()
+            .key_prefix("staging:")
+            .try_finish()
+            .unwrap();
+        let id = SessionID("abc".to_string());
+
+        let mut store = EtcdStore::<()>::with_endpoints(vec![]);
+        assert_eq!(store.key(&id), "turbopump:abc");
+        store.configure(&config);
+        assert_eq!(store.key(&id), "staging:abc");
+
+        let mut store = EtcdStore::<()>::with_endpoints(vec![]).with_key_prefix("app:");
+        store.configure(&config);
+        assert_eq!(store.key(&id), "app:abc");
+    }
+}
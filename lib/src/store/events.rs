@@ -0,0 +1,159 @@
+//! A store that broadcasts an event for every change it makes, e.g. to drive a live admin
+//! dashboard over server-sent events.
+//!
+//! Events are only seen by subscribers on the node that made the change; there's no cluster-wide
+//! feed. Subscribers that fall more than [`CHANNEL_CAPACITY`] events behind miss the oldest ones,
+//! per `tokio::sync::broadcast` semantics, which is logged as a warning.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::warn;
+use rocket::{
+    futures::stream::{self, Stream},
+    tokio::sync::broadcast::{self, error::RecvError},
+};
+
+use crate::{fairing::config::SessionConfig, session::Session, store::SessionStore, SessionID};
+
+/// How many events a subscriber may fall behind before it starts missing them.
+pub const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionEvent {
+    /// A session that wasn't in the store (or had expired) was stored.
+    Created(SessionID),
+    /// An existing session was stored again.
+    Updated(SessionID),
+    Renewed(SessionID),
+    Destroyed(SessionID),
+    Cleared,
+    /// Expired sessions were swept from the store.
+    Tidied,
+}
+
+pub struct EventStore<Inner> {
+    inner: Inner,
+    events: broadcast::Sender<SessionEvent>,
+}
+
+impl<Inner> EventStore<Inner> {
+    pub fn new(inner: Inner) -> Self {
+        let (events, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        Self { inner, events }
+    }
+
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Returns a stream of the events emitted from now on.
+    pub fn subscribe(&self) -> impl Stream<Item = SessionEvent> {
+        stream::unfold(self.events.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("session event subscriber lagged, missing {} events", missed);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    fn emit(&self, event: SessionEvent) {
+        // an error only means there are no subscribers
+        let _ = self.events.send(event);
+    }
+}
+
+#[async_trait::async_trait]
+impl<Inner: SessionStore> SessionStore for EventStore<Inner> {
+    type SessionData = Inner::SessionData;
+
+    fn init() -> Self {
+        Self::new(Inner::init())
+    }
+
+    fn configure(&mut self, config: &SessionConfig) {
+        self.inner.configure(config);
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Self::SessionData>>> {
+        self.inner.load(session_id).await
+    }
+
+    async fn store(&self, session: Session<Self::SessionData>) -> Result<()> {
+        let id = session.id().clone();
+        let existed = self.inner.expires_at(&id).await?.is_some();
+        self.inner.store(session).await?;
+
+        self.emit(if existed {
+            SessionEvent::Updated(id)
+        } else {
+            SessionEvent::Created(id)
+        });
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.inner.clear().await?;
+        self.emit(SessionEvent::Cleared);
+        Ok(())
+    }
+
+    async fn destroy(&self, session: Session<Self::SessionData>) -> Result<()> {
+        let id = session.id().clone();
+        self.inner.destroy(session).await?;
+        self.emit(SessionEvent::Destroyed(id));
+        Ok(())
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        self.inner.tidy(tolerance).await?;
+        self.emit(SessionEvent::Tidied);
+        Ok(())
+    }
+
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<()> {
+        self.inner.renew(session_id, lifespan).await?;
+        self.emit(SessionEvent::Renewed(session_id.clone()));
+        Ok(())
+    }
+
+    async fn expires_at(&self, session_id: &SessionID) -> Result<Option<DateTime<Utc>>> {
+        self.inner.expires_at(session_id).await
+    }
+
+    async fn approx_size_bytes(&self) -> Result<Option<u64>> {
+        self.inner.approx_size_bytes().await
+    }
+
+    async fn export(&self) -> Result<Vec<Session<Self::SessionData>>> {
+        self.inner.export().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::in_memory::InMemory;
+    use rocket::futures::StreamExt;
+
+    #[tokio::test]
+    async fn subscribers_receive_store_and_destroy_events() {
+        let store = EventStore::<InMemory<()>>::init();
+        let mut events = Box::pin(store.subscribe());
+
+        let session = Session::new(3600);
+        let id = session.id().clone();
+        store.store(session.clone()).await.unwrap();
+        store.store(session.clone()).await.unwrap();
+        session.invalidate();
+        store.destroy(session).await.unwrap();
+
+        assert_eq!(events.next().await, Some(SessionEvent::Created(id.clone())));
+        assert_eq!(events.next().await, Some(SessionEvent::Updated(id.clone())));
+        assert_eq!(events.next().await, Some(SessionEvent::Destroyed(id)));
+    }
+}
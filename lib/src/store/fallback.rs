@@ -0,0 +1,282 @@
+//! A store that falls back to per-node in-memory sessions while a networked store is unreachable.
+//!
+//! Attach the fairing with a `FallbackStore<Redis>` (say) so that a brief outage degrades to
+//! sessions kept on whichever node served the request, instead of failing every request. Once an
+//! operation on the primary fails, the primary is skipped for the retry interval and everything
+//! goes to the in-memory store; after that the next operation tries the primary again.
+//!
+//! Consistency caveats:
+//!
+//! - During an outage each node has its own sessions. A client whose requests are spread across
+//!   nodes gets a new session from every node that hasn't seen it before.
+//! - Changes made during the outage aren't copied back. A session created during the outage is
+//!   loaded from the node's fallback until its next write lands it in the primary; a session
+//!   that already existed in the primary reverts to its pre-outage copy.
+//! - Sessions destroyed during the outage are only removed from the fallback, so they come back
+//!   from the primary once it recovers, until they expire.
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use std::sync::Mutex;
+
+use crate::{
+    clock,
+    error::SessionStoreError,
+    fairing::config::SessionConfig,
+    session::Session,
+    store::{in_memory::InMemory, SessionStore},
+    SessionID,
+};
+
+/// How long the primary is skipped after a failure, unless set with
+/// [`FallbackStore::with_retry_interval`].
+const DEFAULT_RETRY_INTERVAL: i64 = 30;
+
+pub struct FallbackStore<Primary: SessionStore> {
+    primary: Primary,
+    fallback: InMemory<Primary::SessionData>,
+    retry_interval: Duration,
+    failed_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl<Primary: SessionStore> FallbackStore<Primary> {
+    pub fn new(primary: Primary) -> Self {
+        Self {
+            primary,
+            fallback: InMemory::init(),
+            retry_interval: Duration::seconds(DEFAULT_RETRY_INTERVAL),
+            failed_at: Mutex::new(None),
+        }
+    }
+
+    /// Skips the primary for `seconds` after an operation on it fails.
+    pub fn with_retry_interval(mut self, seconds: i64) -> Self {
+        self.retry_interval = Duration::seconds(seconds);
+        self
+    }
+
+    pub fn primary(&self) -> &Primary {
+        &self.primary
+    }
+
+    pub fn fallback(&self) -> &InMemory<Primary::SessionData> {
+        &self.fallback
+    }
+
+    /// Whether operations are currently being served by the fallback without trying the
+    /// primary.
+    pub fn is_degraded(&self) -> bool {
+        match *self.failed_at.lock().unwrap() {
+            Some(failed_at) => clock::now() < failed_at + self.retry_interval,
+            None => false,
+        }
+    }
+
+    /// Records the outcome of an operation on the primary, returning its value if it succeeded.
+    /// A `NotFound` error means the primary is reachable, so it doesn't count as a failure.
+    fn primary_result<T>(&self, operation: &str, result: Result<T>) -> Option<T> {
+        let mut failed_at = self.failed_at.lock().unwrap();
+        match result {
+            Ok(value) => {
+                *failed_at = None;
+                Some(value)
+            }
+            Err(error) if matches!(error.downcast_ref(), Some(SessionStoreError::NotFound)) => {
+                *failed_at = None;
+                None
+            }
+            Err(error) => {
+                warn!(
+                    "`{}` failed on the primary store, falling back to memory for {}s: {}",
+                    operation,
+                    self.retry_interval.num_seconds(),
+                    error
+                );
+                *failed_at = Some(clock::now());
+                None
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<Primary: SessionStore> SessionStore for FallbackStore<Primary> {
+    type SessionData = Primary::SessionData;
+
+    fn init() -> Self {
+        Self::new(Primary::init())
+    }
+
+    fn configure(&mut self, config: &SessionConfig) {
+        self.primary.configure(config);
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Self::SessionData>>> {
+        if !self.is_degraded() {
+            let result = self.primary.load(session_id.clone()).await;
+            if let Some(Some(session)) = self.primary_result("load", result) {
+                return Ok(Some(session));
+            }
+        }
+
+        // sessions created during an outage only exist in the fallback
+        self.fallback.load(session_id).await
+    }
+
+    async fn store(&self, session: Session<Self::SessionData>) -> Result<()> {
+        if !self.is_degraded() {
+            let result = self.primary.store(session.clone()).await;
+            if self.primary_result("store", result).is_some() {
+                return Ok(());
+            }
+        }
+
+        self.fallback.store(session).await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.primary.clear().await?;
+        self.fallback.clear().await
+    }
+
+    async fn destroy(&self, session: Session<Self::SessionData>) -> Result<()> {
+        // the session is in either store, depending on where it was last written
+        let fallback = self.fallback.destroy(session.clone()).await;
+        if !self.is_degraded() {
+            let result = self.primary.destroy(session).await;
+            if self.primary_result("destroy", result).is_some() {
+                return Ok(());
+            }
+        }
+
+        fallback
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        if !self.is_degraded() {
+            let result = self.primary.tidy(tolerance).await;
+            self.primary_result("tidy", result);
+        }
+
+        self.fallback.tidy(tolerance).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::mock;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// An `InMemory` store that can be taken down.
+    struct Flaky {
+        inner: InMemory<u32>,
+        down: AtomicBool,
+    }
+
+    impl Flaky {
+        fn check(&self) -> Result<()> {
+            if self.down.load(Ordering::SeqCst) {
+                Err(SessionStoreError::Connection("connection refused".to_string()).into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SessionStore for Flaky {
+        type SessionData = u32;
+
+        fn init() -> Self {
+            Self {
+                inner: InMemory::init(),
+                down: AtomicBool::new(false),
+            }
+        }
+
+        async fn load(&self, session_id: SessionID) -> Result<Option<Session<u32>>> {
+            self.check()?;
+            self.inner.load(session_id).await
+        }
+
+        async fn store(&self, session: Session<u32>) -> Result<()> {
+            self.check()?;
+            self.inner.store(session).await
+        }
+
+        async fn clear(&self) -> Result<()> {
+            self.check()?;
+            self.inner.clear().await
+        }
+
+        async fn destroy(&self, session: Session<u32>) -> Result<()> {
+            self.check()?;
+            self.inner.destroy(session).await
+        }
+
+        async fn tidy(&self, tolerance: i64) -> Result<()> {
+            self.check()?;
+            self.inner.tidy(tolerance).await
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_from_memory_while_the_primary_is_down() {
+        let store = FallbackStore::new(Flaky::init());
+        store.primary().down.store(true, Ordering::SeqCst);
+
+        let session = Session::new(3600);
+        session.tap(|count| *count = 3);
+        store.store(session.clone()).await.unwrap();
+        assert!(store.is_degraded());
+
+        let loaded = store.load(session.id().clone()).await.unwrap().unwrap();
+        assert_eq!(loaded.read(|count| *count), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_the_primary_after_the_interval() {
+        let store = FallbackStore::new(Flaky::init()).with_retry_interval(10);
+        store.primary().down.store(true, Ordering::SeqCst);
+        let session = Session::new(3600);
+        store.store(session.clone()).await.unwrap();
+
+        // the primary is back, but isn't tried until the interval has passed
+        store.primary().down.store(false, Ordering::SeqCst);
+        store.store(session.clone()).await.unwrap();
+        assert!(store
+            .primary()
+            .inner
+            .load(session.id().clone())
+            .await
+            .unwrap()
+            .is_none());
+
+        mock::advance(11);
+        store.store(session.clone()).await.unwrap();
+        assert!(!store.is_degraded());
+        assert!(store
+            .primary()
+            .inner
+            .load(session.id().clone())
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn destroys_sessions_created_during_the_outage() {
+        let store = FallbackStore::new(Flaky::init()).with_retry_interval(10);
+        store.primary().down.store(true, Ordering::SeqCst);
+        let session = Session::new(3600);
+        store.store(session.clone()).await.unwrap();
+
+        store.primary().down.store(false, Ordering::SeqCst);
+        mock::advance(11);
+        session.invalidate();
+        store.destroy(session.clone()).await.unwrap();
+
+        assert!(store.load(session.id().clone()).await.unwrap().is_none());
+    }
+}
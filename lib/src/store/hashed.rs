@@ -0,0 +1,200 @@
+//! A store that keys sessions by the SHA-256 hash of their ID (see [`SessionID::hashed`]) rather
+//! than the ID itself, so a leaked key list (e.g. from a slow-query log, or filenames) can't be
+//! used to hijack sessions.
+//!
+//! The client's cookie still carries the raw ID; the inner store only ever sees the hash, both as
+//! the key and as the ID inside the stored session. Since the hash can't be reversed, the
+//! operations that hand out IDs from the store (`retain`'s predicate, `list`, and `export`) give
+//! hashed IDs, and switching an existing store to hashed keys orphans its sessions.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    fairing::config::SessionConfig, session::Session, store::SessionStore, SessionCursor, SessionID,
+};
+
+pub struct HashedStore<Inner> {
+    inner: Inner,
+}
+
+impl<Inner> HashedStore<Inner> {
+    pub fn new(inner: Inner) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+}
+
+/// The key a session with ID `session_id` is stored under.
+fn key(session_id: &SessionID) -> SessionID {
+    SessionID(session_id.hashed())
+}
+
+fn hide<Data>(mut session: Session<Data>) -> Session<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    let key = key(session.id());
+    session.set_id(key);
+    session
+}
+
+fn reveal<Data>(mut session: Session<Data>, session_id: &SessionID) -> Session<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    session.set_id(session_id.clone());
+    session
+}
+
+#[async_trait::async_trait]
+impl<Inner: SessionStore> SessionStore for HashedStore<Inner> {
+    type SessionData = Inner::SessionData;
+
+    fn init() -> Self {
+        Self::new(Inner::init())
+    }
+
+    fn configure(&mut self, config: &SessionConfig) {
+        self.inner.configure(config);
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Self::SessionData>>> {
+        let session = self.inner.load(key(&session_id)).await?;
+
+        Ok(session.map(|session| reveal(session, &session_id)))
+    }
+
+    async fn store(&self, session: Session<Self::SessionData>) -> Result<()> {
+        self.inner.store(hide(session)).await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.inner.clear().await
+    }
+
+    async fn destroy(&self, session: Session<Self::SessionData>) -> Result<()> {
+        self.inner.destroy(hide(session)).await
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        self.inner.tidy(tolerance).await
+    }
+
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<()> {
+        self.inner.renew(&key(session_id), lifespan).await
+    }
+
+    async fn load_and_renew(
+        &self,
+        session_id: &SessionID,
+        lifespan: i64,
+        tolerance: i64,
+    ) -> Result<Option<Session<Self::SessionData>>> {
+        let session = self
+            .inner
+            .load_and_renew(&key(session_id), lifespan, tolerance)
+            .await?;
+
+        Ok(session.map(|session| reveal(session, session_id)))
+    }
+
+    async fn expire(&self, session_id: &SessionID) -> Result<()> {
+        self.inner.expire(&key(session_id)).await
+    }
+
+    async fn expires_at(&self, session_id: &SessionID) -> Result<Option<DateTime<Utc>>> {
+        self.inner.expires_at(&key(session_id)).await
+    }
+
+    async fn store_if_unchanged(
+        &self,
+        session: Session<Self::SessionData>,
+        expected_version: u64,
+    ) -> Result<bool> {
+        self.inner
+            .store_if_unchanged(hide(session), expected_version)
+            .await
+    }
+
+    async fn index(
+        &self,
+        key_name: &str,
+        session_id: &SessionID,
+        max_sessions: Option<usize>,
+    ) -> Result<()> {
+        self.inner
+            .index(key_name, &key(session_id), max_sessions)
+            .await
+    }
+
+    async fn retain<Predicate>(&self, predicate: Predicate) -> Result<usize>
+    where
+        Predicate: Fn(&SessionID, &Session<Self::SessionData>) -> bool + Send + Sync,
+    {
+        self.inner.retain(predicate).await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.inner.count().await
+    }
+
+    async fn approx_size_bytes(&self) -> Result<Option<u64>> {
+        self.inner.approx_size_bytes().await
+    }
+
+    async fn list(
+        &self,
+        cursor: Option<SessionCursor>,
+        limit: usize,
+    ) -> Result<(Vec<SessionID>, Option<SessionCursor>)> {
+        self.inner.list(cursor, limit).await
+    }
+
+    async fn export(&self) -> Result<Vec<Session<Self::SessionData>>> {
+        self.inner.export().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::in_memory::InMemory;
+
+    #[tokio::test]
+    async fn sessions_are_stored_under_the_hash_and_loaded_by_the_raw_id() {
+        let store = HashedStore::new(InMemory::<u32>::init());
+        let session = Session::new(3600);
+        session.tap(|count| *count = 7);
+        let id = session.id().clone();
+        store.store(session).await.unwrap();
+
+        let (stored_ids, _) = store.inner().list(None, 10).await.unwrap();
+        assert_eq!(stored_ids, vec![SessionID(id.hashed())]);
+        assert!(store.inner().load(id.clone()).await.unwrap().is_none());
+
+        let loaded = store.load(id.clone()).await.unwrap().unwrap();
+        assert_eq!(loaded.id(), &id);
+        assert_eq!(loaded.read(|count| *count), 7);
+
+        let renewed = store.load_and_renew(&id, 7200, 0).await.unwrap().unwrap();
+        assert_eq!(renewed.id(), &id);
+        assert!(store.expires_at(&id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn a_destroy_removes_the_hashed_record() {
+        let store = HashedStore::new(InMemory::<u32>::init());
+        let session = Session::new(3600);
+        let id = session.id().clone();
+        store.store(session.clone()).await.unwrap();
+
+        session.invalidate();
+        store.destroy(session).await.unwrap();
+
+        assert!(store.load(id).await.unwrap().is_none());
+        assert_eq!(store.inner().count().await.unwrap(), 0);
+    }
+}
@@ -1,46 +1,361 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use flurry::HashMap;
-use std::sync::Arc;
+use serde::Serialize;
+use std::{
+    collections::{BinaryHeap, HashMap as StdHashMap},
+    sync::{Arc, Mutex},
+};
 
-use crate::{error::SessionStoreError, session::Session, store::SessionStore, SessionID};
+use crate::{
+    error::SessionStoreError,
+    session::Session,
+    store::{check_data_size, SessionStore},
+    SessionCursor, SessionID,
+};
 
+/// Measures the size of a session's data, for `InMemory::with_max_data_bytes`.
+type SizeOf<Data> = fn(&Data) -> Result<usize>;
+
+/// How [`InMemory::tidy`](SessionStore::tidy) removes expired sessions.
+///
+/// Either way, removing an entry only locks its bin of the map, so concurrent loads never block
+/// and concurrent writes only contend for the bins being tidied.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TidyMode {
+    /// Removes expired sessions while scanning the map, all under one pin of the map's epoch
+    /// guard. Memory freed by concurrent writes can't be reclaimed until the whole scan is done.
+    Retain,
+    /// Collects the IDs of expired sessions in one scan, then removes them one at a time under
+    /// separate pins, re-checking each in case it was renewed in the meantime. Keeps each pin
+    /// short at the cost of a second lookup per expired session.
+    TwoPhase,
+}
+
+impl Default for TidyMode {
+    fn default() -> Self {
+        Self::Retain
+    }
+}
+
+/// What a write to an [`InMemory`] store does with the data of a session that other requests
+/// may have loaded and changed in the meantime.
+pub enum WriteMode<Data> {
+    /// Loads return clones sharing the stored session's data, so concurrent requests for a
+    /// session see each other's changes as they make them, and no change to the data is lost.
+    /// A write still replaces the session's metadata (e.g. its expiration) with that of the
+    /// writer's copy.
+    Shared,
+    /// Loads return independent copies of the data, and each write replaces the stored data, so
+    /// the last of several concurrent writes wins, like in stores that serialize sessions.
+    LastWriteWins,
+    /// Loads return independent copies of the data, and each write merges its data into the
+    /// stored data with the function, called with the stored data and the data being written.
+    Merge(fn(&mut Data, Data)),
+}
+
+impl<Data> Clone for WriteMode<Data> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Data> Copy for WriteMode<Data> {}
+
+impl<Data> Default for WriteMode<Data> {
+    fn default() -> Self {
+        Self::Shared
+    }
+}
+
+/// A store keeping sessions in a concurrent map, local to the process.
+///
+/// By default the store shares each session's data with every request that loads it (see
+/// [`WriteMode::Shared`]), so concurrent requests on this node never lose each other's changes.
+///
+/// Each operation on a session is atomic with respect to the others on that session, and none
+/// blocks loads. In particular a `tidy` never removes a session renewed or rewritten while it
+/// runs: [`TidyMode::Retain`] only removes the very entry it found expired, and
+/// [`TidyMode::TwoPhase`] re-checks each entry under its lock. A load followed by a `store` is
+/// not atomic, though; use `store_if_unchanged` to detect concurrent writes in between.
 #[derive(Clone)]
 pub struct InMemory<Data>
 where
-    Data: Clone + Default + Send + Sync + 'static,
+    Data: Clone + Send + Sync + 'static,
 {
     sessions: Arc<HashMap<SessionID, Session<Data>>>,
+    indexes: Arc<Mutex<StdHashMap<String, Vec<SessionID>>>>,
+    max_data_bytes: Option<(usize, SizeOf<Data>)>,
+    size_of: Option<SizeOf<Data>>,
+    tidy_mode: TidyMode,
+    tidy_batch: Option<usize>,
+    max_sessions: Option<usize>,
+    write_mode: WriteMode<Data>,
+}
+
+impl<Data> InMemory<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    pub fn with_tidy_mode(mut self, tidy_mode: TidyMode) -> Self {
+        self.tidy_mode = tidy_mode;
+        self
+    }
+
+    /// Limits each `tidy` to removing `batch` expired sessions, those that expired first going
+    /// first, to bound the time a single tidy takes. Tidies then collect the expired sessions
+    /// before removing them, like [`TidyMode::TwoPhase`], whatever the tidy mode.
+    pub fn with_tidy_batch(mut self, batch: usize) -> Self {
+        self.tidy_batch = Some(batch);
+        self
+    }
+
+    /// Caps the number of sessions held. Storing a new session in a full store first evicts the
+    /// sessions expiring soonest (expired ones first, ties broken by ID), which are the least
+    /// likely to be used again. The cap isn't enforced atomically, so concurrent writes may
+    /// briefly overshoot it.
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = Some(max_sessions);
+        self
+    }
+
+    pub fn with_write_mode(mut self, write_mode: WriteMode<Data>) -> Self {
+        self.write_mode = write_mode;
+        self
+    }
+
+    /// The copy of `session` to hand out or keep, per the write mode.
+    fn copy(&self, session: &Session<Data>) -> Session<Data> {
+        match self.write_mode {
+            WriteMode::Shared => session.clone(),
+            _ => session.with_data(session.read(Clone::clone)),
+        }
+    }
+
+    fn sweep(&self, tolerance: i64) {
+        match (self.tidy_mode, self.tidy_batch) {
+            (TidyMode::Retain, None) => {
+                let sessions_ref = self.sessions.pin();
+                sessions_ref.retain(|_, session| session.is_valid(tolerance));
+            }
+            (_, batch) => {
+                let mut session_ids = self.expired_ids(tolerance);
+                if let Some(batch) = batch {
+                    session_ids.truncate(batch);
+                }
+
+                self.remove_expired(session_ids, tolerance)
+            }
+        }
+    }
+
+    /// The IDs of the sessions matching `filter`, soonest-expiring first and then by ID, so
+    /// eviction and batched tidies are deterministic.
+    fn ids_by_expiration(&self, filter: impl Fn(&Session<Data>) -> bool) -> Vec<SessionID> {
+        let mut candidates: Vec<_> = self
+            .sessions
+            .pin()
+            .iter()
+            .filter(|(_, session)| filter(session))
+            .map(|(session_id, session)| (session.expiration(), session_id.clone()))
+            .collect();
+        candidates.sort_unstable();
+
+        candidates
+            .into_iter()
+            .map(|(_, session_id)| session_id)
+            .collect()
+    }
+
+    /// The IDs of the `count` sessions expiring soonest, ordered like [`Self::ids_by_expiration`].
+    /// Only the `count` best candidates are kept while scanning, so for the usual `count` of one
+    /// this is a single linear pass rather than a sort of every session.
+    fn soonest_expiring(&self, count: usize) -> Vec<SessionID> {
+        let mut soonest: BinaryHeap<(DateTime<Utc>, SessionID)> = BinaryHeap::new();
+        for (session_id, session) in self.sessions.pin().iter() {
+            let expiration = session.expiration();
+            if soonest.len() < count {
+                soonest.push((expiration, session_id.clone()));
+            } else if let Some(mut latest) = soonest.peek_mut() {
+                if (expiration, session_id) < (latest.0, &latest.1) {
+                    *latest = (expiration, session_id.clone());
+                }
+            }
+        }
+
+        soonest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(_, session_id)| session_id)
+            .collect()
+    }
+
+    fn expired_ids(&self, tolerance: i64) -> Vec<SessionID> {
+        self.ids_by_expiration(|session| !session.is_valid(tolerance))
+    }
+
+    /// Evicts the sessions expiring soonest until there's room for `session_id` under
+    /// `max_sessions`.
+    fn make_room(&self, session_id: &SessionID) {
+        let max_sessions = match self.max_sessions {
+            Some(max_sessions) => max_sessions,
+            None => return,
+        };
+
+        let sessions_ref = self.sessions.pin();
+        if sessions_ref.contains_key(session_id) || sessions_ref.len() < max_sessions {
+            return;
+        }
+
+        let excess = sessions_ref.len() + 1 - max_sessions;
+        for evicted in self.soonest_expiring(excess) {
+            sessions_ref.remove(&evicted);
+        }
+    }
+
+    fn remove_expired(&self, session_ids: Vec<SessionID>, tolerance: i64) {
+        for session_id in session_ids {
+            self.sessions
+                .pin()
+                .compute_if_present(&session_id, |_, session| {
+                    Some(session.clone()).filter(|session| session.is_valid(tolerance))
+                });
+        }
+    }
+}
+
+impl<Data> InMemory<Data>
+where
+    Data: Clone + Send + Sync + Serialize + 'static,
+{
+    /// Rejects writes of sessions whose data serializes to more than `max_data_bytes` of JSON,
+    /// failing them with a `StoreFailure` instead, so a handler that keeps growing its session
+    /// can't grow the store's memory without bound. Data that can't be serialized is rejected
+    /// with a `Serialization` error. Only available when `Data` is `Serialize`, and each write
+    /// pays for serializing the data.
+    pub fn with_max_data_bytes(mut self, max_data_bytes: usize) -> Self {
+        self.max_data_bytes = Some((max_data_bytes, serialized_len::<Data>));
+        self.size_of = Some(serialized_len::<Data>);
+        self
+    }
+
+    /// Makes [`approx_size_bytes`](SessionStore::approx_size_bytes) report the total size of
+    /// the sessions' IDs and data serialized to JSON, rather than `None`. It's an estimate of
+    /// the live data, not of the memory the map takes, and serializes every session each time
+    /// it's called. Only available when `Data` is `Serialize`; `with_max_data_bytes` enables it
+    /// too.
+    pub fn with_size_estimate(mut self) -> Self {
+        self.size_of = Some(serialized_len::<Data>);
+        self
+    }
+}
+
+#[cfg(feature = "snapshot")]
+impl<Data> InMemory<Data>
+where
+    Data: Clone + Send + Sync + Serialize + serde::de::DeserializeOwned + 'static,
+{
+    /// Serializes every unexpired session, with its ID, expiration, and data, to JSON, for
+    /// [`InMemory::restore`] to load into a new store (e.g. across a restart, or to set up a
+    /// test). Indexes aren't included. Requires the `snapshot` feature.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let sessions_ref = self.sessions.pin();
+        let sessions: Vec<_> = sessions_ref
+            .values()
+            .filter(|session| session.is_valid(0))
+            .collect();
+
+        serde_json::to_vec(&sessions).expect("sessions serialize to JSON")
+    }
+
+    /// Builds a store holding the sessions of a [`InMemory::snapshot`], skipping those that
+    /// expired since it was taken. Configure it with the `with_` methods as usual.
+    pub fn restore(bytes: &[u8]) -> Result<Self> {
+        let sessions: Vec<Session<Data>> = serde_json::from_slice(bytes)
+            .map_err(|error| SessionStoreError::Serialization(error.to_string()))?;
+
+        let store = Self::init();
+        let sessions_ref = store.sessions.pin();
+        for session in sessions.into_iter().filter(|session| session.is_valid(0)) {
+            sessions_ref.insert(session.id().clone(), session);
+        }
+        drop(sessions_ref);
+
+        Ok(store)
+    }
+}
+
+fn serialized_len<Data: Serialize>(data: &Data) -> Result<usize> {
+    let json = serde_json::to_vec(data)
+        .map_err(|error| SessionStoreError::Serialization(error.to_string()))?;
+
+    Ok(json.len())
 }
 
 #[async_trait::async_trait]
 impl<Data> SessionStore for InMemory<Data>
 where
-    Data: Clone + Default + Send + Sync + 'static,
+    Data: Clone + Send + Sync + 'static,
 {
     type SessionData = Data;
 
     fn init() -> Self {
         Self {
             sessions: Default::default(),
+            indexes: Default::default(),
+            max_data_bytes: None,
+            size_of: None,
+            tidy_mode: TidyMode::default(),
+            tidy_batch: None,
+            max_sessions: None,
+            write_mode: WriteMode::default(),
         }
     }
 
     async fn load(&self, session_id: SessionID) -> Result<Option<Session<Self::SessionData>>> {
         let sessions_ref = self.sessions.pin();
-        let session = sessions_ref.get(&session_id).cloned();
+        let session = sessions_ref
+            .get(&session_id)
+            .map(|session| self.copy(session));
 
         Ok(session)
     }
 
-    async fn store(&self, session: Session<Self::SessionData>) -> Result<()> {
+    async fn store(&self, mut session: Session<Self::SessionData>) -> Result<()> {
+        if let Some((max_data_bytes, size_of)) = self.max_data_bytes {
+            check_data_size(session.read(size_of)?, Some(max_data_bytes))?;
+        }
+        self.make_room(session.id());
+
         let sessions_ref = self.sessions.pin();
-        sessions_ref.insert(session.id().clone(), session);
+        let version = sessions_ref
+            .get(session.id())
+            .map_or(0, |stored| stored.version());
+        session.set_version(version + 1);
+
+        if let WriteMode::Merge(merge) = self.write_mode {
+            let mut merged = false;
+            sessions_ref.compute_if_present(session.id(), |_, stored| {
+                let mut data = stored.read(Clone::clone);
+                merge(&mut data, session.read(Clone::clone));
+                merged = true;
+                Some(session.with_data(data))
+            });
+            if merged {
+                return Ok(());
+            }
+        }
+
+        sessions_ref.insert(session.id().clone(), self.copy(&session));
 
         Ok(())
     }
 
     async fn clear(&self) -> Result<()> {
-        todo!()
+        self.sessions.pin().clear();
+        self.indexes.lock().unwrap().clear();
+
+        Ok(())
     }
 
     async fn destroy(&self, session: Session<Self::SessionData>) -> Result<()> {
@@ -54,6 +369,786 @@ where
                 })?
         }
 
-        Err(SessionStoreError::Unknown.into())
+        Ok(())
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        self.sweep(tolerance);
+
+        Ok(())
+    }
+
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<()> {
+        let sessions_ref = self.sessions.pin();
+        let mut renewed = false;
+        sessions_ref.compute_if_present(session_id, |_, session| {
+            let mut session = session.clone();
+            if !session.expired() {
+                session.renew_extending(lifespan);
+                renewed = true;
+            }
+
+            Some(session)
+        });
+
+        if renewed {
+            Ok(())
+        } else {
+            Err(SessionStoreError::NotFound.into())
+        }
+    }
+
+    async fn load_and_renew(
+        &self,
+        session_id: &SessionID,
+        lifespan: i64,
+        tolerance: i64,
+    ) -> Result<Option<Session<Self::SessionData>>> {
+        let sessions_ref = self.sessions.pin();
+        let mut renewed = None;
+        sessions_ref.compute_if_present(session_id, |_, session| {
+            let mut session = session.clone();
+            if session.is_valid(tolerance) {
+                session.renew_extending(session.lifespan().unwrap_or(lifespan));
+                renewed = Some(self.copy(&session));
+            }
+
+            Some(session)
+        });
+
+        Ok(renewed)
+    }
+
+    async fn expires_at(&self, session_id: &SessionID) -> Result<Option<DateTime<Utc>>> {
+        let sessions_ref = self.sessions.pin();
+        let expiration = sessions_ref
+            .get(session_id)
+            .filter(|session| !session.expired())
+            .map(|session| session.expiration());
+
+        Ok(expiration)
+    }
+
+    async fn store_if_unchanged(
+        &self,
+        mut session: Session<Self::SessionData>,
+        expected_version: u64,
+    ) -> Result<bool> {
+        let sessions_ref = self.sessions.pin();
+        let id = session.id().clone();
+        session.set_version(expected_version + 1);
+
+        if expected_version == 0 {
+            self.make_room(&id);
+            return Ok(sessions_ref.try_insert(id, self.copy(&session)).is_ok());
+        }
+
+        // the version check and the write happen atomically under the entry's lock
+        let mut swapped = false;
+        sessions_ref.compute_if_present(&id, |_, stored| {
+            if stored.version() == expected_version {
+                swapped = true;
+                Some(self.copy(&session))
+            } else {
+                Some(stored.clone())
+            }
+        });
+
+        Ok(swapped)
+    }
+
+    async fn index(
+        &self,
+        key: &str,
+        session_id: &SessionID,
+        max_sessions: Option<usize>,
+    ) -> Result<()> {
+        let sessions_ref = self.sessions.pin();
+        let mut indexes = self.indexes.lock().unwrap();
+        let ids = indexes.entry(key.to_string()).or_default();
+
+        ids.retain(|id| sessions_ref.contains_key(id));
+        if !ids.contains(session_id) {
+            ids.push(session_id.clone());
+        }
+
+        if let Some(max_sessions) = max_sessions {
+            // the session being indexed is always kept
+            let max_sessions = max_sessions.max(1);
+            if ids.len() > max_sessions {
+                // Oldest first, except for the session being indexed, which sorts last. The sort
+                // is stable, so sessions created at the same instant are evicted in the order
+                // they were indexed.
+                ids.sort_by_key(|id| {
+                    (
+                        id == session_id,
+                        sessions_ref.get(id).map(|session| session.created()),
+                    )
+                });
+
+                for id in ids.drain(..ids.len() - max_sessions) {
+                    sessions_ref.remove(&id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn retain<Predicate>(&self, predicate: Predicate) -> Result<usize>
+    where
+        Predicate: Fn(&SessionID, &Session<Self::SessionData>) -> bool + Send + Sync,
+    {
+        let mut removed = 0;
+        self.sessions.pin().retain(|session_id, session| {
+            let keep = predicate(session_id, session);
+            if !keep {
+                removed += 1;
+            }
+
+            keep
+        });
+
+        Ok(removed)
+    }
+
+    async fn count(&self) -> Result<usize> {
+        Ok(self.sessions.len())
+    }
+
+    /// Lists sessions in order of their IDs; the cursor is the last ID listed. Each page scans
+    /// and sorts the IDs after the cursor.
+    async fn list(
+        &self,
+        cursor: Option<SessionCursor>,
+        limit: usize,
+    ) -> Result<(Vec<SessionID>, Option<SessionCursor>)> {
+        if limit == 0 {
+            return Ok((Vec::new(), cursor));
+        }
+
+        let after = cursor.map(|cursor| SessionID(cursor.0));
+        let mut ids: Vec<SessionID> = self
+            .sessions
+            .pin()
+            .iter()
+            .filter(|(id, _)| match &after {
+                Some(after) => *id > after,
+                None => true,
+            })
+            .filter(|(_, session)| !session.expired())
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort_unstable();
+
+        let next = if ids.len() > limit {
+            ids.truncate(limit);
+            ids.last().map(|id| SessionCursor(id.0.clone()))
+        } else {
+            None
+        };
+
+        Ok((ids, next))
+    }
+
+    async fn approx_size_bytes(&self) -> Result<Option<u64>> {
+        let size_of = match self.size_of {
+            Some(size_of) => size_of,
+            None => return Ok(None),
+        };
+
+        let mut bytes = 0;
+        for (session_id, session) in self.sessions.pin().iter() {
+            bytes += (session_id.0.len() + session.read(size_of)?) as u64;
+        }
+
+        Ok(Some(bytes))
+    }
+
+    async fn export(&self) -> Result<Vec<Session<Self::SessionData>>> {
+        let sessions_ref = self.sessions.pin();
+        let sessions = sessions_ref
+            .values()
+            .map(|session| self.copy(session))
+            .collect();
+
+        Ok(sessions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn renew_extends_a_stored_session() {
+        let store = InMemory::<()>::init();
+        let session = Session::new(10);
+        let id = session.id().clone();
+        store.store(session).await.unwrap();
+
+        store.renew(&id, 3600).await.unwrap();
+
+        let session = store.load(id).await.unwrap().unwrap();
+        assert!(session.expiration() > chrono::Utc::now() + chrono::Duration::seconds(3500));
+    }
+
+    #[tokio::test]
+    async fn renew_fails_for_missing_or_expired_sessions() {
+        let store = InMemory::<()>::init();
+        let expired = Session::new(-1);
+        let id = expired.id().clone();
+        store.store(expired).await.unwrap();
+
+        for id in &[id, "missing".into()] {
+            let error = store.renew(id, 3600).await.unwrap_err();
+            assert!(matches!(
+                error.downcast_ref(),
+                Some(SessionStoreError::NotFound)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn load_and_renew_returns_the_renewed_session() {
+        let store = InMemory::<u32>::init();
+        let session = Session::new(10);
+        session.tap(|count| *count = 3);
+        store.store(session.clone()).await.unwrap();
+
+        let renewed = store
+            .load_and_renew(session.id(), 3600, 0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(renewed.read(|count| *count), 3);
+        assert!(renewed.expiration() > chrono::Utc::now() + chrono::Duration::seconds(3500));
+
+        let stored = store.expires_at(session.id()).await.unwrap();
+        assert_eq!(stored, Some(renewed.expiration()));
+    }
+
+    #[tokio::test]
+    async fn load_and_renew_skips_expired_sessions() {
+        let store = InMemory::<u32>::init();
+        let expired = Session::new(-5);
+        store.store(expired.clone()).await.unwrap();
+
+        assert!(store
+            .load_and_renew(expired.id(), 3600, 0)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(store
+            .load_and_renew(expired.id(), 3600, 10)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(store
+            .load_and_renew(&"missing".into(), 3600, 0)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn index_evicts_the_oldest_sessions_over_the_limit() {
+        let store = InMemory::<()>::init();
+
+        let mut ids = Vec::new();
+        for _ in 0..4 {
+            let session = Session::new(3600);
+            let id = session.id().clone();
+            store.store(session).await.unwrap();
+            store.index("user:42", &id, Some(3)).await.unwrap();
+            ids.push(id);
+        }
+
+        assert!(store.load(ids[0].clone()).await.unwrap().is_none());
+        for id in &ids[1..] {
+            assert!(store.load(id.clone()).await.unwrap().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn index_never_evicts_the_session_being_indexed() {
+        let store = InMemory::<()>::init();
+        let oldest = Session::new(3600);
+        store.store(oldest.clone()).await.unwrap();
+
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let session = Session::new(3600);
+            let id = session.id().clone();
+            store.store(session).await.unwrap();
+            store.index("user:42", &id, Some(3)).await.unwrap();
+            ids.push(id);
+        }
+        store.index("user:42", oldest.id(), Some(3)).await.unwrap();
+
+        assert!(store.load(oldest.id().clone()).await.unwrap().is_some());
+        assert!(store.load(ids[0].clone()).await.unwrap().is_none());
+        for id in &ids[1..] {
+            assert!(store.load(id.clone()).await.unwrap().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn tidy_removes_expired_sessions() {
+        let store = InMemory::<()>::init();
+        let expired = Session::new(-1);
+        let valid = Session::new(3600);
+        store.store(expired.clone()).await.unwrap();
+        store.store(valid.clone()).await.unwrap();
+
+        store.tidy(0).await.unwrap();
+
+        let remaining = store.export().await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id(), valid.id());
+    }
+
+    #[tokio::test]
+    async fn a_full_store_evicts_the_soonest_expiring_sessions() {
+        let store = InMemory::<()>::init().with_max_sessions(3);
+        let lifespans = [600, -1, 3600, 60];
+        let mut ids = Vec::new();
+        for lifespan in &lifespans {
+            let session = Session::new(*lifespan);
+            ids.push(session.id().clone());
+            store.store(session).await.unwrap();
+        }
+
+        // the expired session made room for the last one
+        assert_eq!(store.count().await.unwrap(), 3);
+        assert!(store.load(ids[1].clone()).await.unwrap().is_none());
+
+        let session = Session::new(3600);
+        store.store(session.clone()).await.unwrap();
+        assert!(store.load(ids[3].clone()).await.unwrap().is_none());
+        for id in &[&ids[0], &ids[2], session.id()] {
+            assert!(store.load((*id).clone()).await.unwrap().is_some());
+        }
+
+        // rewriting a stored session doesn't evict anything
+        store.store(session).await.unwrap();
+        assert_eq!(store.count().await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn batched_tidies_remove_the_longest_expired_sessions_first() {
+        let store = InMemory::<()>::init().with_tidy_batch(2);
+        let mut ids = Vec::new();
+        for lifespan in &[-10, -30, 3600, -20] {
+            let session = Session::new(*lifespan);
+            ids.push(session.id().clone());
+            store.store(session).await.unwrap();
+        }
+
+        store.tidy(0).await.unwrap();
+        let mut remaining: Vec<_> = store
+            .export()
+            .await
+            .unwrap()
+            .iter()
+            .map(|session| session.id().clone())
+            .collect();
+        remaining.sort();
+        let mut expected = vec![ids[0].clone(), ids[2].clone()];
+        expected.sort();
+        assert_eq!(remaining, expected);
+
+        store.tidy(0).await.unwrap();
+        assert_eq!(store.count().await.unwrap(), 1);
+    }
+
+    /// Loads a stored session twice, as two concurrent requests would, and has each copy add an
+    /// item from its own thread before storing it back.
+    async fn concurrent_writes(store: InMemory<Vec<u32>>) -> Vec<u32> {
+        let session = Session::new(3600);
+        let id = session.id().clone();
+        store.store(session).await.unwrap();
+
+        let first = store.load(id.clone()).await.unwrap().unwrap();
+        let second = store.load(id.clone()).await.unwrap().unwrap();
+        let taps: Vec<_> = [(first.clone(), 1), (second.clone(), 2)]
+            .iter()
+            .cloned()
+            .map(|(session, item)| std::thread::spawn(move || session.tap(|data| data.push(item))))
+            .collect();
+        for tap in taps {
+            tap.join().unwrap();
+        }
+        store.store(first).await.unwrap();
+        store.store(second).await.unwrap();
+
+        let mut data = store.load(id).await.unwrap().unwrap().read(Clone::clone);
+        data.sort_unstable();
+        data
+    }
+
+    #[tokio::test]
+    async fn shared_writes_keep_every_change() {
+        let store = InMemory::init();
+        assert_eq!(concurrent_writes(store).await, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn the_last_write_wins_with_independent_copies() {
+        let store = InMemory::init().with_write_mode(WriteMode::LastWriteWins);
+        assert_eq!(concurrent_writes(store).await, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn merged_writes_keep_every_change() {
+        fn merge(stored: &mut Vec<u32>, written: Vec<u32>) {
+            for item in written {
+                if !stored.contains(&item) {
+                    stored.push(item);
+                }
+            }
+        }
+
+        let store = InMemory::init().with_write_mode(WriteMode::Merge(merge));
+        assert_eq!(concurrent_writes(store).await, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn list_pages_through_every_unexpired_session_once() {
+        let store = InMemory::<()>::init();
+        let mut ids = Vec::new();
+        for _ in 0..25 {
+            let session = Session::new(3600);
+            ids.push(session.id().clone());
+            store.store(session).await.unwrap();
+        }
+        let mut expired = Session::new(3600);
+        expired.force_expire();
+        store.store(expired).await.unwrap();
+
+        let mut listed = Vec::new();
+        let mut cursor = None;
+        let mut pages = 0;
+        loop {
+            let (page, next) = store.list(cursor, 10).await.unwrap();
+            assert!(page.len() <= 10);
+            listed.extend(page);
+            pages += 1;
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        ids.sort();
+        assert_eq!(listed, ids);
+        assert_eq!(pages, 3);
+    }
+
+    #[tokio::test]
+    async fn retain_removes_sessions_failing_the_predicate() {
+        let store = InMemory::<String>::init();
+        let mut ids = Vec::new();
+        for role in &["admin", "banned_user", "user", "banned_user"] {
+            let session = Session::new(3600);
+            session.tap(|data| *data = role.to_string());
+            ids.push(session.id().clone());
+            store.store(session).await.unwrap();
+        }
+
+        let removed = store
+            .retain(|_, session| session.read(|role| role != "banned_user"))
+            .await
+            .unwrap();
+        assert_eq!(removed, 2);
+
+        let mut survivors: Vec<_> = store
+            .export()
+            .await
+            .unwrap()
+            .iter()
+            .map(|session| session.read(Clone::clone))
+            .collect();
+        survivors.sort();
+        assert_eq!(survivors, vec!["admin", "user"]);
+        assert!(store.load(ids[1].clone()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn two_phase_tidy_removes_expired_sessions() {
+        let store = InMemory::<()>::init().with_tidy_mode(TidyMode::TwoPhase);
+        let expired = Session::new(-1);
+        let valid = Session::new(3600);
+        store.store(expired.clone()).await.unwrap();
+        store.store(valid.clone()).await.unwrap();
+        assert_eq!(store.count().await.unwrap(), 2);
+
+        store.tidy(0).await.unwrap();
+
+        let remaining = store.export().await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id(), valid.id());
+        assert_eq!(store.count().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn two_phase_tidy_keeps_sessions_renewed_between_phases() {
+        let store = InMemory::<()>::init().with_tidy_mode(TidyMode::TwoPhase);
+        let mut session = Session::new(-1);
+        store.store(session.clone()).await.unwrap();
+
+        let expired = store.expired_ids(0);
+        assert_eq!(expired, vec![session.id().clone()]);
+        session.renew(3600);
+        store.store(session.clone()).await.unwrap();
+        store.remove_expired(expired, 0);
+
+        assert!(store.load(session.id().clone()).await.unwrap().is_some());
+    }
+
+    #[test]
+    fn tidy_modes_keep_valid_sessions_under_concurrent_writes() {
+        const WRITERS: usize = 4;
+        const SESSIONS: usize = 2_000;
+
+        for &mode in &[TidyMode::Retain, TidyMode::TwoPhase] {
+            let store = InMemory::<()>::init().with_tidy_mode(mode);
+            for _ in 0..SESSIONS {
+                let session = Session::new(-1);
+                store.sessions.pin().insert(session.id().clone(), session);
+            }
+
+            let running = Arc::new(AtomicUsize::new(WRITERS));
+            let writers: Vec<_> = (0..WRITERS)
+                .map(|_| {
+                    let store = store.clone();
+                    let running = running.clone();
+                    std::thread::spawn(move || {
+                        for _ in 0..SESSIONS {
+                            let session = Session::new(3600);
+                            let sessions_ref = store.sessions.pin();
+                            sessions_ref.insert(session.id().clone(), session.clone());
+                            assert!(sessions_ref.get(session.id()).is_some());
+                        }
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                })
+                .collect();
+            while running.load(Ordering::SeqCst) > 0 {
+                store.sweep(0);
+            }
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            store.sweep(0);
+
+            assert_eq!(store.sessions.len(), WRITERS * SESSIONS, "{:?}", mode);
+        }
+    }
+
+    #[tokio::test]
+    async fn store_if_unchanged_rejects_conflicting_writes() {
+        let store = InMemory::<u32>::init();
+        let session = Session::new(3600);
+        let id = session.id().clone();
+        assert!(store.store_if_unchanged(session.clone(), 0).await.unwrap());
+        assert!(!store.store_if_unchanged(session, 0).await.unwrap());
+
+        // two requests load the same version and race to write it back
+        let first = store.load(id.clone()).await.unwrap().unwrap();
+        let second = store.load(id.clone()).await.unwrap().unwrap();
+        assert_eq!(first.version(), 1);
+
+        let (first, second) = tokio::join!(
+            store.store_if_unchanged(first.clone(), first.version()),
+            store.store_if_unchanged(second.clone(), second.version()),
+        );
+        assert!(first.unwrap() ^ second.unwrap());
+
+        let stored = store.load(id).await.unwrap().unwrap();
+        assert_eq!(stored.version(), 2);
+    }
+
+    #[tokio::test]
+    async fn expire_keeps_the_record_until_tidied() {
+        let store = InMemory::<u32>::init();
+        let session = Session::new(3600);
+        session.tap(|count| *count = 5);
+        store.store(session.clone()).await.unwrap();
+
+        store.expire(session.id()).await.unwrap();
+
+        let expired = store.load(session.id().clone()).await.unwrap().unwrap();
+        assert_eq!(expired.read(|count| *count), 5);
+        assert!(expired.validate(0).is_none());
+
+        store.tidy(0).await.unwrap();
+        assert!(store.load(session.id().clone()).await.unwrap().is_none());
+
+        let error = store.expire(session.id()).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SessionStoreError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn expires_at_reports_unexpired_sessions_only() {
+        let store = InMemory::<()>::init();
+        let valid = Session::new(3600);
+        let expired = Session::new(-1);
+        store.store(valid.clone()).await.unwrap();
+        store.store(expired.clone()).await.unwrap();
+
+        assert_eq!(
+            store.expires_at(valid.id()).await.unwrap(),
+            Some(valid.expiration())
+        );
+        assert_eq!(store.expires_at(expired.id()).await.unwrap(), None);
+        assert_eq!(store.expires_at(&"missing".into()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn store_rejects_oversized_data() {
+        let store = InMemory::<String>::init().with_max_data_bytes(16);
+        let small = Session::new(3600);
+        small.tap(|data| *data = "short".to_string());
+        let large = Session::new(3600);
+        large.tap(|data| *data = "x".repeat(64));
+
+        store.store(small).await.unwrap();
+
+        let error = store.store(large.clone()).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SessionStoreError::StoreFailure(_))
+        ));
+        assert!(store.load(large.id().clone()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn data_that_cant_be_serialized_is_rejected() {
+        use std::collections::BTreeMap;
+
+        let store = InMemory::init().with_max_data_bytes(1024);
+        let session = Session::<BTreeMap<(u8, u8), u8>>::new(3600);
+        session.tap(|data| data.insert((1, 2), 3));
+
+        let error = store.store(session.clone()).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SessionStoreError::Serialization(_))
+        ));
+        assert!(store.load(session.id().clone()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_writes_leave_the_previous_copy_intact() {
+        let store = InMemory::<String>::init().with_max_data_bytes(16);
+        let session = Session::new(60);
+        session.tap(|data| *data = "v1".to_string());
+        store.store(session.clone()).await.unwrap();
+
+        // a copy that doesn't share its data with the stored session, as a serializing store
+        // would load it, is renewed and changed, but the write fails partway
+        let json = serde_json::to_string(&session).unwrap();
+        let mut updated: Session<String> = serde_json::from_str(&json).unwrap();
+        updated.renew(3600);
+        updated.tap(|data| *data = "x".repeat(64));
+        assert!(store.store(updated).await.is_err());
+
+        let current = store.load(session.id().clone()).await.unwrap().unwrap();
+        assert_eq!(current.expiration(), session.expiration());
+        assert_eq!(current.read(String::clone), "v1");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "snapshot")]
+    async fn a_snapshot_restores_into_a_new_store() {
+        let store = InMemory::<u32>::init();
+        let sessions: Vec<_> = (1..=3).map(|_| Session::new(3600)).collect();
+        for (count, session) in sessions.iter().enumerate() {
+            session.tap(|data| *data = count as u32);
+            store.store(session.clone()).await.unwrap();
+        }
+        let expired = Session::new(-1);
+        store.store(expired.clone()).await.unwrap();
+
+        let restored = InMemory::<u32>::restore(&store.snapshot()).unwrap();
+
+        assert_eq!(restored.count().await.unwrap(), 3);
+        for (count, session) in sessions.iter().enumerate() {
+            let loaded = restored.load(session.id().clone()).await.unwrap().unwrap();
+            assert_eq!(loaded.read(|data| *data), count as u32);
+            assert_eq!(loaded.expiration(), session.expiration());
+        }
+        assert!(restored.load(expired.id().clone()).await.unwrap().is_none());
+        assert!(InMemory::<u32>::restore(b"not a snapshot").is_err());
+    }
+
+    #[tokio::test]
+    async fn approx_size_bytes_sums_serialized_sessions() {
+        let store = InMemory::<String>::init();
+        assert_eq!(store.approx_size_bytes().await.unwrap(), None);
+
+        let store = store.with_size_estimate();
+        assert_eq!(store.approx_size_bytes().await.unwrap(), Some(0));
+
+        let session = Session::new(3600);
+        session.tap(|data| *data = "hello".to_string());
+        store.store(session.clone()).await.unwrap();
+        store.store(Session::new(3600)).await.unwrap();
+
+        // two 40-character IDs, `"hello"`, and `""`
+        assert_eq!(store.approx_size_bytes().await.unwrap(), Some(89));
+    }
+
+    #[tokio::test]
+    async fn tidy_never_removes_a_session_renewed_concurrently() {
+        use std::sync::atomic::AtomicBool;
+
+        // with this tolerance, tidies treat sessions as expired until they're renewed past 3601s
+        const TOLERANCE: i64 = -3601;
+
+        for (mode, batch) in &[
+            (TidyMode::Retain, None),
+            (TidyMode::TwoPhase, None),
+            (TidyMode::Retain, Some(8)),
+        ] {
+            let mut store = InMemory::<u32>::init().with_tidy_mode(*mode);
+            if let Some(batch) = batch {
+                store = store.with_tidy_batch(*batch);
+            }
+            let sessions: Vec<_> = (0..500).map(|_| Session::new(3600)).collect();
+            for session in &sessions {
+                store.store(session.clone()).await.unwrap();
+            }
+
+            let done = Arc::new(AtomicBool::new(false));
+            let tidier = {
+                let (store, done) = (store.clone(), Arc::clone(&done));
+                std::thread::spawn(move || {
+                    while !done.load(Ordering::SeqCst) {
+                        store.sweep(TOLERANCE);
+                    }
+                })
+            };
+
+            let mut renewed = Vec::new();
+            for session in &sessions {
+                if store.renew(session.id(), 7200).await.is_ok() {
+                    renewed.push(session.id().clone());
+                }
+            }
+            done.store(true, Ordering::SeqCst);
+            tidier.join().unwrap();
+            store.sweep(TOLERANCE);
+
+            assert_eq!(store.count().await.unwrap(), renewed.len());
+            for session_id in renewed {
+                assert!(store.load(session_id).await.unwrap().is_some());
+            }
+        }
     }
 }
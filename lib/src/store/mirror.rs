@@ -0,0 +1,213 @@
+//! A store that mirrors writes to a second store, for migrating between stores without downtime.
+//!
+//! Attach the fairing with a `MirrorStore<Old, New>` so every write lands in both stores while
+//! reads are still served by the old one, then switch to `New` once it has caught up (e.g. after
+//! a full session lifetime, or after copying the rest over with [`migrate`](super::migrate)).
+//!
+//! Consistency caveats:
+//!
+//! - The writes aren't atomic across the two stores: a request can observe a session in the
+//!   primary that hasn't reached the secondary yet, and a secondary failure under
+//!   [`SecondaryFailurePolicy::Log`] leaves the secondary stale until the session's next write.
+//! - Sessions written only before mirroring started exist in the primary alone.
+//! - Expirations are renewed in both stores, but the stores' clocks and tidy schedules are
+//!   independent, so a session may outlive its expiration in one of them for a while.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::warn;
+
+use crate::{
+    fairing::config::SessionConfig, session::Session, store::SessionStore, SessionCursor, SessionID,
+};
+
+/// What a [`MirrorStore`] does when a write to its secondary store fails.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SecondaryFailurePolicy {
+    /// Log the error and report success, as long as the primary write succeeded.
+    Log,
+    /// Return the error.
+    Fail,
+}
+
+impl Default for SecondaryFailurePolicy {
+    fn default() -> Self {
+        Self::Log
+    }
+}
+
+pub struct MirrorStore<Primary, Secondary> {
+    primary: Primary,
+    secondary: Secondary,
+    policy: SecondaryFailurePolicy,
+}
+
+impl<Primary, Secondary> MirrorStore<Primary, Secondary> {
+    pub fn new(primary: Primary, secondary: Secondary) -> Self {
+        Self {
+            primary,
+            secondary,
+            policy: SecondaryFailurePolicy::default(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: SecondaryFailurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn primary(&self) -> &Primary {
+        &self.primary
+    }
+
+    pub fn secondary(&self) -> &Secondary {
+        &self.secondary
+    }
+
+    fn mirrored(&self, operation: &str, result: Result<()>) -> Result<()> {
+        match (result, self.policy) {
+            (Err(error), SecondaryFailurePolicy::Log) => {
+                warn!(
+                    "mirrored `{}` failed on the secondary store: {}",
+                    operation, error
+                );
+                Ok(())
+            }
+            (result, _) => result,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<Data, Primary, Secondary> SessionStore for MirrorStore<Primary, Secondary>
+where
+    Data: Clone + Send + Sync + 'static,
+    Primary: SessionStore<SessionData = Data>,
+    Secondary: SessionStore<SessionData = Data>,
+{
+    type SessionData = Data;
+
+    /// Initializes both stores, logging secondary failures.
+    fn init() -> Self {
+        Self::new(Primary::init(), Secondary::init())
+    }
+
+    fn configure(&mut self, config: &SessionConfig) {
+        self.primary.configure(config);
+        self.secondary.configure(config);
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Data>>> {
+        self.primary.load(session_id).await
+    }
+
+    async fn store(&self, session: Session<Data>) -> Result<()> {
+        self.primary.store(session.clone()).await?;
+        self.mirrored("store", self.secondary.store(session).await)
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.primary.clear().await?;
+        self.mirrored("clear", self.secondary.clear().await)
+    }
+
+    async fn destroy(&self, session: Session<Data>) -> Result<()> {
+        self.primary.destroy(session.clone()).await?;
+        self.mirrored("destroy", self.secondary.destroy(session).await)
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        self.primary.tidy(tolerance).await?;
+        self.mirrored("tidy", self.secondary.tidy(tolerance).await)
+    }
+
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<()> {
+        self.primary.renew(session_id, lifespan).await?;
+        self.mirrored("renew", self.secondary.renew(session_id, lifespan).await)
+    }
+
+    async fn expire(&self, session_id: &SessionID) -> Result<()> {
+        self.primary.expire(session_id).await?;
+        self.mirrored("expire", self.secondary.expire(session_id).await)
+    }
+
+    async fn expires_at(&self, session_id: &SessionID) -> Result<Option<DateTime<Utc>>> {
+        self.primary.expires_at(session_id).await
+    }
+
+    async fn retain<Predicate>(&self, predicate: Predicate) -> Result<usize>
+    where
+        Predicate: Fn(&SessionID, &Session<Data>) -> bool + Send + Sync,
+    {
+        let removed = self.primary.retain(&predicate).await?;
+        let mirrored = self.secondary.retain(&predicate).await.map(|_| ());
+        self.mirrored("retain", mirrored)?;
+
+        Ok(removed)
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.primary.count().await
+    }
+
+    async fn approx_size_bytes(&self) -> Result<Option<u64>> {
+        self.primary.approx_size_bytes().await
+    }
+
+    async fn list(
+        &self,
+        cursor: Option<SessionCursor>,
+        limit: usize,
+    ) -> Result<(Vec<SessionID>, Option<SessionCursor>)> {
+        self.primary.list(cursor, limit).await
+    }
+
+    async fn export(&self) -> Result<Vec<Session<Data>>> {
+        self.primary.export().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::in_memory::InMemory;
+
+    type Store = MirrorStore<InMemory<u32>, InMemory<u32>>;
+
+    #[tokio::test]
+    async fn writes_land_in_both_stores() {
+        let store = Store::init();
+        let session = Session::new(3600);
+        session.tap(|count| *count = 7);
+        store.store(session.clone()).await.unwrap();
+
+        let loaded = store.load(session.id().clone()).await.unwrap().unwrap();
+        assert_eq!(loaded.read(|count| *count), 7);
+
+        let mirrored = store.secondary().load(session.id().clone()).await;
+        assert_eq!(mirrored.unwrap().unwrap().read(|count| *count), 7);
+
+        session.invalidate();
+        store.destroy(session.clone()).await.unwrap();
+        assert!(store
+            .primary()
+            .load(session.id().clone())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(store
+            .secondary()
+            .load(session.id().clone())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn reads_are_served_by_the_primary() {
+        let store = Store::init();
+        let session = Session::new(3600);
+        store.secondary().store(session.clone()).await.unwrap();
+
+        assert!(store.load(session.id().clone()).await.unwrap().is_none());
+    }
+}
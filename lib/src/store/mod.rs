@@ -1,19 +1,335 @@
 use anyhow::Result;
 use async_trait::async_trait;
 
-use crate::{session::Session, SessionID};
+use chrono::{DateTime, Utc};
 
+use crate::{
+    error::SessionStoreError, fairing::config::SessionConfig, session::Session, SessionCursor,
+    SessionID,
+};
+
+pub mod audit;
+#[cfg(feature = "etcd")]
+pub mod etcd;
+#[cfg(feature = "rocket")]
+pub mod events;
+pub mod fallback;
+pub mod hashed;
 pub mod in_memory;
+pub mod mirror;
+pub mod replicated;
+pub mod retry;
+pub mod schema;
+#[cfg(feature = "service")]
+pub mod service;
+pub mod sharded;
+pub mod timed;
 
 #[async_trait]
 pub trait SessionStore: Send + Sync + 'static {
-    type SessionData: Clone + Default + Send + Sync + 'static;
+    type SessionData: Clone + Send + Sync + 'static;
 
     fn init() -> Self
     where
         Self: Sized;
+
+    /// Called by the fairing with the session config before the store is put in managed
+    /// state, for stores that take settings (e.g. `key_prefix`) from it. Does nothing by
+    /// default.
+    fn configure(&mut self, _config: &SessionConfig) {}
+
     async fn load(&self, session_id: SessionID) -> Result<Option<Session<Self::SessionData>>>;
+
+    /// Persists the session's ID, data, and expiration together, so that a failed or
+    /// interrupted write leaves either the previous copy or the new one, never the new data with
+    /// the old expiration or vice versa (e.g. a single SQL row update, or `SET ... EX` in Redis).
+    ///
+    /// Backends that can't write both atomically must write the data first and the expiration
+    /// second: a write interrupted in between then leaves a session that expires on its old
+    /// schedule, rather than stale data kept alive by a new expiration.
     async fn store(&self, session: Session<Self::SessionData>) -> Result<()>;
     async fn clear(&self) -> Result<()>;
     async fn destroy(&self, session: Session<Self::SessionData>) -> Result<()>;
+
+    /// Removes every session that is no longer valid given `tolerance` seconds of allowed clock
+    /// skew (see [`Session::is_valid`]). Called by the fairing's lottery sweep, so it must be
+    /// safe to run concurrently with other store operations and with itself.
+    async fn tidy(&self, tolerance: i64) -> Result<()>;
+
+    /// Extends the expiration of the session identified by `session_id` to at least `lifespan`
+    /// seconds from now (see [`Session::renew_extending`]). Returns
+    /// [`SessionStoreError::NotFound`] when there's no unexpired session to renew, so the caller
+    /// can start a new one instead.
+    ///
+    /// The default implementation loads, renews, and stores the whole session; stores that can
+    /// update the expiration on its own should override it.
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<()> {
+        match self.load(session_id.clone()).await? {
+            Some(mut session) if !session.expired() => {
+                session.renew_extending(lifespan);
+                self.store(session).await
+            }
+            _ => Err(SessionStoreError::NotFound.into()),
+        }
+    }
+
+    /// Loads the session identified by `session_id` and, if it's still valid given `tolerance`
+    /// seconds of clock skew, extends its expiration like [`SessionStore::renew`], by the
+    /// session's own lifespan if it has one (see [`Session::set_lifespan`]) or `lifespan`
+    /// otherwise. Returns the renewed session, or `None` if there's no valid session. The renewal
+    /// is persisted before the session is returned, including for a session that had expired
+    /// within the tolerance.
+    ///
+    /// The default implementation loads the session, renews it, and stores it back, which is two
+    /// round trips; stores that can do both in one (e.g. Redis `GETEX`, or SQL
+    /// `UPDATE ... RETURNING`) should override it.
+    async fn load_and_renew(
+        &self,
+        session_id: &SessionID,
+        lifespan: i64,
+        tolerance: i64,
+    ) -> Result<Option<Session<Self::SessionData>>> {
+        let mut session = match self.load(session_id.clone()).await? {
+            Some(session) if session.is_valid(tolerance) => session,
+            _ => return Ok(None),
+        };
+
+        session.renew_extending(session.lifespan().unwrap_or(lifespan));
+        self.store(session.clone()).await?;
+
+        Ok(Some(session))
+    }
+
+    /// Force-expires the session identified by `session_id` (see [`Session::force_expire`]),
+    /// keeping its record until it's tidied away, unlike [`SessionStore::destroy`]. Returns
+    /// [`SessionStoreError::NotFound`] when there's no such session.
+    ///
+    /// The default implementation loads, expires, and stores the whole session.
+    async fn expire(&self, session_id: &SessionID) -> Result<()> {
+        match self.load(session_id.clone()).await? {
+            Some(mut session) => {
+                session.force_expire();
+                self.store(session).await
+            }
+            None => Err(SessionStoreError::NotFound.into()),
+        }
+    }
+
+    /// Returns when the session identified by `session_id` expires, or `None` if there's no such
+    /// session or it has already expired.
+    ///
+    /// The default implementation loads the whole session; stores that can look up the
+    /// expiration on its own should override it.
+    async fn expires_at(&self, session_id: &SessionID) -> Result<Option<DateTime<Utc>>> {
+        let session = self.load(session_id.clone()).await?;
+
+        Ok(session
+            .filter(|session| !session.expired())
+            .map(|session| session.expiration()))
+    }
+
+    /// Stores `session` only if the stored copy's version still equals `expected_version` (`0`
+    /// meaning there is no stored copy yet), bumping the session's version to
+    /// `expected_version + 1`. Returns `false` without writing on a version conflict, so the
+    /// caller can reload, reapply its changes, and retry.
+    async fn store_if_unchanged(
+        &self,
+        _session: Session<Self::SessionData>,
+        _expected_version: u64,
+    ) -> Result<bool> {
+        Err(SessionStoreError::Unsupported("store_if_unchanged".to_string()).into())
+    }
+
+    /// Adds `session_id` to the sessions filed under `key` in the store's secondary index. When
+    /// `max_sessions` is set and `key` would exceed it, the oldest other sessions under `key` are
+    /// destroyed to make room; `session_id` itself is always kept.
+    async fn index(
+        &self,
+        _key: &str,
+        _session_id: &SessionID,
+        _max_sessions: Option<usize>,
+    ) -> Result<()> {
+        Err(SessionStoreError::Unsupported("index".to_string()).into())
+    }
+
+    /// Keeps only the sessions for which `predicate` returns `true`, removing the rest (e.g. all
+    /// sessions of a banned user), and returns the number of sessions removed. Stores that can't
+    /// scan their sessions return [`SessionStoreError::Unsupported`].
+    async fn retain<Predicate>(&self, _predicate: Predicate) -> Result<usize>
+    where
+        Predicate: Fn(&SessionID, &Session<Self::SessionData>) -> bool + Send + Sync,
+    {
+        Err(SessionStoreError::Unsupported("retain".to_string()).into())
+    }
+
+    /// Returns the number of sessions held by the store, including expired ones that haven't
+    /// been tidied yet. Stores that can't count their sessions return
+    /// [`SessionStoreError::Unsupported`].
+    async fn count(&self) -> Result<usize> {
+        Err(SessionStoreError::Unsupported("count".to_string()).into())
+    }
+
+    /// Returns roughly how many bytes the store's sessions take up, for capacity planning, or
+    /// `None` if the store can't tell. How it's estimated is up to the store (e.g. Redis's
+    /// `MEMORY USAGE`, or a SQL table's statistics), so only compare figures from the same store.
+    async fn approx_size_bytes(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Returns the IDs of up to `limit` unexpired sessions, starting where `cursor` left off (or
+    /// from the beginning when it's `None`), along with the cursor for the next page, or `None`
+    /// once every session has been listed. Each session present for the whole listing is
+    /// listed exactly once. Stores that can't enumerate their sessions return
+    /// [`SessionStoreError::Unsupported`].
+    async fn list(
+        &self,
+        _cursor: Option<SessionCursor>,
+        _limit: usize,
+    ) -> Result<(Vec<SessionID>, Option<SessionCursor>)> {
+        Err(SessionStoreError::Unsupported("list".to_string()).into())
+    }
+
+    /// Returns every session held by the store. Stores that can't enumerate their sessions
+    /// return [`SessionStoreError::Unsupported`].
+    async fn export(&self) -> Result<Vec<Session<Self::SessionData>>> {
+        Err(SessionStoreError::Unsupported("export".to_string()).into())
+    }
+}
+
+/// Rejects session data that serialized to more than `max_data_bytes`, for stores enforcing a
+/// size limit in [`SessionStore::store`].
+pub(crate) fn check_data_size(size: usize, max_data_bytes: Option<usize>) -> Result<()> {
+    match max_data_bytes {
+        Some(max_data_bytes) if size > max_data_bytes => {
+            Err(SessionStoreError::StoreFailure(format!(
+                "session data is {} bytes, over the limit of {} bytes",
+                size, max_data_bytes
+            ))
+            .into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Copies every unexpired session from `from` into `to`, returning the number of sessions
+/// migrated.
+pub async fn migrate<Data, From, To>(from: &From, to: &To) -> Result<usize>
+where
+    Data: Clone + Send + Sync + 'static,
+    From: SessionStore<SessionData = Data>,
+    To: SessionStore<SessionData = Data>,
+{
+    let mut migrated = 0;
+    for session in from.export().await? {
+        if session.expired() {
+            continue;
+        }
+
+        to.store(session).await?;
+        migrated += 1;
+    }
+
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::in_memory::InMemory;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// An `InMemory` store counting its round trips, relying on every default method.
+    struct RoundTrips {
+        inner: InMemory<u32>,
+        count: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl SessionStore for RoundTrips {
+        type SessionData = u32;
+
+        fn init() -> Self {
+            Self {
+                inner: InMemory::init(),
+                count: AtomicUsize::new(0),
+            }
+        }
+
+        async fn load(&self, session_id: SessionID) -> Result<Option<Session<u32>>> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            self.inner.load(session_id).await
+        }
+
+        async fn store(&self, session: Session<u32>) -> Result<()> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            self.inner.store(session).await
+        }
+
+        async fn clear(&self) -> Result<()> {
+            self.inner.clear().await
+        }
+
+        async fn destroy(&self, session: Session<u32>) -> Result<()> {
+            self.inner.destroy(session).await
+        }
+
+        async fn tidy(&self, tolerance: i64) -> Result<()> {
+            self.inner.tidy(tolerance).await
+        }
+    }
+
+    #[tokio::test]
+    async fn migrate_copies_unexpired_sessions() {
+        let from = InMemory::<u32>::init();
+        let to = InMemory::<u32>::init();
+
+        let mut ids = Vec::new();
+        for count in 0..3 {
+            let session = Session::new(3600);
+            session.tap(|data| *data = count);
+            ids.push(session.id().clone());
+            from.store(session).await.unwrap();
+        }
+        let expired = Session::new(-1);
+        from.store(expired.clone()).await.unwrap();
+
+        assert_eq!(migrate(&from, &to).await.unwrap(), 3);
+
+        for (count, id) in ids.into_iter().enumerate() {
+            let session = to.load(id).await.unwrap().expect("session was migrated");
+            assert_eq!(session.tap(|data| *data), count as u32);
+        }
+        assert!(to.load(expired.id().clone()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn default_load_and_renew_persists_the_renewal() {
+        // `MirrorStore` relies on the default implementation
+        let store = mirror::MirrorStore::<InMemory<u32>, InMemory<u32>>::init();
+        let session = Session::new(10);
+        session.set_lifespan(7200);
+        store.store(session.clone()).await.unwrap();
+
+        let renewed = store
+            .load_and_renew(session.id(), 3600, 0)
+            .await
+            .unwrap()
+            .unwrap();
+        let in_two_hours = Utc::now() + chrono::Duration::seconds(7100);
+        assert!(!renewed.is_dirty());
+        assert!(renewed.expiration() > in_two_hours);
+        let stored = store.expires_at(session.id()).await.unwrap().unwrap();
+        assert!(stored > in_two_hours);
+    }
+
+    #[tokio::test]
+    async fn default_load_and_renew_takes_two_round_trips() {
+        let store = RoundTrips::init();
+        let session = Session::new(10);
+        store.inner.store(session.clone()).await.unwrap();
+
+        let renewed = store.load_and_renew(session.id(), 3600, 0).await.unwrap();
+        assert!(renewed.is_some());
+        assert_eq!(store.count.load(Ordering::SeqCst), 2);
+    }
 }
@@ -0,0 +1,332 @@
+//! A store that serves reads from a read replica and sends writes to the primary, e.g. a Redis
+//! primary with replicas.
+//!
+//! `load`, `expires_at`, `count`, `list`, and `export` go to the replica; everything else,
+//! including `approx_size_bytes`, goes to the primary. Renewals load from the replica and write
+//! to the primary.
+//!
+//! Replication lag caveat: a session written to the primary may not have reached the replica
+//! yet, so a load right after a write can miss it (starting a new session), see an older copy of
+//! its data, or still find a session that was just destroyed. With
+//! [`ReplicatedStore::with_read_your_writes`], sessions written through this store are loaded
+//! from the primary for a while after each write. That only covers writes made on this node;
+//! writes from other nodes are still subject to lag.
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use crate::{
+    clock, fairing::config::SessionConfig, session::Session, store::SessionStore, SessionCursor,
+    SessionID,
+};
+
+/// The sessions written within the read-your-writes window, oldest first.
+#[derive(Default)]
+struct RecentWrites {
+    order: VecDeque<(DateTime<Utc>, SessionID)>,
+    latest: HashMap<SessionID, DateTime<Utc>>,
+}
+
+impl RecentWrites {
+    fn prune(&mut self, cutoff: DateTime<Utc>) {
+        while let Some((written, _)) = self.order.front() {
+            if *written > cutoff {
+                break;
+            }
+
+            let (written, id) = self.order.pop_front().unwrap();
+            if self.latest.get(&id) == Some(&written) {
+                self.latest.remove(&id);
+            }
+        }
+    }
+}
+
+pub struct ReplicatedStore<Primary, Replica> {
+    primary: Primary,
+    replica: Replica,
+    read_your_writes: Option<Duration>,
+    recent_writes: Mutex<RecentWrites>,
+}
+
+impl<Primary, Replica> ReplicatedStore<Primary, Replica> {
+    pub fn new(primary: Primary, replica: Replica) -> Self {
+        Self {
+            primary,
+            replica,
+            read_your_writes: None,
+            recent_writes: Mutex::new(RecentWrites::default()),
+        }
+    }
+
+    /// Loads a session from the primary for `seconds` after this store last wrote it, which
+    /// should be longer than the replication lag.
+    pub fn with_read_your_writes(mut self, seconds: i64) -> Self {
+        self.read_your_writes = Some(Duration::seconds(seconds));
+        self
+    }
+
+    pub fn primary(&self) -> &Primary {
+        &self.primary
+    }
+
+    pub fn replica(&self) -> &Replica {
+        &self.replica
+    }
+
+    fn wrote(&self, session_id: &SessionID) {
+        if self.read_your_writes.is_some() {
+            let now = clock::now();
+            let mut recent = self.recent_writes.lock().unwrap();
+            recent.order.push_back((now, session_id.clone()));
+            recent.latest.insert(session_id.clone(), now);
+        }
+    }
+
+    fn recently_written(&self, session_id: &SessionID) -> bool {
+        match self.read_your_writes {
+            Some(window) => {
+                let mut recent = self.recent_writes.lock().unwrap();
+                recent.prune(clock::now() - window);
+                recent.latest.contains_key(session_id)
+            }
+            None => false,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<Data, Primary, Replica> SessionStore for ReplicatedStore<Primary, Replica>
+where
+    Data: Clone + Send + Sync + 'static,
+    Primary: SessionStore<SessionData = Data>,
+    Replica: SessionStore<SessionData = Data>,
+{
+    type SessionData = Data;
+
+    fn init() -> Self {
+        Self::new(Primary::init(), Replica::init())
+    }
+
+    fn configure(&mut self, config: &SessionConfig) {
+        self.primary.configure(config);
+        self.replica.configure(config);
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Data>>> {
+        if self.recently_written(&session_id) {
+            self.primary.load(session_id).await
+        } else {
+            self.replica.load(session_id).await
+        }
+    }
+
+    async fn store(&self, session: Session<Data>) -> Result<()> {
+        let id = session.id().clone();
+        self.primary.store(session).await?;
+        self.wrote(&id);
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.primary.clear().await
+    }
+
+    async fn destroy(&self, session: Session<Data>) -> Result<()> {
+        let id = session.id().clone();
+        self.primary.destroy(session).await?;
+        self.wrote(&id);
+        Ok(())
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        self.primary.tidy(tolerance).await
+    }
+
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<()> {
+        self.primary.renew(session_id, lifespan).await
+    }
+
+    async fn expire(&self, session_id: &SessionID) -> Result<()> {
+        self.primary.expire(session_id).await?;
+        self.wrote(session_id);
+        Ok(())
+    }
+
+    async fn expires_at(&self, session_id: &SessionID) -> Result<Option<DateTime<Utc>>> {
+        if self.recently_written(session_id) {
+            self.primary.expires_at(session_id).await
+        } else {
+            self.replica.expires_at(session_id).await
+        }
+    }
+
+    async fn store_if_unchanged(
+        &self,
+        session: Session<Data>,
+        expected_version: u64,
+    ) -> Result<bool> {
+        let id = session.id().clone();
+        let stored = self
+            .primary
+            .store_if_unchanged(session, expected_version)
+            .await?;
+        if stored {
+            self.wrote(&id);
+        }
+
+        Ok(stored)
+    }
+
+    async fn index(
+        &self,
+        key: &str,
+        session_id: &SessionID,
+        max_sessions: Option<usize>,
+    ) -> Result<()> {
+        self.primary.index(key, session_id, max_sessions).await
+    }
+
+    async fn retain<Predicate>(&self, predicate: Predicate) -> Result<usize>
+    where
+        Predicate: Fn(&SessionID, &Session<Data>) -> bool + Send + Sync,
+    {
+        self.primary.retain(predicate).await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.replica.count().await
+    }
+
+    async fn approx_size_bytes(&self) -> Result<Option<u64>> {
+        self.primary.approx_size_bytes().await
+    }
+
+    async fn list(
+        &self,
+        cursor: Option<SessionCursor>,
+        limit: usize,
+    ) -> Result<(Vec<SessionID>, Option<SessionCursor>)> {
+        self.replica.list(cursor, limit).await
+    }
+
+    async fn export(&self) -> Result<Vec<Session<Data>>> {
+        self.replica.export().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{clock::mock, store::in_memory::InMemory};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// An `InMemory` store counting its loads and writes.
+    struct Counted {
+        inner: InMemory<u32>,
+        loads: AtomicUsize,
+        writes: AtomicUsize,
+    }
+
+    impl Counted {
+        fn loads(&self) -> usize {
+            self.loads.load(Ordering::SeqCst)
+        }
+
+        fn writes(&self) -> usize {
+            self.writes.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SessionStore for Counted {
+        type SessionData = u32;
+
+        fn init() -> Self {
+            Self {
+                inner: InMemory::init(),
+                loads: AtomicUsize::new(0),
+                writes: AtomicUsize::new(0),
+            }
+        }
+
+        async fn load(&self, session_id: SessionID) -> Result<Option<Session<u32>>> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            self.inner.load(session_id).await
+        }
+
+        async fn store(&self, session: Session<u32>) -> Result<()> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            self.inner.store(session).await
+        }
+
+        async fn clear(&self) -> Result<()> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            self.inner.clear().await
+        }
+
+        async fn destroy(&self, session: Session<u32>) -> Result<()> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            self.inner.destroy(session).await
+        }
+
+        async fn tidy(&self, tolerance: i64) -> Result<()> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            self.inner.tidy(tolerance).await
+        }
+
+        async fn count(&self) -> Result<usize> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            self.inner.count().await
+        }
+    }
+
+    type Store = ReplicatedStore<Counted, Counted>;
+
+    #[tokio::test]
+    async fn reads_hit_the_replica_and_writes_hit_the_primary() {
+        let store = Store::init();
+        let session = Session::new(3600);
+        store.store(session.clone()).await.unwrap();
+        store.load(session.id().clone()).await.unwrap();
+        store.count().await.unwrap();
+        session.invalidate();
+        store.destroy(session).await.unwrap();
+        store.clear().await.unwrap();
+        store.tidy(0).await.unwrap();
+
+        assert_eq!(store.primary().writes(), 4);
+        assert_eq!(store.primary().loads(), 0);
+        assert_eq!(store.replica().writes(), 0);
+        assert_eq!(store.replica().loads(), 2);
+    }
+
+    #[tokio::test]
+    async fn a_lagging_replica_misses_a_fresh_write() {
+        let store = Store::init();
+        let session = Session::new(3600);
+        store.store(session.clone()).await.unwrap();
+
+        assert!(store.load(session.id().clone()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn recent_writes_are_read_from_the_primary() {
+        let store = Store::init().with_read_your_writes(5);
+        let session = Session::new(3600);
+        let other = Session::<u32>::new(3600);
+        store.store(session.clone()).await.unwrap();
+
+        assert!(store.load(session.id().clone()).await.unwrap().is_some());
+        assert!(store.load(other.id().clone()).await.unwrap().is_none());
+        assert_eq!(store.primary().loads(), 1);
+        assert_eq!(store.replica().loads(), 1);
+
+        mock::advance(6);
+        assert!(store.load(session.id().clone()).await.unwrap().is_none());
+        assert_eq!(store.replica().loads(), 2);
+    }
+}
@@ -0,0 +1,360 @@
+//! A store that retries `load`, `store`, and `destroy` on the inner store when they fail with a
+//! transient error, e.g. a dropped connection to a networked store.
+//!
+//! Retries happen while the request waits, so a store that keeps failing adds up to the sum of
+//! the backoffs (75ms with the defaults) to the request's latency before the error surfaces.
+//! Other operations are passed through without retrying. Backoffs are slept on a Tokio timer, so
+//! the store must be used from within a Tokio runtime, such as Rocket's.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::warn;
+use std::{io, marker::PhantomData, time::Duration};
+
+use crate::{
+    error::SessionStoreError, fairing::config::SessionConfig, session::Session,
+    store::SessionStore, SessionCursor, SessionID,
+};
+
+/// How many times an operation is attempted, unless set with [`RetryStore::with_max_attempts`].
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+/// The wait before the first retry, unless set with [`RetryStore::with_backoff`].
+const DEFAULT_BACKOFF: Duration = Duration::from_millis(25);
+
+/// Decides whether a failed operation is worth retrying.
+pub trait RetryClassifier: Send + Sync + 'static {
+    fn is_transient(error: &anyhow::Error) -> bool;
+}
+
+/// The default classifier: an error is transient if it's a retryable [`SessionStoreError`] (see
+/// [`SessionStoreError::is_retryable`]) or was caused by an I/O error that usually goes away on
+/// its own, such as a reset connection or a timeout.
+#[derive(Clone, Copy, Debug)]
+pub struct Transient;
+
+impl RetryClassifier for Transient {
+    fn is_transient(error: &anyhow::Error) -> bool {
+        if let Some(error) = error.downcast_ref::<SessionStoreError>() {
+            return error.is_retryable();
+        }
+
+        error
+            .chain()
+            .filter_map(|cause| cause.downcast_ref::<io::Error>())
+            .any(|error| {
+                matches!(
+                    error.kind(),
+                    io::ErrorKind::ConnectionRefused
+                        | io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::ConnectionAborted
+                        | io::ErrorKind::NotConnected
+                        | io::ErrorKind::BrokenPipe
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::Interrupted
+                        | io::ErrorKind::WouldBlock
+                )
+            })
+    }
+}
+
+pub struct RetryStore<Inner, Classifier = Transient> {
+    inner: Inner,
+    classifier: PhantomData<fn() -> Classifier>,
+    max_attempts: u32,
+    backoff: Duration,
+}
+
+impl<Inner> RetryStore<Inner> {
+    pub fn new(inner: Inner) -> Self {
+        Self {
+            inner,
+            classifier: PhantomData,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            backoff: DEFAULT_BACKOFF,
+        }
+    }
+}
+
+impl<Inner, Classifier: RetryClassifier> RetryStore<Inner, Classifier> {
+    /// Retries the errors `C` considers transient, instead of [`Transient`]'s.
+    pub fn with_classifier<C: RetryClassifier>(self) -> RetryStore<Inner, C> {
+        RetryStore {
+            inner: self.inner,
+            classifier: PhantomData,
+            max_attempts: self.max_attempts,
+            backoff: self.backoff,
+        }
+    }
+
+    /// Attempts each operation at most `max_attempts` times, including the first; `1` disables
+    /// retrying.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Waits `backoff` before the first retry, doubling the wait before each one after that.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    async fn retry<T, F, Fut>(&self, operation: &str, mut attempt: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let mut backoff = self.backoff;
+        let mut attempts = 1;
+        loop {
+            match attempt().await {
+                Err(error) if attempts < self.max_attempts && Classifier::is_transient(&error) => {
+                    warn!(
+                        "session store `{}` failed (attempt {} of {}), retrying in {:?}: {}",
+                        operation, attempts, self.max_attempts, backoff, error
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempts += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<Inner, Classifier> SessionStore for RetryStore<Inner, Classifier>
+where
+    Inner: SessionStore,
+    Classifier: RetryClassifier,
+{
+    type SessionData = Inner::SessionData;
+
+    fn init() -> Self {
+        RetryStore::new(Inner::init()).with_classifier()
+    }
+
+    fn configure(&mut self, config: &SessionConfig) {
+        self.inner.configure(config);
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Self::SessionData>>> {
+        self.retry("load", || self.inner.load(session_id.clone()))
+            .await
+    }
+
+    async fn store(&self, session: Session<Self::SessionData>) -> Result<()> {
+        self.retry("store", || self.inner.store(session.clone()))
+            .await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.inner.clear().await
+    }
+
+    async fn destroy(&self, session: Session<Self::SessionData>) -> Result<()> {
+        self.retry("destroy", || self.inner.destroy(session.clone()))
+            .await
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        self.inner.tidy(tolerance).await
+    }
+
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<()> {
+        self.inner.renew(session_id, lifespan).await
+    }
+
+    async fn load_and_renew(
+        &self,
+        session_id: &SessionID,
+        lifespan: i64,
+        tolerance: i64,
+    ) -> Result<Option<Session<Self::SessionData>>> {
+        self.inner
+            .load_and_renew(session_id, lifespan, tolerance)
+            .await
+    }
+
+    async fn expire(&self, session_id: &SessionID) -> Result<()> {
+        self.inner.expire(session_id).await
+    }
+
+    async fn expires_at(&self, session_id: &SessionID) -> Result<Option<DateTime<Utc>>> {
+        self.inner.expires_at(session_id).await
+    }
+
+    async fn store_if_unchanged(
+        &self,
+        session: Session<Self::SessionData>,
+        expected_version: u64,
+    ) -> Result<bool> {
+        self.inner
+            .store_if_unchanged(session, expected_version)
+            .await
+    }
+
+    async fn index(
+        &self,
+        key: &str,
+        session_id: &SessionID,
+        max_sessions: Option<usize>,
+    ) -> Result<()> {
+        self.inner.index(key, session_id, max_sessions).await
+    }
+
+    async fn retain<Predicate>(&self, predicate: Predicate) -> Result<usize>
+    where
+        Predicate: Fn(&SessionID, &Session<Self::SessionData>) -> bool + Send + Sync,
+    {
+        self.inner.retain(predicate).await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.inner.count().await
+    }
+
+    async fn approx_size_bytes(&self) -> Result<Option<u64>> {
+        self.inner.approx_size_bytes().await
+    }
+
+    async fn list(
+        &self,
+        cursor: Option<SessionCursor>,
+        limit: usize,
+    ) -> Result<(Vec<SessionID>, Option<SessionCursor>)> {
+        self.inner.list(cursor, limit).await
+    }
+
+    async fn export(&self) -> Result<Vec<Session<Self::SessionData>>> {
+        self.inner.export().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::in_memory::InMemory;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fails the first `failures` loads and stores with `kind`, then defers to an `InMemory`.
+    struct FlakyStore {
+        inner: InMemory<u32>,
+        kind: io::ErrorKind,
+        failures: usize,
+        attempts: AtomicUsize,
+    }
+
+    impl FlakyStore {
+        fn new(kind: io::ErrorKind, failures: usize) -> Self {
+            Self {
+                inner: InMemory::init(),
+                kind,
+                failures,
+                attempts: AtomicUsize::new(0),
+            }
+        }
+
+        fn attempt(&self) -> Result<()> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err(io::Error::new(self.kind, "flaky").into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SessionStore for FlakyStore {
+        type SessionData = u32;
+
+        fn init() -> Self {
+            Self::new(io::ErrorKind::ConnectionReset, 1)
+        }
+
+        async fn load(&self, session_id: SessionID) -> Result<Option<Session<u32>>> {
+            self.attempt()?;
+            self.inner.load(session_id).await
+        }
+
+        async fn store(&self, session: Session<u32>) -> Result<()> {
+            self.attempt()?;
+            self.inner.store(session).await
+        }
+
+        async fn clear(&self) -> Result<()> {
+            self.inner.clear().await
+        }
+
+        async fn destroy(&self, session: Session<u32>) -> Result<()> {
+            self.inner.destroy(session).await
+        }
+
+        async fn tidy(&self, tolerance: i64) -> Result<()> {
+            self.inner.tidy(tolerance).await
+        }
+    }
+
+    fn retrying(store: FlakyStore) -> RetryStore<FlakyStore> {
+        RetryStore::new(store).with_backoff(Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn a_transient_failure_is_retried() {
+        let store = retrying(FlakyStore::new(io::ErrorKind::ConnectionReset, 1));
+        let session = Session::new(3600);
+        session.tap(|count| *count = 7);
+
+        store.store(session.clone()).await.unwrap();
+        assert_eq!(store.inner().attempts.load(Ordering::SeqCst), 2);
+
+        let loaded = store.load(session.id().clone()).await.unwrap().unwrap();
+        assert_eq!(loaded.read(|count| *count), 7);
+    }
+
+    #[tokio::test]
+    async fn a_fatal_failure_is_not_retried() {
+        let store = retrying(FlakyStore::new(io::ErrorKind::PermissionDenied, 1));
+
+        assert!(store.store(Session::new(3600)).await.is_err());
+        assert_eq!(store.inner().attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_after_max_attempts() {
+        let store = retrying(FlakyStore::new(io::ErrorKind::TimedOut, 5)).with_max_attempts(3);
+
+        assert!(store.load("id".into()).await.is_err());
+        assert_eq!(store.inner().attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn retryable_store_errors_are_transient() {
+        let timeout = SessionStoreError::Timeout("no reply".to_string()).into();
+        let serialization = SessionStoreError::Serialization("bad JSON".to_string()).into();
+
+        assert!(Transient::is_transient(&timeout));
+        assert!(!Transient::is_transient(&serialization));
+    }
+
+    #[tokio::test]
+    async fn a_custom_classifier_decides_what_is_transient() {
+        struct Everything;
+
+        impl RetryClassifier for Everything {
+            fn is_transient(_: &anyhow::Error) -> bool {
+                true
+            }
+        }
+
+        let store = retrying(FlakyStore::new(io::ErrorKind::PermissionDenied, 1))
+            .with_classifier::<Everything>();
+
+        assert!(store.store(Session::new(3600)).await.is_ok());
+        assert_eq!(store.inner().attempts.load(Ordering::SeqCst), 2);
+    }
+}
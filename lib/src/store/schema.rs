@@ -0,0 +1,332 @@
+//! Versioning for the session data kept by persistent stores, so sessions written by an older
+//! deploy survive a change to the shape of `Data`.
+//!
+//! Stores write sessions with [`encode`], tagging them with the [`Migrator::current_version`],
+//! and read them back with [`decode`]. When a session written under another version is loaded,
+//! its data is handed to [`Migrator::migrate`] to be upgraded; if that fails the session is
+//! treated as missing, so the client starts a fresh one instead of the request failing. Sessions
+//! written before a migrator was configured have version `0`.
+//!
+//! Sessions can be written in any [`StorageFormat`], chosen at runtime with the session config's
+//! `storage_format`. JSON records are plain JSON; records in other formats start with a marker
+//! byte, so [`decode`] reads every format regardless of the one currently configured, and
+//! switching formats leaves existing sessions readable. Each format's bytes come from the
+//! matching [`PayloadCodec`].
+use anyhow::Result;
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+#[cfg(feature = "bincode")]
+use crate::payload::BincodePayload;
+#[cfg(feature = "messagepack")]
+use crate::payload::MessagePackPayload;
+use crate::{
+    payload::{JsonPayload, PayloadCodec},
+    session::Session,
+};
+
+const VERSION_FIELD: &str = "schema_version";
+
+const BINCODE_MARKER: u8 = 1;
+const MESSAGEPACK_MARKER: u8 = 2;
+
+/// The encoding of sessions written by persistent stores. Formats other than JSON need their
+/// crate feature (`bincode` or `messagepack`) enabled.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageFormat {
+    /// Human-readable, for debugging. Written without a marker byte, so deploys predating
+    /// storage formats can read it.
+    Json,
+    /// Compact and fast, but not self-describing: sessions written under another schema version
+    /// can't be handed to a [`Migrator`], and are treated as missing instead.
+    Bincode,
+    /// Compact and self-describing.
+    MessagePack,
+}
+
+impl Default for StorageFormat {
+    fn default() -> Self {
+        Self::Json
+    }
+}
+
+/// Upgrades session data stored under an older schema version to the current `Data`.
+pub trait Migrator<Data>: Send + Sync + 'static {
+    /// The version stored alongside newly written sessions.
+    fn current_version(&self) -> u32;
+
+    /// Converts `bytes`, the JSON of session data stored under `version`, into the current
+    /// `Data`.
+    fn migrate(&self, version: u32, bytes: &[u8]) -> Result<Data>;
+}
+
+/// Serializes `session` as JSON, tagged with `version`.
+pub fn encode<Data>(session: &Session<Data>, version: u32) -> Result<Vec<u8>>
+where
+    Data: Clone + Send + Sync + Serialize + 'static,
+{
+    encode_as(session, version, StorageFormat::Json)
+}
+
+/// Serializes `session` in `format`, tagged with `version`.
+pub fn encode_as<Data>(
+    session: &Session<Data>,
+    version: u32,
+    format: StorageFormat,
+) -> Result<Vec<u8>>
+where
+    Data: Clone + Send + Sync + Serialize + 'static,
+{
+    if format == StorageFormat::Bincode {
+        return encode_bincode(session, version);
+    }
+
+    let mut record = serde_json::to_value(session)?;
+    if let Value::Object(fields) = &mut record {
+        fields.insert(VERSION_FIELD.to_string(), version.into());
+    }
+
+    match format {
+        StorageFormat::MessagePack => encode_messagepack(&record),
+        _ => JsonPayload.encode(&record),
+    }
+}
+
+#[cfg(feature = "bincode")]
+fn encode_bincode<Data>(session: &Session<Data>, version: u32) -> Result<Vec<u8>>
+where
+    Data: Clone + Send + Sync + Serialize + 'static,
+{
+    let mut bytes = vec![BINCODE_MARKER];
+    bytes.extend(BincodePayload.encode(&(version, session))?);
+
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "bincode"))]
+fn encode_bincode<Data>(_session: &Session<Data>, _version: u32) -> Result<Vec<u8>>
+where
+    Data: Clone + Send + Sync + Serialize + 'static,
+{
+    anyhow::bail!("the bincode storage format requires the `bincode` feature")
+}
+
+#[cfg(feature = "messagepack")]
+fn encode_messagepack(record: &Value) -> Result<Vec<u8>> {
+    let mut bytes = vec![MESSAGEPACK_MARKER];
+    bytes.extend(MessagePackPayload.encode(record)?);
+
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "messagepack"))]
+fn encode_messagepack(_record: &Value) -> Result<Vec<u8>> {
+    anyhow::bail!("the messagepack storage format requires the `messagepack` feature")
+}
+
+/// Deserializes a session written by [`encode`] or [`encode_as`] in any format, migrating its
+/// data if it was written under another version than the migrator's current one. Returns `None`
+/// if the data can't be migrated.
+pub fn decode<Data>(
+    bytes: &[u8],
+    migrator: Option<&dyn Migrator<Data>>,
+) -> Result<Option<Session<Data>>>
+where
+    Data: Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    let mut record: Value = match bytes.first() {
+        Some(&BINCODE_MARKER) => return decode_bincode(&bytes[1..], migrator),
+        Some(&MESSAGEPACK_MARKER) => decode_messagepack(&bytes[1..])?,
+        _ => JsonPayload.decode(bytes)?,
+    };
+    let version = record
+        .get(VERSION_FIELD)
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as u32;
+
+    if let Some(migrator) = migrator.filter(|migrator| migrator.current_version() != version) {
+        let data = serde_json::to_vec(&record["data"])?;
+        match migrator.migrate(version, &data) {
+            Ok(data) => record["data"] = serde_json::to_value(data)?,
+            Err(error) => {
+                warn!(
+                    "failed to migrate session data from version {}: {}",
+                    version, error
+                );
+                return Ok(None);
+            }
+        }
+    }
+
+    Ok(serde_json::from_value(record).map(Some)?)
+}
+
+#[cfg(feature = "bincode")]
+fn decode_bincode<Data>(
+    bytes: &[u8],
+    migrator: Option<&dyn Migrator<Data>>,
+) -> Result<Option<Session<Data>>>
+where
+    Data: Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    // the version leads the record, so it can be read without knowing the shape of `Data`
+    let version: u32 = BincodePayload.decode(bytes)?;
+    if let Some(migrator) = migrator.filter(|migrator| migrator.current_version() != version) {
+        warn!(
+            "can't migrate bincode session data from version {} to {}",
+            version,
+            migrator.current_version()
+        );
+        return Ok(None);
+    }
+
+    let (_, session): (u32, Session<Data>) = BincodePayload.decode(bytes)?;
+    Ok(Some(session))
+}
+
+#[cfg(not(feature = "bincode"))]
+fn decode_bincode<Data>(
+    _bytes: &[u8],
+    _migrator: Option<&dyn Migrator<Data>>,
+) -> Result<Option<Session<Data>>>
+where
+    Data: Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    anyhow::bail!("a session was stored as bincode, but the `bincode` feature is disabled")
+}
+
+#[cfg(feature = "messagepack")]
+fn decode_messagepack(bytes: &[u8]) -> Result<Value> {
+    MessagePackPayload.decode(bytes)
+}
+
+#[cfg(not(feature = "messagepack"))]
+fn decode_messagepack(_bytes: &[u8]) -> Result<Value> {
+    anyhow::bail!("a session was stored as messagepack, but the `messagepack` feature is disabled")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Clone, Default, Serialize, Deserialize)]
+    struct UserV1 {
+        name: String,
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct UserV2 {
+        first_name: String,
+        last_name: String,
+    }
+
+    struct SplitName;
+
+    impl Migrator<UserV2> for SplitName {
+        fn current_version(&self) -> u32 {
+            2
+        }
+
+        fn migrate(&self, version: u32, bytes: &[u8]) -> Result<UserV2> {
+            anyhow::ensure!(version == 1, "unknown version {}", version);
+            let user: UserV1 = serde_json::from_slice(bytes)?;
+            let mut names = user.name.splitn(2, ' ');
+
+            Ok(UserV2 {
+                first_name: names.next().unwrap_or_default().to_string(),
+                last_name: names.next().unwrap_or_default().to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn old_payloads_are_migrated_on_load() {
+        let session = Session::<UserV1>::new(3600);
+        session.tap(|user| user.name = "Ada Lovelace".to_string());
+        let bytes = encode(&session, 1).unwrap();
+
+        let migrated = decode(&bytes, Some(&SplitName)).unwrap().unwrap();
+
+        assert_eq!(migrated.id(), session.id());
+        assert_eq!(migrated.expiration(), session.expiration());
+        assert_eq!(
+            migrated.read(UserV2::clone),
+            UserV2 {
+                first_name: "Ada".to_string(),
+                last_name: "Lovelace".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn current_payloads_are_loaded_as_is() {
+        let session = Session::<UserV2>::new(3600);
+        session.tap(|user| user.first_name = "Grace".to_string());
+        let bytes = encode(&session, 2).unwrap();
+
+        let loaded = decode(&bytes, Some(&SplitName)).unwrap().unwrap();
+        assert_eq!(loaded.read(|user| user.first_name.clone()), "Grace");
+    }
+
+    #[test]
+    fn unmarked_json_records_still_load_after_a_format_switch() {
+        let session = Session::<UserV2>::new(3600);
+        session.tap(|user| user.first_name = "Grace".to_string());
+        // as written before storage formats existed
+        let bytes = encode(&session, 2).unwrap();
+        assert_eq!(bytes[0], b'{');
+
+        let loaded = decode(&bytes, Some(&SplitName)).unwrap().unwrap();
+        assert_eq!(loaded.read(|user| user.first_name.clone()), "Grace");
+    }
+
+    #[test]
+    #[cfg(feature = "bincode")]
+    fn bincode_records_are_marked_and_round_trip() {
+        let session = Session::<UserV2>::new(3600);
+        session.tap(|user| user.last_name = "Hopper".to_string());
+        let bytes = encode_as(&session, 2, StorageFormat::Bincode).unwrap();
+        assert_eq!(bytes[0], BINCODE_MARKER);
+
+        let loaded = decode(&bytes, Some(&SplitName)).unwrap().unwrap();
+        assert_eq!(loaded.id(), session.id());
+        assert_eq!(loaded.expiration(), session.expiration());
+        assert_eq!(loaded.read(|user| user.last_name.clone()), "Hopper");
+
+        // bincode data can't be handed to the migrator
+        let bytes = encode_as(&session, 1, StorageFormat::Bincode).unwrap();
+        assert!(decode(&bytes, Some(&SplitName)).unwrap().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "messagepack")]
+    fn messagepack_records_are_marked_and_migrated() {
+        let session = Session::<UserV1>::new(3600);
+        session.tap(|user| user.name = "Ada Lovelace".to_string());
+        let bytes = encode_as(&session, 1, StorageFormat::MessagePack).unwrap();
+        assert_eq!(bytes[0], MESSAGEPACK_MARKER);
+
+        let migrated = decode(&bytes, Some(&SplitName)).unwrap().unwrap();
+        assert_eq!(migrated.read(|user| user.last_name.clone()), "Lovelace");
+    }
+
+    #[test]
+    #[cfg(not(feature = "bincode"))]
+    fn formats_without_their_feature_are_rejected() {
+        let session = Session::<UserV2>::new(3600);
+
+        assert!(encode_as(&session, 2, StorageFormat::Bincode).is_err());
+        assert!(decode::<UserV2>(&[BINCODE_MARKER, 0, 0, 0, 0], None).is_err());
+    }
+
+    #[test]
+    fn unmigratable_payloads_are_treated_as_missing() {
+        let session = Session::<UserV1>::new(3600);
+        let bytes = encode(&session, 0).unwrap();
+
+        assert!(decode(&bytes, Some(&SplitName)).unwrap().is_none());
+    }
+}
@@ -0,0 +1,268 @@
+//! A store backed by a [`tower_service::Service`], for plugging in a custom session backend
+//! (e.g. a client for a session microservice) without implementing [`SessionStore`] yourself.
+//! Requires the `service` feature.
+//!
+//! The service must answer each [`SessionRequest`] as follows:
+//!
+//! | Request               | Response                                                      |
+//! |-----------------------|---------------------------------------------------------------|
+//! | `Load(id)`            | `Session(Some(session))`, or `Session(None)` if there's none |
+//! | `Store(session)`      | `Done`, once the session is persisted                         |
+//! | `Destroy(id)`         | `Done`, once the session is removed                           |
+//! | `Clear`               | `Done`, once every session is removed                         |
+//! | `Tidy { tolerance }`  | `Done`; see [`SessionStore::tidy`]                            |
+//!
+//! Any other response fails the operation. Renewals and expirations go through `Load` and
+//! `Store`, and the store's optional operations (e.g. `index`) are unsupported.
+use anyhow::Result;
+use futures::future::poll_fn;
+use std::marker::PhantomData;
+use tower_service::Service;
+
+use crate::{error::SessionStoreError, session::Session, store::SessionStore, SessionID};
+
+/// The error type services may fail with, as in `tower`.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug)]
+pub enum SessionRequest<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    Load(SessionID),
+    Store(Box<Session<Data>>),
+    Destroy(SessionID),
+    Clear,
+    Tidy { tolerance: i64 },
+}
+
+#[derive(Debug)]
+pub enum SessionResponse<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    /// The answer to `Load`.
+    Session(Option<Box<Session<Data>>>),
+    /// The answer to every other request.
+    Done,
+}
+
+/// Adapts a service speaking [`SessionRequest`]s into a [`SessionStore`]. The service is cloned
+/// for each request, so services that can't be cloned cheaply should be wrapped in e.g.
+/// `tower::buffer::Buffer`.
+pub struct ServiceStore<S, Data> {
+    service: S,
+    data: PhantomData<fn() -> Data>,
+}
+
+impl<S, Data> ServiceStore<S, Data> {
+    pub fn new(service: S) -> Self {
+        Self {
+            service,
+            data: PhantomData,
+        }
+    }
+
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+}
+
+impl<S, Data> ServiceStore<S, Data>
+where
+    S: Service<SessionRequest<Data>, Response = SessionResponse<Data>> + Clone + Send + Sync,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+    Data: Clone + Send + Sync + 'static,
+{
+    async fn call(&self, request: SessionRequest<Data>) -> Result<SessionResponse<Data>> {
+        let mut service = self.service.clone();
+        poll_fn(|cx| service.poll_ready(cx))
+            .await
+            .map_err(service_error)?;
+
+        service.call(request).await.map_err(service_error)
+    }
+
+    async fn call_done(&self, request: SessionRequest<Data>, operation: &str) -> Result<()> {
+        match self.call(request).await? {
+            SessionResponse::Done => Ok(()),
+            SessionResponse::Session(_) => Err(unexpected_response(operation)),
+        }
+    }
+}
+
+fn service_error(error: impl Into<BoxError>) -> anyhow::Error {
+    let error: BoxError = error.into();
+    anyhow::anyhow!(error)
+}
+
+fn unexpected_response(operation: &str) -> anyhow::Error {
+    SessionStoreError::StoreFailure(format!(
+        "the session service answered `{}` with an unexpected response",
+        operation
+    ))
+    .into()
+}
+
+#[async_trait::async_trait]
+impl<S, Data> SessionStore for ServiceStore<S, Data>
+where
+    S: Service<SessionRequest<Data>, Response = SessionResponse<Data>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+    Data: Clone + Send + Sync + 'static,
+{
+    type SessionData = Data;
+
+    /// There's no service to adapt, so this panics; pass a `ServiceStore` to
+    /// `SessionFairing::with_store` instead.
+    fn init() -> Self {
+        panic!("a `ServiceStore` needs a service; attach the fairing `with_store` instead")
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Data>>> {
+        match self.call(SessionRequest::Load(session_id)).await? {
+            SessionResponse::Session(session) => Ok(session.map(|session| *session)),
+            SessionResponse::Done => Err(SessionStoreError::LoadFailure(
+                "the session service answered `load` without a session".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    async fn store(&self, session: Session<Data>) -> Result<()> {
+        self.call_done(SessionRequest::Store(Box::new(session)), "store")
+            .await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.call_done(SessionRequest::Clear, "clear").await
+    }
+
+    async fn destroy(&self, session: Session<Data>) -> Result<()> {
+        if session.should_destroy() {
+            let request = SessionRequest::Destroy(session.id().clone());
+            self.call_done(request, "destroy").await?;
+        }
+
+        Ok(())
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        self.call_done(SessionRequest::Tidy { tolerance }, "tidy")
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::in_memory::InMemory;
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    };
+
+    /// Speaks the protocol in-process, keeping sessions in an `InMemory` store.
+    #[derive(Clone)]
+    struct MockService {
+        sessions: Arc<InMemory<u32>>,
+    }
+
+    impl Service<SessionRequest<u32>> for MockService {
+        type Response = SessionResponse<u32>;
+        type Error = BoxError;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: SessionRequest<u32>) -> Self::Future {
+            let sessions = Arc::clone(&self.sessions);
+            Box::pin(async move {
+                let response = match request {
+                    SessionRequest::Load(id) => {
+                        SessionResponse::Session(sessions.load(id).await?.map(Box::new))
+                    }
+                    SessionRequest::Store(session) => {
+                        sessions.store(*session).await?;
+                        SessionResponse::Done
+                    }
+                    SessionRequest::Destroy(id) => {
+                        if let Some(session) = sessions.load(id).await? {
+                            session.invalidate();
+                            sessions.destroy(session).await?;
+                        }
+                        SessionResponse::Done
+                    }
+                    SessionRequest::Clear => {
+                        sessions.clear().await?;
+                        SessionResponse::Done
+                    }
+                    SessionRequest::Tidy { tolerance } => {
+                        sessions.tidy(tolerance).await?;
+                        SessionResponse::Done
+                    }
+                };
+
+                Ok(response)
+            })
+        }
+    }
+
+    fn store() -> ServiceStore<MockService, u32> {
+        ServiceStore::new(MockService {
+            sessions: Arc::new(InMemory::init()),
+        })
+    }
+
+    #[tokio::test]
+    async fn it_drives_the_service_through_the_store_trait() {
+        let store = store();
+        let session = Session::new(3600);
+        session.tap(|count| *count = 7);
+        store.store(session.clone()).await.unwrap();
+
+        let loaded = store.load(session.id().clone()).await.unwrap().unwrap();
+        assert_eq!(loaded.read(|count| *count), 7);
+
+        store.renew(session.id(), 7200).await.unwrap();
+        assert!(store.expires_at(session.id()).await.unwrap().is_some());
+
+        session.invalidate();
+        store.destroy(session.clone()).await.unwrap();
+        assert!(store.load(session.id().clone()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn it_rejects_unexpected_responses() {
+        #[derive(Clone)]
+        struct AlwaysDone;
+
+        impl Service<SessionRequest<u32>> for AlwaysDone {
+            type Response = SessionResponse<u32>;
+            type Error = BoxError;
+            type Future = std::future::Ready<Result<Self::Response, BoxError>>;
+
+            fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, _request: SessionRequest<u32>) -> Self::Future {
+                std::future::ready(Ok(SessionResponse::Done))
+            }
+        }
+
+        let store = ServiceStore::new(AlwaysDone);
+        assert!(store.load("id".into()).await.is_err());
+        assert!(store.store(Session::new(3600)).await.is_ok());
+    }
+}
@@ -0,0 +1,308 @@
+//! A store that spreads sessions over several inner stores by their ID, e.g. a Redis cluster
+//! sharded by session, to spread the load and connections.
+//!
+//! Operations on one session go to the shard [`shard_for`] picks for its ID; `clear`, `tidy`,
+//! `retain`, and `export` go to every shard, and `count` and `approx_size_bytes` add theirs up.
+//! `index` is unsupported, as a key's sessions are spread over shards that each only know their
+//! own.
+//!
+//! Rebalancing caveat: placement depends on the number of shards, so adding or removing one
+//! moves most sessions to a shard that doesn't have them, which logs their users out. Copy the
+//! sessions over with [`migrate`](super::migrate) from the old layout to the new one before
+//! switching.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::SessionStoreError, fairing::config::SessionConfig, session::Session,
+    store::SessionStore, SessionCursor, SessionID,
+};
+
+/// The number of shards of a store created with `SessionStore::init`.
+pub const DEFAULT_SHARDS: usize = 4;
+
+/// The shard out of `shards` that holds the session `session_id`. Hashes the ID with SHA-256,
+/// so every node and every process places a session on the same shard.
+pub fn shard_for(session_id: &SessionID, shards: usize) -> usize {
+    assert!(shards > 0, "there must be at least one shard");
+    let digest = Sha256::digest(session_id.0.as_bytes());
+
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(bytes) % shards as u64) as usize
+}
+
+pub struct ShardedStore<Inner> {
+    shards: Vec<Inner>,
+}
+
+impl<Inner> ShardedStore<Inner> {
+    /// Panics if `shards` is empty.
+    pub fn new(shards: Vec<Inner>) -> Self {
+        assert!(!shards.is_empty(), "there must be at least one shard");
+        Self { shards }
+    }
+
+    pub fn shards(&self) -> &[Inner] {
+        &self.shards
+    }
+
+    /// The shard holding the session `session_id`.
+    pub fn shard(&self, session_id: &SessionID) -> &Inner {
+        &self.shards[shard_for(session_id, self.shards.len())]
+    }
+}
+
+/// Splits a cursor into the shard it's on and the shard's own cursor.
+fn parse_cursor(cursor: &SessionCursor) -> Result<(usize, Option<SessionCursor>)> {
+    let mut parts = cursor.0.splitn(2, ':');
+    let shard = parts
+        .next()
+        .and_then(|shard| shard.parse().ok())
+        .ok_or_else(|| SessionStoreError::LoadFailure(format!("invalid cursor `{}`", cursor.0)))?;
+
+    Ok((
+        shard,
+        parts.next().map(|inner| SessionCursor(inner.to_string())),
+    ))
+}
+
+#[async_trait::async_trait]
+impl<Data, Inner> SessionStore for ShardedStore<Inner>
+where
+    Data: Clone + Send + Sync + 'static,
+    Inner: SessionStore<SessionData = Data>,
+{
+    type SessionData = Data;
+
+    fn init() -> Self {
+        Self::new((0..DEFAULT_SHARDS).map(|_| Inner::init()).collect())
+    }
+
+    fn configure(&mut self, config: &SessionConfig) {
+        for shard in &mut self.shards {
+            shard.configure(config);
+        }
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Data>>> {
+        self.shard(&session_id).load(session_id).await
+    }
+
+    async fn store(&self, session: Session<Data>) -> Result<()> {
+        self.shard(session.id()).store(session).await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        for shard in &self.shards {
+            shard.clear().await?;
+        }
+
+        Ok(())
+    }
+
+    async fn destroy(&self, session: Session<Data>) -> Result<()> {
+        self.shard(session.id()).destroy(session).await
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        for shard in &self.shards {
+            shard.tidy(tolerance).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<()> {
+        self.shard(session_id).renew(session_id, lifespan).await
+    }
+
+    async fn load_and_renew(
+        &self,
+        session_id: &SessionID,
+        lifespan: i64,
+        tolerance: i64,
+    ) -> Result<Option<Session<Data>>> {
+        self.shard(session_id)
+            .load_and_renew(session_id, lifespan, tolerance)
+            .await
+    }
+
+    async fn expire(&self, session_id: &SessionID) -> Result<()> {
+        self.shard(session_id).expire(session_id).await
+    }
+
+    async fn expires_at(&self, session_id: &SessionID) -> Result<Option<DateTime<Utc>>> {
+        self.shard(session_id).expires_at(session_id).await
+    }
+
+    async fn store_if_unchanged(
+        &self,
+        session: Session<Data>,
+        expected_version: u64,
+    ) -> Result<bool> {
+        self.shard(session.id())
+            .store_if_unchanged(session, expected_version)
+            .await
+    }
+
+    async fn retain<Predicate>(&self, predicate: Predicate) -> Result<usize>
+    where
+        Predicate: Fn(&SessionID, &Session<Data>) -> bool + Send + Sync,
+    {
+        let mut removed = 0;
+        for shard in &self.shards {
+            removed += shard.retain(&predicate).await?;
+        }
+
+        Ok(removed)
+    }
+
+    async fn count(&self) -> Result<usize> {
+        let mut count = 0;
+        for shard in &self.shards {
+            count += shard.count().await?;
+        }
+
+        Ok(count)
+    }
+
+    async fn approx_size_bytes(&self) -> Result<Option<u64>> {
+        let mut size = 0;
+        for shard in &self.shards {
+            match shard.approx_size_bytes().await? {
+                Some(bytes) => size += bytes,
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(size))
+    }
+
+    /// Lists one shard after another; the cursor is the shard's index, followed by `:` and the
+    /// shard's own cursor when it's partway through one.
+    async fn list(
+        &self,
+        cursor: Option<SessionCursor>,
+        limit: usize,
+    ) -> Result<(Vec<SessionID>, Option<SessionCursor>)> {
+        if limit == 0 {
+            return Ok((Vec::new(), cursor));
+        }
+
+        let (mut shard, mut inner) = match &cursor {
+            Some(cursor) => parse_cursor(cursor)?,
+            None => (0, None),
+        };
+        while let Some(store) = self.shards.get(shard) {
+            let (ids, next) = store.list(inner, limit).await?;
+            let next = match next {
+                Some(next) => Some((shard, Some(next))),
+                None if shard + 1 < self.shards.len() => Some((shard + 1, None)),
+                None => None,
+            };
+
+            match next {
+                // skips over empty shards rather than returning empty pages
+                Some(next) if ids.is_empty() => {
+                    shard = next.0;
+                    inner = next.1;
+                }
+                next => {
+                    let next = next.map(|(shard, inner)| match inner {
+                        Some(inner) => SessionCursor(format!("{}:{}", shard, inner.0)),
+                        None => SessionCursor(shard.to_string()),
+                    });
+
+                    return Ok((ids, next));
+                }
+            }
+        }
+
+        Ok((Vec::new(), None))
+    }
+
+    async fn export(&self) -> Result<Vec<Session<Data>>> {
+        let mut sessions = Vec::new();
+        for shard in &self.shards {
+            sessions.extend(shard.export().await?);
+        }
+
+        Ok(sessions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::in_memory::InMemory;
+
+    type Store = ShardedStore<InMemory<u32>>;
+
+    #[test]
+    fn an_id_always_maps_to_the_same_shard() {
+        let id = SessionID("a-session-id".to_string());
+        let shard = shard_for(&id, 8);
+        for _ in 0..10 {
+            assert_eq!(shard_for(&id, 8), shard);
+        }
+        assert_eq!(shard_for(&id, 1), 0);
+    }
+
+    #[tokio::test]
+    async fn sessions_are_stored_on_their_shard_and_counted_across_all() {
+        let store = Store::init();
+        let mut ids = Vec::new();
+        for _ in 0..20 {
+            let session = Session::new(3600);
+            ids.push(session.id().clone());
+            store.store(session).await.unwrap();
+        }
+
+        assert_eq!(store.count().await.unwrap(), 20);
+        for id in &ids {
+            let shard = shard_for(id, DEFAULT_SHARDS);
+            assert!(store.shards()[shard]
+                .load(id.clone())
+                .await
+                .unwrap()
+                .is_some());
+            assert!(store.load(id.clone()).await.unwrap().is_some());
+        }
+        let mut used = 0;
+        for shard in store.shards() {
+            if shard.count().await.unwrap() > 0 {
+                used += 1;
+            }
+        }
+        assert!(used > 1, "20 sessions all landed on one shard");
+    }
+
+    #[tokio::test]
+    async fn list_pages_through_every_shard() {
+        let store = Store::init();
+        let mut ids = Vec::new();
+        for _ in 0..25 {
+            let session = Session::new(3600);
+            ids.push(session.id().clone());
+            store.store(session).await.unwrap();
+        }
+
+        let mut listed = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (page, next) = store.list(cursor, 4).await.unwrap();
+            assert!(page.len() <= 4);
+            listed.extend(page);
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        listed.sort();
+        ids.sort();
+        assert_eq!(listed, ids);
+    }
+}
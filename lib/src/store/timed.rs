@@ -0,0 +1,266 @@
+//! A store that times every operation of the store it wraps and reports the slow ones, to spot a
+//! degrading backend without a metrics stack.
+//!
+//! Reports hold the operation's name and how long it took, never the session's ID or data.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::warn;
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    fairing::config::SessionConfig, session::Session, store::SessionStore, SessionCursor, SessionID,
+};
+
+/// The threshold of a store created with `SessionStore::init`.
+pub const DEFAULT_SLOW_OP_THRESHOLD: Duration = Duration::from_millis(100);
+
+/// A store operation that took longer than the threshold.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlowOperation {
+    /// The [`SessionStore`] method, e.g. `"load_and_renew"`.
+    pub operation: &'static str,
+    pub elapsed: Duration,
+    pub threshold: Duration,
+}
+
+type Sink = Box<dyn Fn(&SlowOperation) + Send + Sync>;
+
+pub struct TimedStore<Inner> {
+    inner: Inner,
+    slow_op_threshold: Duration,
+    sink: Sink,
+}
+
+impl<Inner> TimedStore<Inner> {
+    /// Logs a warning for every operation taking longer than `slow_op_threshold`.
+    pub fn new(inner: Inner, slow_op_threshold: Duration) -> Self {
+        Self {
+            inner,
+            slow_op_threshold,
+            sink: Box::new(|slow| {
+                warn!(
+                    "session store operation `{}` took {:?}, over the {:?} threshold",
+                    slow.operation, slow.elapsed, slow.threshold
+                )
+            }),
+        }
+    }
+
+    /// Passes slow operations to `sink` instead of logging them, e.g. to count them.
+    pub fn with_sink(mut self, sink: impl Fn(&SlowOperation) + Send + Sync + 'static) -> Self {
+        self.sink = Box::new(sink);
+        self
+    }
+
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    async fn timed<T>(&self, operation: &'static str, future: impl Future<Output = T>) -> T {
+        let start = Instant::now();
+        let output = future.await;
+
+        let elapsed = start.elapsed();
+        if elapsed > self.slow_op_threshold {
+            (self.sink)(&SlowOperation {
+                operation,
+                elapsed,
+                threshold: self.slow_op_threshold,
+            });
+        }
+        output
+    }
+}
+
+#[async_trait::async_trait]
+impl<Inner: SessionStore> SessionStore for TimedStore<Inner> {
+    type SessionData = Inner::SessionData;
+
+    fn init() -> Self {
+        Self::new(Inner::init(), DEFAULT_SLOW_OP_THRESHOLD)
+    }
+
+    fn configure(&mut self, config: &SessionConfig) {
+        self.inner.configure(config);
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Self::SessionData>>> {
+        self.timed("load", self.inner.load(session_id)).await
+    }
+
+    async fn store(&self, session: Session<Self::SessionData>) -> Result<()> {
+        self.timed("store", self.inner.store(session)).await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.timed("clear", self.inner.clear()).await
+    }
+
+    async fn destroy(&self, session: Session<Self::SessionData>) -> Result<()> {
+        self.timed("destroy", self.inner.destroy(session)).await
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<()> {
+        self.timed("tidy", self.inner.tidy(tolerance)).await
+    }
+
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<()> {
+        self.timed("renew", self.inner.renew(session_id, lifespan))
+            .await
+    }
+
+    async fn load_and_renew(
+        &self,
+        session_id: &SessionID,
+        lifespan: i64,
+        tolerance: i64,
+    ) -> Result<Option<Session<Self::SessionData>>> {
+        self.timed(
+            "load_and_renew",
+            self.inner.load_and_renew(session_id, lifespan, tolerance),
+        )
+        .await
+    }
+
+    async fn expire(&self, session_id: &SessionID) -> Result<()> {
+        self.timed("expire", self.inner.expire(session_id)).await
+    }
+
+    async fn expires_at(&self, session_id: &SessionID) -> Result<Option<DateTime<Utc>>> {
+        self.timed("expires_at", self.inner.expires_at(session_id))
+            .await
+    }
+
+    async fn store_if_unchanged(
+        &self,
+        session: Session<Self::SessionData>,
+        expected_version: u64,
+    ) -> Result<bool> {
+        self.timed(
+            "store_if_unchanged",
+            self.inner.store_if_unchanged(session, expected_version),
+        )
+        .await
+    }
+
+    async fn index(
+        &self,
+        key: &str,
+        session_id: &SessionID,
+        max_sessions: Option<usize>,
+    ) -> Result<()> {
+        self.timed("index", self.inner.index(key, session_id, max_sessions))
+            .await
+    }
+
+    async fn retain<Predicate>(&self, predicate: Predicate) -> Result<usize>
+    where
+        Predicate: Fn(&SessionID, &Session<Self::SessionData>) -> bool + Send + Sync,
+    {
+        self.timed("retain", self.inner.retain(predicate)).await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.timed("count", self.inner.count()).await
+    }
+
+    async fn approx_size_bytes(&self) -> Result<Option<u64>> {
+        self.timed("approx_size_bytes", self.inner.approx_size_bytes())
+            .await
+    }
+
+    async fn list(
+        &self,
+        cursor: Option<SessionCursor>,
+        limit: usize,
+    ) -> Result<(Vec<SessionID>, Option<SessionCursor>)> {
+        self.timed("list", self.inner.list(cursor, limit)).await
+    }
+
+    async fn export(&self) -> Result<Vec<Session<Self::SessionData>>> {
+        self.timed("export", self.inner.export()).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::in_memory::InMemory;
+    use std::sync::{Arc, Mutex};
+
+    /// An `InMemory` store whose loads take `delay`.
+    struct SlowLoads {
+        inner: InMemory<()>,
+        delay: Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl SessionStore for SlowLoads {
+        type SessionData = ();
+
+        fn init() -> Self {
+            Self {
+                inner: InMemory::init(),
+                delay: Duration::from_millis(50),
+            }
+        }
+
+        async fn load(&self, session_id: SessionID) -> Result<Option<Session<()>>> {
+            std::thread::sleep(self.delay);
+            self.inner.load(session_id).await
+        }
+
+        async fn store(&self, session: Session<()>) -> Result<()> {
+            self.inner.store(session).await
+        }
+
+        async fn clear(&self) -> Result<()> {
+            self.inner.clear().await
+        }
+
+        async fn destroy(&self, session: Session<()>) -> Result<()> {
+            self.inner.destroy(session).await
+        }
+
+        async fn tidy(&self, tolerance: i64) -> Result<()> {
+            self.inner.tidy(tolerance).await
+        }
+    }
+
+    fn recording_store(
+        threshold: Duration,
+    ) -> (TimedStore<SlowLoads>, Arc<Mutex<Vec<SlowOperation>>>) {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        let store = TimedStore::new(SlowLoads::init(), threshold)
+            .with_sink(move |slow: &SlowOperation| sink.lock().unwrap().push(slow.clone()));
+
+        (store, reports)
+    }
+
+    #[tokio::test]
+    async fn operations_over_the_threshold_are_reported() {
+        let (store, reports) = recording_store(Duration::from_millis(10));
+        let session = Session::new(3600);
+        store.store(session.clone()).await.unwrap();
+        store.load(session.id().clone()).await.unwrap();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].operation, "load");
+        assert!(reports[0].elapsed >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn operations_under_the_threshold_are_not() {
+        let (store, reports) = recording_store(Duration::from_secs(5));
+        let session = Session::new(3600);
+        store.store(session.clone()).await.unwrap();
+        store.load(session.id().clone()).await.unwrap();
+
+        assert!(reports.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,14 @@
+/// Transforms session data as the session fairing moves it across the store boundary, e.g. to
+/// decrypt a field or fill in derived fields on load, or to strip transient fields before a
+/// write. Unlike a [`CookieCodec`](crate::codec::CookieCodec), it works on the typed data. Hooks
+/// run on the request path, so they should be cheap.
+pub trait DataTransform<Data>: Send + Sync + 'static {
+    /// Called on a loaded session's data after its expiry check, so never for expired sessions
+    /// (nor for new ones), and before any handler sees it. The change doesn't by itself mark the
+    /// session dirty.
+    fn on_load(&self, _data: &mut Data) {}
+
+    /// Called on a copy of the session's data right before it's written to the store, so the
+    /// request's session keeps the data as its handlers left it.
+    fn before_store(&self, _data: &mut Data) {}
+}
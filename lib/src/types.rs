@@ -1,6 +1,14 @@
 use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const REDACTED_PREFIX_LEN: usize = 6;
+
+pub(crate) fn redact(secret: &str) -> String {
+    let prefix: String = secret.chars().take(REDACTED_PREFIX_LEN).collect();
+    format!("{}…", prefix)
+}
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct CsrfToken(pub String);
@@ -14,14 +22,71 @@ impl Display for CsrfToken {
 #[derive(Clone, Debug, Default, Hash, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SessionID(pub String);
 
+impl SessionID {
+    /// The longest ID [`SessionID::parse`] accepts.
+    pub const MAX_LEN: usize = 256;
+
+    /// Parses an ID sent by a client, rejecting empty values, values longer than
+    /// [`SessionID::MAX_LEN`], and values with characters other than ASCII alphanumerics and
+    /// `-_.~+/=` (enough for generated IDs and base64), so garbage never reaches the store.
+    pub fn parse(value: &str) -> Option<SessionID> {
+        let valid = !value.is_empty()
+            && value.len() <= Self::MAX_LEN
+            && value
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || b"-_.~+/=".contains(&byte));
+
+        if valid {
+            Some(SessionID(value.to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// The first few characters of the ID followed by an ellipsis, safe to include in logs.
+    pub fn redacted(&self) -> String {
+        redact(&self.0)
+    }
+
+    /// The SHA-256 hash of the ID, URL-safe base64 encoded. Unlike [`SessionID::redacted`], it
+    /// identifies the session without revealing any of its ID, e.g. for audit logs.
+    pub fn hashed(&self) -> String {
+        base64::encode_config(Sha256::digest(self.0.as_bytes()), base64::URL_SAFE_NO_PAD)
+    }
+}
+
 impl Display for SessionID {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
-impl Into<SessionID> for &str {
-    fn into(self) -> SessionID {
-        SessionID(self.to_string())
+/// Where [`SessionStore::list`](crate::SessionStore::list) resumes listing sessions. Its contents
+/// are up to the store; pass it back unchanged.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionCursor(pub String);
+
+impl From<&str> for SessionID {
+    fn from(id: &str) -> Self {
+        SessionID(id.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_malformed_ids() {
+        let id = "Xr3Ua1h8GdvyJ8Wwx0kwW6aKeD2pD9ZV0rvgqL1u";
+        assert_eq!(SessionID::parse(id), Some(SessionID(id.to_string())));
+        assert!(SessionID::parse("a-b_c.d~e+f/g=").is_some());
+
+        assert_eq!(SessionID::parse(""), None);
+        assert_eq!(SessionID::parse("has space"), None);
+        assert_eq!(SessionID::parse("nul\0"), None);
+        assert_eq!(SessionID::parse("ünïcode"), None);
+        assert_eq!(SessionID::parse(&"a".repeat(SessionID::MAX_LEN + 1)), None);
+        assert!(SessionID::parse(&"a".repeat(SessionID::MAX_LEN)).is_some());
     }
 }
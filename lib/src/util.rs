@@ -1,15 +1,294 @@
-use rand::{distributions::Alphanumeric, Rng};
-use rocket::http::CookieJar;
+use rand::RngCore;
+#[cfg(feature = "rocket")]
+use rocket::{
+    http::{Cookie, CookieJar},
+    Response,
+};
+#[cfg(feature = "rocket")]
+use time::Duration;
 
+#[cfg(feature = "rocket")]
+use crate::fairing::config::SessionConfig;
+
+#[cfg(feature = "rocket")]
 pub(crate) fn private_cookie_exists(cookie_jar: &CookieJar<'_>, cookie_name: &str) -> bool {
     cookie_jar.get_private(cookie_name).is_some()
         || cookie_jar.get_private_pending(cookie_name).is_some()
 }
 
-pub(crate) fn random_string() -> String {
-    rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(40)
-        .map(|c| c as char)
-        .collect()
+/// Builds a cookie with the config's attributes. `value` is used as is, so a session ID must
+/// go through the private jar (or the cookie codec) before it's sent; headers set directly on
+/// the response only ever carry a value that already did.
+#[cfg(feature = "rocket")]
+pub(crate) fn make_cookie(config: &SessionConfig, name: &str, value: String) -> Cookie<'static> {
+    // A negative `Max-Age` means the same to browsers as zero, but some reject the cookie
+    // outright, so it's clamped. Any `i32` fits in a `Duration`, so this can't overflow.
+    let max_age = Duration::seconds(config.cookie_max_age().max(0).into());
+    let mut cookie = Cookie::build(name.to_string(), value)
+        .max_age(max_age)
+        .same_site(config.same_site.into())
+        .http_only(config.http_only)
+        .finish();
+
+    if let Some(secure) = config.secure {
+        cookie.set_secure(secure);
+    }
+
+    if let Some(domain) = &config.domain {
+        cookie.set_domain(domain.clone());
+    }
+
+    if let Some(path) = &config.path {
+        cookie.set_path(path.clone());
+    }
+
+    cookie
+}
+
+/// Builds a cookie that makes the browser delete the cookie `name` set by [`make_cookie`]: it has
+/// an empty value and `Max-Age=0`, and the same `Path` and `Domain`, since a mismatch in either
+/// makes the browser treat it as a different cookie and keep the original.
+#[cfg(feature = "rocket")]
+pub(crate) fn make_expired_cookie(config: &SessionConfig, name: &str) -> Cookie<'static> {
+    let mut cookie = make_cookie(config, name, String::new());
+    cookie.set_max_age(Duration::zero());
+
+    cookie
+}
+
+/// Whether `host` (optionally with a port) is `domain` or one of its subdomains. A leading dot on
+/// `domain` is ignored, as browsers do.
+#[cfg(feature = "rocket")]
+pub(crate) fn host_within_domain(host: &str, domain: &str) -> bool {
+    let host = host
+        .rsplitn(2, ':')
+        .last()
+        .unwrap_or(host)
+        .to_ascii_lowercase();
+    let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// Whether `path` is `prefix` or lies under it, comparing whole segments: `/health` covers
+/// `/health` and `/health/live`, but not `/healthz`. A trailing slash on `prefix` is ignored.
+#[cfg(feature = "rocket")]
+pub(crate) fn path_within(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+
+    path == prefix || (path.starts_with(prefix) && path[prefix.len()..].starts_with('/'))
+}
+
+/// Renders `cookie` as a `Set-Cookie` header value, including the attributes the `cookie` crate
+/// doesn't know about (currently just `Priority`). Like Rocket's own cookie handling, the name
+/// and value are percent-encoded.
+#[cfg(feature = "rocket")]
+pub(crate) fn set_cookie_header(config: &SessionConfig, cookie: &Cookie<'_>) -> String {
+    match config.priority {
+        Some(priority) => format!("{}; Priority={}", cookie.encoded(), priority),
+        None => cookie.encoded().to_string(),
+    }
+}
+
+/// Removes the `Set-Cookie` header for `cookie_name` from the response, leaving every other
+/// `Set-Cookie` header in place, and returns the removed cookie with its value percent-decoded.
+#[cfg(feature = "rocket")]
+pub(crate) fn take_set_cookie(
+    res: &mut Response<'_>,
+    cookie_name: &str,
+) -> Option<Cookie<'static>> {
+    let set_cookies: Vec<String> = res
+        .headers()
+        .get("Set-Cookie")
+        .map(|value| value.to_string())
+        .collect();
+    res.remove_header("Set-Cookie");
+
+    let mut taken = None;
+    for raw in set_cookies {
+        match Cookie::parse_encoded(raw.clone()) {
+            Ok(cookie) if taken.is_none() && cookie.name() == cookie_name => taken = Some(cookie),
+            _ => res.adjoin_raw_header("Set-Cookie", raw),
+        }
+    }
+
+    taken
+}
+
+/// The longest path, in whole segments, that every one of `paths` falls under, or `None` if
+/// `paths` is empty.
+#[cfg(feature = "rocket")]
+pub(crate) fn common_path_prefix<'a>(paths: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let mut common: Option<Vec<&str>> = None;
+    for path in paths {
+        let segments = path.split('/').filter(|segment| !segment.is_empty());
+        common = Some(match common {
+            None => segments.collect(),
+            Some(common) => common
+                .into_iter()
+                .zip(segments)
+                .take_while(|(left, right)| left == right)
+                .map(|(segment, _)| segment)
+                .collect(),
+        });
+    }
+
+    common.map(|segments| format!("/{}", segments.join("/")))
+}
+
+const ALPHANUMERIC: &[u8; 62] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const RANDOM_STRING_LEN: usize = 40;
+
+/// 40 uniformly random alphanumeric characters. Each `u64` drawn from `rng` is split into ten
+/// 6-bit indices, rejecting those past the end of the alphabet, which takes about a fifth of the
+/// RNG calls of sampling each character on its own.
+pub(crate) fn random_string(rng: &mut impl RngCore) -> String {
+    let mut chars = [0u8; RANDOM_STRING_LEN];
+    let mut filled = 0;
+    while filled < RANDOM_STRING_LEN {
+        let mut bits = rng.next_u64();
+        for _ in 0..10 {
+            let index = (bits & 0x3f) as usize;
+            bits >>= 6;
+            if index < ALPHANUMERIC.len() && filled < RANDOM_STRING_LEN {
+                chars[filled] = ALPHANUMERIC[index];
+                filled += 1;
+            }
+        }
+    }
+
+    String::from_utf8(chars.to_vec()).expect("the alphabet is ASCII")
+}
+
+#[cfg(all(test, feature = "rocket"))]
+mod test {
+    use super::*;
+    use crate::fairing::config::{CookiePriority, LifetimeConfig};
+
+    #[test]
+    fn set_cookie_header_includes_priority_when_configured() {
+        let config = SessionConfig {
+            priority: Some(CookiePriority::High),
+            ..SessionConfig::default()
+        };
+        let cookie = make_cookie(&config, "session_id", "abc".to_string());
+
+        assert!(set_cookie_header(&config, &cookie).ends_with("; Priority=High"));
+    }
+
+    #[test]
+    fn set_cookie_header_omits_priority_by_default() {
+        let config = SessionConfig::default();
+        let cookie = make_cookie(&config, "session_id", "abc".to_string());
+
+        assert!(!set_cookie_header(&config, &cookie).contains("Priority"));
+    }
+
+    #[test]
+    fn set_cookie_header_round_trips_reserved_characters() {
+        let config = SessionConfig {
+            priority: Some(CookiePriority::High),
+            ..SessionConfig::default()
+        };
+        let value = "a+b/c==; d";
+        let cookie = make_cookie(&config, "session_id", value.to_string());
+
+        let header = set_cookie_header(&config, &cookie);
+        assert!(!header.contains(value));
+
+        let parsed = Cookie::parse_encoded(header).unwrap();
+        assert_eq!(parsed.value(), value);
+    }
+
+    #[test]
+    fn make_cookie_handles_extreme_max_ages() {
+        for &(max_age, expected) in &[(0, 0), (i32::MAX, i32::MAX), (-1, 0), (i32::MIN, 0)] {
+            let config = SessionConfig {
+                max_age,
+                ..SessionConfig::default()
+            };
+            let cookie = make_cookie(&config, "session_id", "abc".to_string());
+
+            assert_eq!(cookie.max_age(), Some(Duration::seconds(expected.into())));
+            assert!(set_cookie_header(&config, &cookie).contains(&format!("Max-Age={}", expected)));
+        }
+    }
+
+    #[test]
+    fn the_cookie_max_age_comes_from_the_lifetime() {
+        let mut config = SessionConfig {
+            lifetime: Some(LifetimeConfig {
+                idle_timeout: 900,
+                cookie_max_age: Some(1800),
+                ..LifetimeConfig::default()
+            }),
+            ..SessionConfig::default()
+        };
+        let cookie = make_cookie(&config, "session_id", "abc".to_string());
+        assert_eq!(cookie.max_age(), Some(Duration::seconds(1800)));
+
+        // as a session's own lifespan does
+        config.set_cookie_max_age(60);
+        let cookie = make_cookie(&config, "session_id", "abc".to_string());
+        assert_eq!(cookie.max_age(), Some(Duration::seconds(60)));
+    }
+
+    #[test]
+    fn expired_cookie_matches_the_original_scope() {
+        let config = SessionConfig {
+            domain: Some("example.local".to_string()),
+            path: Some("/app".to_string()),
+            ..SessionConfig::default()
+        };
+        let cookie = make_cookie(&config, "session_id", "abc".to_string());
+        let expired = make_expired_cookie(&config, "session_id");
+
+        assert_eq!(expired.name(), cookie.name());
+        assert_eq!(expired.value(), "");
+        assert_eq!(expired.max_age(), Some(Duration::zero()));
+        assert_eq!(expired.path(), cookie.path());
+        assert_eq!(expired.domain(), cookie.domain());
+        assert!(set_cookie_header(&config, &expired).contains("Max-Age=0"));
+    }
+
+    #[test]
+    fn common_path_prefix_compares_whole_segments() {
+        assert_eq!(
+            common_path_prefix(["/app", "/app/"].iter().copied()),
+            Some("/app".into())
+        );
+        assert_eq!(
+            common_path_prefix(["/app/admin", "/app/api"].iter().copied()),
+            Some("/app".into())
+        );
+        assert_eq!(
+            common_path_prefix(["/app", "/apple"].iter().copied()),
+            Some("/".into())
+        );
+        assert_eq!(common_path_prefix(["/"].iter().copied()), Some("/".into()));
+        assert_eq!(common_path_prefix(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn host_within_domain_matches_the_domain_and_its_subdomains() {
+        assert!(host_within_domain("example.com", ".example.com"));
+        assert!(host_within_domain(
+            "tenant.example.com:8000",
+            ".example.com"
+        ));
+        assert!(host_within_domain("A.Example.com", "example.com"));
+        assert!(!host_within_domain("example.org", ".example.com"));
+        assert!(!host_within_domain("notexample.com", ".example.com"));
+    }
+
+    #[test]
+    fn path_within_matches_whole_segments() {
+        assert!(path_within("/health", "/health"));
+        assert!(path_within("/health/live", "/health"));
+        assert!(path_within("/health/live", "/health/"));
+        assert!(path_within("/health", "/"));
+        assert!(!path_within("/healthz", "/health"));
+        assert!(!path_within("/health-admin", "/health"));
+        assert!(!path_within("/", "/health"));
+    }
 }
@@ -0,0 +1,70 @@
+mod common;
+
+use rocket::{get, local::asynchronous::Client, post, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session, SessionStore,
+};
+
+use common::HitCounter;
+
+type Store = InMemory<HitCounter>;
+
+fn rocket() -> Rocket {
+    let config = SessionConfig::builder()
+        .require_activation(true)
+        .try_finish()
+        .unwrap();
+
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(config))
+        .mount("/", routes![increment_count, login])
+}
+
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+#[post("/login")]
+fn login(s: &Session<HitCounter>) -> String {
+    s.activate();
+    increment_count(s)
+}
+
+#[rocket::async_test]
+async fn an_anonymous_request_sets_no_cookie() {
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client.get("/").dispatch().await;
+    assert!(response.cookies().iter().next().is_none());
+    assert_eq!(response.into_string().await.unwrap(), "1");
+
+    let response = client.get("/").dispatch().await;
+    assert_eq!(response.into_string().await.unwrap(), "1");
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+    assert_eq!(store.count().await.unwrap(), 0);
+}
+
+#[rocket::async_test]
+async fn an_activated_session_is_kept() {
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client.post("/login").dispatch().await;
+    assert!(response.cookies().get("session_id").is_some());
+    assert!(response.cookies().get("xsrf_token").is_some());
+    assert_eq!(response.into_string().await.unwrap(), "1");
+
+    // no need to activate a session loaded from the store
+    let response = client.get("/").dispatch().await;
+    assert_eq!(response.into_string().await.unwrap(), "2");
+}
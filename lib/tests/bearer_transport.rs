@@ -0,0 +1,74 @@
+mod common;
+
+use rocket::{get, http::Header, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{
+        config::{SessionConfig, TokenDerivation, Transport},
+        SessionFairing,
+    },
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+fn rocket() -> Rocket {
+    let config = SessionConfig {
+        transport: Transport::Bearer {
+            derivation: TokenDerivation::Hash,
+        },
+        ..SessionConfig::default()
+    };
+
+    rocket::ignite()
+        .attach(SessionFairing::<InMemory<HitCounter>>::with_config(config))
+        .mount("/", routes![increment_count])
+}
+
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+fn bearer(token: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {}", token))
+}
+
+#[test]
+fn it_keys_the_session_by_the_bearer_token() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let get = |token| {
+        client
+            .get("/")
+            .header(bearer(token))
+            .dispatch()
+            .into_string()
+            .unwrap()
+    };
+
+    assert_eq!(get("alice-token"), "1");
+    assert_eq!(get("alice-token"), "2");
+    assert_eq!(get("bob-token"), "1");
+    assert_eq!(get("alice-token"), "3");
+}
+
+#[test]
+fn it_sends_nothing_back() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/").header(bearer("alice-token")).dispatch();
+    assert!(response.cookies().iter().next().is_none());
+    assert!(response.headers().get_one("Authorization").is_none());
+}
+
+#[test]
+fn a_request_without_a_token_gets_a_throwaway_session() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "1");
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "1");
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use rand::{rngs::StdRng, SeedableRng};
+use rocket::{get, local::blocking::Client, routes, Rocket, State};
+use turbopump::{
+    codec::{CookieCodec, HmacCodec},
+    fairing::{config::SessionConfig, SessionFairing},
+    id::{RngGenerator, SessionIdGenerator},
+    observer::SessionObserver,
+    store::SessionStore,
+    Session, SessionID,
+};
+
+use common::{CountingStore, HitCounter};
+
+#[derive(Clone, Default)]
+struct Counts {
+    created: Arc<AtomicUsize>,
+    destroyed: Arc<AtomicUsize>,
+}
+
+impl SessionObserver for Counts {
+    fn session_created(&self, _session_id: &SessionID) {
+        self.created.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn session_destroyed(&self, _session_id: &SessionID) {
+        self.destroyed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+const KEY: &str = "a very secret signing key";
+
+fn rocket(counts: Counts) -> Rocket {
+    let config = SessionConfig::builder()
+        .cookie_name("sid")
+        .try_finish()
+        .unwrap();
+    let fairing = SessionFairing::<CountingStore<HitCounter>>::builder()
+        .config(config)
+        .store(CountingStore::init())
+        .codec(HmacCodec::new(KEY))
+        .id_generator(RngGenerator::new(StdRng::seed_from_u64(7)))
+        .observer(counts)
+        .build();
+
+    rocket::ignite()
+        .attach(fairing)
+        .mount("/", routes![index, logout])
+}
+
+#[get("/")]
+fn index(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| counter.count += 1);
+    s.id().to_string()
+}
+
+#[get("/logout")]
+fn logout(s: &Session<HitCounter>, store: State<'_, Box<CountingStore<HitCounter>>>) -> String {
+    s.invalidate();
+    store.writes().to_string()
+}
+
+#[test]
+fn builder_applies_every_option() {
+    let counts = Counts::default();
+    let client = Client::tracked(rocket(counts.clone())).expect("valid rocket");
+
+    let response = client.get("/").dispatch();
+    let cookie = response.cookies().get("sid").cloned().unwrap();
+    let id = response.into_string().unwrap();
+
+    // the id comes from the seeded generator and the cookie is signed rather than encrypted
+    let expected = RngGenerator::new(StdRng::seed_from_u64(7));
+    assert_eq!(id, expected.generate());
+    assert_eq!(HmacCodec::new(KEY).decode(cookie.value()), Some(id));
+    assert_eq!(counts.created.load(Ordering::SeqCst), 1);
+
+    // the pre-built store is the one in managed state
+    let writes = client.get("/logout").dispatch().into_string().unwrap();
+    assert_eq!(writes, "1");
+    assert_eq!(counts.created.load(Ordering::SeqCst), 1);
+    assert_eq!(counts.destroyed.load(Ordering::SeqCst), 1);
+}
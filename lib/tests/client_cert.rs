@@ -0,0 +1,61 @@
+mod common;
+
+use rocket::{get, http::Header, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    client_cert::ForwardedCertificate, fairing::SessionFairing, store::in_memory::InMemory, Session,
+};
+
+use common::HitCounter;
+
+const CERT_HEADER: &str = "X-Client-Cert";
+
+fn rocket() -> Rocket {
+    let fairing = SessionFairing::<InMemory<HitCounter>>::builder()
+        .config(Default::default())
+        .client_cert_source(ForwardedCertificate::new(CERT_HEADER))
+        .build();
+
+    rocket::ignite().attach(fairing).mount("/", routes![index])
+}
+
+#[get("/")]
+fn index(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+fn visit(client: &Client, cert: Option<&'static str>) -> String {
+    let mut request = client.get("/");
+    if let Some(cert) = cert {
+        request = request.header(Header::new(CERT_HEADER, cert));
+    }
+
+    request.dispatch().into_string().unwrap()
+}
+
+#[test]
+fn sessions_follow_their_client_certificate() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    assert_eq!(visit(&client, Some("cert-a")), "1");
+    assert_eq!(visit(&client, Some("cert-a")), "2");
+
+    // a stolen cookie presented with another certificate, or none, gets a fresh session
+    assert_eq!(visit(&client, Some("cert-b")), "1");
+    assert_eq!(visit(&client, None), "1");
+}
+
+#[test]
+fn sessions_without_a_certificate_are_left_unbound() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    assert_eq!(visit(&client, None), "1");
+    assert_eq!(visit(&client, None), "2");
+
+    // the first certificate presented binds the session
+    assert_eq!(visit(&client, Some("cert-a")), "3");
+    assert_eq!(visit(&client, Some("cert-b")), "1");
+}
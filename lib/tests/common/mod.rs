@@ -0,0 +1,160 @@
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use turbopump::{
+    error::SessionStoreError,
+    store::{in_memory::InMemory, SessionStore},
+    Error, Session, SessionID,
+};
+
+#[derive(Clone, Default)]
+pub struct HitCounter {
+    pub count: u32,
+}
+
+/// An `InMemory` store that counts how many times sessions were written to or renewed in it.
+pub struct CountingStore<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    inner: InMemory<Data>,
+    writes: AtomicUsize,
+    renews: AtomicUsize,
+}
+
+impl<Data> CountingStore<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    pub fn inner(&self) -> &InMemory<Data> {
+        &self.inner
+    }
+
+    pub fn writes(&self) -> usize {
+        self.writes.load(Ordering::SeqCst)
+    }
+
+    pub fn renews(&self) -> usize {
+        self.renews.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait::async_trait]
+impl<Data> SessionStore for CountingStore<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type SessionData = Data;
+
+    fn init() -> Self {
+        Self {
+            inner: InMemory::init(),
+            writes: AtomicUsize::new(0),
+            renews: AtomicUsize::new(0),
+        }
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Data>>, Error> {
+        self.inner.load(session_id).await
+    }
+
+    async fn store(&self, session: Session<Data>) -> Result<(), Error> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        self.inner.store(session).await
+    }
+
+    async fn clear(&self) -> Result<(), Error> {
+        self.inner.clear().await
+    }
+
+    async fn destroy(&self, session: Session<Data>) -> Result<(), Error> {
+        self.inner.destroy(session).await
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<(), Error> {
+        self.inner.tidy(tolerance).await
+    }
+
+    async fn renew(&self, session_id: &SessionID, lifespan: i64) -> Result<(), Error> {
+        self.renews.fetch_add(1, Ordering::SeqCst);
+        self.inner.renew(session_id, lifespan).await
+    }
+}
+
+/// An `InMemory` store whose writes always fail.
+pub struct FailingStore<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    inner: InMemory<Data>,
+}
+
+#[async_trait::async_trait]
+impl<Data> SessionStore for FailingStore<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type SessionData = Data;
+
+    fn init() -> Self {
+        Self {
+            inner: InMemory::init(),
+        }
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<Data>>, Error> {
+        self.inner.load(session_id).await
+    }
+
+    async fn store(&self, _session: Session<Data>) -> Result<(), Error> {
+        Err(SessionStoreError::StoreFailure("store is unavailable".to_string()).into())
+    }
+
+    async fn clear(&self) -> Result<(), Error> {
+        self.inner.clear().await
+    }
+
+    async fn destroy(&self, session: Session<Data>) -> Result<(), Error> {
+        self.inner.destroy(session).await
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<(), Error> {
+        self.inner.tidy(tolerance).await
+    }
+}
+
+/// A store whose backend can't be reached: every operation fails.
+pub struct UnreachableStore<Data>(std::marker::PhantomData<fn() -> Data>);
+
+#[async_trait::async_trait]
+impl<Data> SessionStore for UnreachableStore<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type SessionData = Data;
+
+    fn init() -> Self {
+        Self(std::marker::PhantomData)
+    }
+
+    async fn load(&self, _session_id: SessionID) -> Result<Option<Session<Data>>, Error> {
+        Err(SessionStoreError::Connection("connection refused".to_string()).into())
+    }
+
+    async fn store(&self, _session: Session<Data>) -> Result<(), Error> {
+        Err(SessionStoreError::Connection("connection refused".to_string()).into())
+    }
+
+    async fn clear(&self) -> Result<(), Error> {
+        Err(SessionStoreError::Connection("connection refused".to_string()).into())
+    }
+
+    async fn destroy(&self, _session: Session<Data>) -> Result<(), Error> {
+        Err(SessionStoreError::Connection("connection refused".to_string()).into())
+    }
+
+    async fn tidy(&self, _tolerance: i64) -> Result<(), Error> {
+        Err(SessionStoreError::Connection("connection refused".to_string()).into())
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, CompanionCookie, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+fn rocket() -> Rocket {
+    let config = SessionConfig::builder()
+        .http_only(true)
+        .try_finish()
+        .unwrap();
+    let signed_in = CompanionCookie::new("signed_in", |session: &Session<HitCounter>| {
+        session.read(|counter| Some((counter.count > 0).to_string()))
+    });
+    let fairing = SessionFairing::<InMemory<HitCounter>>::builder()
+        .config(config)
+        .companion_cookie(signed_in)
+        .build();
+
+    rocket::ignite()
+        .attach(fairing)
+        .mount("/", routes![index, sign_in, sign_out])
+}
+
+#[get("/")]
+fn index(_s: &Session<HitCounter>) -> &'static str {
+    "Home"
+}
+
+#[get("/sign_in")]
+fn sign_in(s: &Session<HitCounter>) -> &'static str {
+    s.tap(|counter| counter.count += 1);
+    "Signed in"
+}
+
+#[get("/sign_out")]
+fn sign_out(s: &Session<HitCounter>) -> &'static str {
+    s.invalidate();
+    "Signed out"
+}
+
+#[test]
+fn a_script_readable_companion_is_set_next_to_the_session_cookie() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/").dispatch();
+    let session = response.cookies().get("session_id").cloned().unwrap();
+    let companion = response.cookies().get("signed_in").cloned().unwrap();
+    assert_eq!(session.http_only(), Some(true));
+    assert_ne!(companion.http_only(), Some(true));
+    assert_eq!(companion.value(), "false");
+
+    // unchanged, so not re-sent
+    let response = client.get("/").dispatch();
+    assert!(response.cookies().get("signed_in").is_none());
+
+    let response = client.get("/sign_in").dispatch();
+    assert_eq!(response.cookies().get("signed_in").unwrap().value(), "true");
+
+    let response = client.get("/sign_out").dispatch();
+    let companion = response.cookies().get("signed_in").cloned().unwrap();
+    assert_eq!(companion.max_age(), Some(time::Duration::zero()));
+}
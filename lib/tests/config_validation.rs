@@ -0,0 +1,33 @@
+mod common;
+
+use rocket::{local::blocking::Client, Rocket};
+use turbopump::{
+    fairing::{
+        config::{SameSite, SessionConfig},
+        SessionFairing,
+    },
+    store::in_memory::InMemory,
+};
+
+use common::HitCounter;
+
+fn rocket(strict: bool) -> Rocket {
+    // browsers reject `SameSite=None` cookies that aren't `Secure`
+    let config = SessionConfig {
+        same_site: SameSite::None,
+        secure: Some(false),
+        ..SessionConfig::default()
+    };
+    let mut fairing = SessionFairing::<InMemory<HitCounter>>::builder().config(config);
+    if strict {
+        fairing = fairing.strict();
+    }
+
+    rocket::ignite().attach(fairing.build())
+}
+
+#[test]
+fn a_strict_fairing_refuses_to_launch_with_an_invalid_config() {
+    assert!(Client::tracked(rocket(true)).is_err());
+    assert!(Client::tracked(rocket(false)).is_ok());
+}
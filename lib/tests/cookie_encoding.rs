@@ -0,0 +1,74 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    codec::CookieCodec,
+    fairing::{
+        config::{CookiePriority, SessionConfig},
+        SessionFairing,
+    },
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+/// Wraps the session ID in characters that need percent-encoding inside a cookie.
+struct ReservedCharsCodec;
+
+const SUFFIX: &str = "+/==; ,";
+
+impl CookieCodec for ReservedCharsCodec {
+    fn encode(&self, value: &str) -> String {
+        format!("{}{}", value, SUFFIX)
+    }
+
+    fn decode(&self, encoded: &str) -> Option<String> {
+        encoded.strip_suffix(SUFFIX).map(str::to_string)
+    }
+}
+
+fn rocket(priority: Option<CookiePriority>) -> Rocket {
+    let config = SessionConfig {
+        priority,
+        ..SessionConfig::default()
+    };
+
+    rocket::ignite()
+        .attach(
+            SessionFairing::<InMemory<HitCounter>>::with_config(config)
+                .with_codec(ReservedCharsCodec),
+        )
+        .mount("/", routes![increment_count])
+}
+
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+fn assert_round_trips(client: &Client) {
+    let response = client.get("/").dispatch();
+    let cookie = response.cookies().get("session_id").unwrap().clone();
+    assert!(cookie.value().ends_with(SUFFIX));
+    assert_eq!(response.into_string().unwrap(), "1");
+
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "2");
+}
+
+#[test]
+fn it_round_trips_values_set_through_the_cookie_jar() {
+    let client = Client::tracked(rocket(None)).expect("valid rocket instance");
+    assert_round_trips(&client);
+}
+
+#[test]
+fn it_round_trips_values_set_through_rewritten_headers() {
+    let client =
+        Client::tracked(rocket(Some(CookiePriority::High))).expect("valid rocket instance");
+    assert_round_trips(&client);
+}
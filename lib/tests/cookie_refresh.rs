@@ -0,0 +1,78 @@
+use chrono::{Duration, Utc};
+use rocket::{get, http::Cookie, local::asynchronous::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session, SessionStore,
+};
+
+type Store = InMemory<()>;
+
+fn rocket() -> Rocket {
+    let config = SessionConfig::builder()
+        .cookie_refresh_threshold(60)
+        .try_finish()
+        .unwrap();
+
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(config))
+        .mount("/", routes![index])
+}
+
+#[get("/")]
+fn index(_s: &Session<()>) -> &'static str {
+    "Hello, world!"
+}
+
+/// Stores a session expiring in an hour whose last cookie expires `cookie_expires_in` seconds
+/// from now.
+async fn store_session(store: &Store, id: &str, cookie_expires_in: i64) {
+    let now = Utc::now();
+    let record = serde_json::json!({
+        "id": id,
+        "token": "token",
+        "created": now,
+        "expiration": now + Duration::seconds(3600),
+        "last_accessed": now,
+        "cookie_expiration": now + Duration::seconds(cookie_expires_in),
+        "data": null,
+    });
+    let session: Session<()> = serde_json::from_value(record).unwrap();
+    store.store(session).await.unwrap();
+}
+
+#[rocket::async_test]
+async fn the_cookie_is_reissued_once_its_expiry_falls_behind_the_session() {
+    let client = Client::untracked(rocket())
+        .await
+        .expect("valid rocket instance");
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+
+    // renewed to expire in an hour, like the client's cookie
+    store_session(store, "up-to-date", 3600).await;
+    let response = client
+        .get("/")
+        .private_cookie(Cookie::new("session_id", "up-to-date"))
+        .dispatch()
+        .await;
+    assert!(response.cookies().get("session_id").is_none());
+
+    // renewed to expire in an hour, but the client's cookie expires in ten minutes
+    store_session(store, "sliding", 600).await;
+    let response = client
+        .get("/")
+        .private_cookie(Cookie::new("session_id", "sliding"))
+        .dispatch()
+        .await;
+    let cookie = response.cookies().get_private("session_id").unwrap();
+    assert_eq!(cookie.value(), "sliding");
+    assert_eq!(cookie.max_age(), Some(time::Duration::seconds(3600)));
+
+    // the refreshed cookie is remembered, so the next request doesn't re-issue it
+    let response = client
+        .get("/")
+        .private_cookie(Cookie::new("session_id", "sliding"))
+        .dispatch()
+        .await;
+    assert!(response.cookies().get("session_id").is_none());
+}
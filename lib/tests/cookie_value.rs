@@ -0,0 +1,65 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    codec::CookieCodec,
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+/// Reads `<session id>.<tenant>` cookies, as a gateway routing on the tenant might require.
+struct TenantCodec;
+
+impl CookieCodec for TenantCodec {
+    fn encode(&self, value: &str) -> String {
+        format!("{}.default", value)
+    }
+
+    fn decode(&self, encoded: &str) -> Option<String> {
+        encoded.split('.').next().map(str::to_string)
+    }
+}
+
+fn rocket() -> Rocket {
+    rocket::ignite()
+        .attach(
+            SessionFairing::<InMemory<HitCounter>>::with_config(SessionConfig::default())
+                .with_codec(TenantCodec),
+        )
+        .mount("/", routes![increment_count, eu_west])
+}
+
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+#[get("/eu-west")]
+fn eu_west(s: &Session<HitCounter>) -> String {
+    s.set_cookie_value(format!("{}.eu-west", s.id()));
+    increment_count(s)
+}
+
+#[test]
+fn an_overridden_cookie_value_resumes_the_session() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/eu-west").dispatch();
+    let value = response
+        .cookies()
+        .get("session_id")
+        .unwrap()
+        .value()
+        .to_string();
+    assert!(value.ends_with(".eu-west"));
+    assert_eq!(response.into_string().unwrap(), "1");
+
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "2");
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use rocket::{get, http::Header, local::asynchronous::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::{in_memory::InMemory, SessionStore},
+    Session,
+};
+
+use common::HitCounter;
+
+type Store = InMemory<HitCounter>;
+
+fn rocket() -> Rocket {
+    let config = SessionConfig::builder()
+        .max_new_sessions_per_ip(2)
+        .new_session_window(60)
+        .try_finish()
+        .unwrap();
+
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(config))
+        .mount("/", routes![index])
+}
+
+#[get("/")]
+fn index(s: &Session<HitCounter>) -> &'static str {
+    s.tap(|counter| counter.count += 1);
+    if s.is_ephemeral() {
+        "throttled"
+    } else {
+        "ok"
+    }
+}
+
+async fn visit_from(client: &Client, ip: &'static str) -> (bool, String) {
+    let response = client
+        .get("/")
+        .header(Header::new("X-Real-IP", ip))
+        .dispatch()
+        .await;
+    let cookie_set = response.cookies().get("session_id").is_some();
+
+    (cookie_set, response.into_string().await.unwrap())
+}
+
+#[rocket::async_test]
+async fn rapid_cookieless_requests_from_one_ip_are_throttled() {
+    let client = Client::untracked(rocket())
+        .await
+        .expect("valid rocket instance");
+    let ok = (true, "ok".to_string());
+
+    assert_eq!(visit_from(&client, "203.0.113.7").await, ok);
+    assert_eq!(visit_from(&client, "203.0.113.7").await, ok);
+    for _ in 0..3 {
+        let throttled = (false, "throttled".to_string());
+        assert_eq!(visit_from(&client, "203.0.113.7").await, throttled);
+    }
+    assert_eq!(visit_from(&client, "198.51.100.1").await, ok);
+
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+    assert_eq!(store.count().await.unwrap(), 3);
+}
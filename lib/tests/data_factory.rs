@@ -0,0 +1,45 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+/// Session data with no sensible default: every visitor gets the next guest number.
+#[derive(Clone)]
+struct Guest {
+    id: u32,
+}
+
+fn rocket() -> Rocket {
+    let next_guest = AtomicU32::new(1);
+    let fairing = SessionFairing::<InMemory<Guest>>::builder_with_factory(move || Guest {
+        id: next_guest.fetch_add(1, Ordering::SeqCst),
+    })
+    .config(SessionConfig::default())
+    .build();
+
+    rocket::ignite().attach(fairing).mount("/", routes![whoami])
+}
+
+#[get("/")]
+fn whoami(s: &Session<Guest>) -> String {
+    s.read(|guest| format!("guest {}", guest.id))
+}
+
+#[test]
+fn new_sessions_get_their_data_from_the_factory() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/").dispatch();
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(response.into_string().unwrap(), "guest 1");
+
+    // without the cookie, the next request starts another session
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "guest 2");
+
+    let resumed = client.get("/").cookie(cookie).dispatch();
+    assert_eq!(resumed.into_string().unwrap(), "guest 1");
+}
@@ -0,0 +1,46 @@
+use rocket::{get, http::Header, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+fn rocket() -> Rocket {
+    let config = SessionConfig::builder()
+        .domain(".example.com")
+        .validate_domain(true)
+        .try_finish()
+        .unwrap();
+
+    rocket::ignite()
+        .attach(SessionFairing::<InMemory<()>>::with_config(config))
+        .mount("/", routes![index])
+}
+
+#[get("/")]
+fn index(_s: &Session<()>) -> &'static str {
+    "Hello, world!"
+}
+
+#[test]
+fn it_sets_cookies_for_hosts_under_the_domain() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client
+        .get("/")
+        .header(Header::new("Host", "tenant.example.com"))
+        .dispatch();
+
+    assert!(response.cookies().get_private("session_id").is_some());
+}
+
+#[test]
+fn it_skips_cookies_for_other_hosts() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client
+        .get("/")
+        .header(Header::new("Host", "tenant.example.org"))
+        .dispatch();
+
+    assert!(response.cookies().get("session_id").is_none());
+    assert!(response.cookies().get("xsrf_token").is_none());
+}
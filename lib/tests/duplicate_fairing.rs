@@ -0,0 +1,56 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+type Fairing = SessionFairing<InMemory<HitCounter>>;
+
+fn config(cookie_name: &str) -> SessionConfig {
+    SessionConfig::builder()
+        .cookie_name(cookie_name)
+        .try_finish()
+        .unwrap()
+}
+
+fn rocket(second: Fairing) -> Rocket {
+    rocket::ignite()
+        .attach(Fairing::with_config(config("first")))
+        .attach(second)
+        .mount("/", routes![increment_count])
+}
+
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+#[test]
+fn a_strict_duplicate_fairing_refuses_to_launch() {
+    let second = Fairing::builder().config(config("second")).strict().build();
+
+    assert!(Client::tracked(rocket(second)).is_err());
+}
+
+#[test]
+fn a_duplicate_fairing_steps_aside_for_the_first() {
+    let client =
+        Client::tracked(rocket(Fairing::with_config(config("second")))).expect("valid rocket");
+
+    let response = client.get("/").dispatch();
+    assert!(response.cookies().get("first").is_some());
+    assert!(response.cookies().get("second").is_none());
+    assert_eq!(response.into_string().unwrap(), "1");
+
+    // the first fairing's cookie resumes the session
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "2");
+}
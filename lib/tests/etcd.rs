@@ -0,0 +1,76 @@
+#![cfg(feature = "etcd")]
+
+use std::env;
+
+use serde::{Deserialize, Serialize};
+use turbopump::{fairing::config::SessionConfig, store::etcd::EtcdStore, Session, SessionStore};
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+struct Cart {
+    items: Vec<String>,
+}
+
+// these talk to a real cluster, so they only run when one is configured
+fn store() -> Option<EtcdStore<Cart>> {
+    env::var("ETCD_ENDPOINTS").ok().map(|_| EtcdStore::init())
+}
+
+#[rocket::async_test]
+async fn it_stores_and_loads_a_session() {
+    let store = match store() {
+        Some(store) => store,
+        None => return,
+    };
+
+    let session = Session::<Cart>::new(60);
+    session.tap(|cart| cart.items.push("anvil".to_string()));
+    store.store(session.clone()).await.unwrap();
+
+    let loaded = store.load(session.id().clone()).await.unwrap().unwrap();
+    assert_eq!(loaded.id(), session.id());
+    assert_eq!(loaded.read(|cart| cart.items.clone()), vec!["anvil"]);
+
+    store.destroy(loaded).await.unwrap();
+}
+
+#[rocket::async_test]
+async fn destroy_removes_the_session() {
+    let store = match store() {
+        Some(store) => store,
+        None => return,
+    };
+
+    let session = Session::<Cart>::new(60);
+    store.store(session.clone()).await.unwrap();
+    store.destroy(session.clone()).await.unwrap();
+
+    assert!(store.load(session.id().clone()).await.unwrap().is_none());
+}
+
+#[rocket::async_test]
+async fn it_writes_keys_under_the_configured_prefix() {
+    let endpoints = match env::var("ETCD_ENDPOINTS") {
+        Ok(endpoints) => endpoints,
+        Err(_) => return,
+    };
+
+    let mut store = EtcdStore::<Cart>::init();
+    let config = SessionConfig::builder()
+        .key_prefix("staging:")
+        .try_finish()
+        .unwrap();
+    store.configure(&config);
+
+    let session = Session::<Cart>::new(60);
+    store.store(session.clone()).await.unwrap();
+
+    let endpoints: Vec<&str> = endpoints.split(',').map(str::trim).collect();
+    let mut client = etcd_client::Client::connect(endpoints, None).await.unwrap();
+    let response = client
+        .get(format!("staging:{}", session.id()), None)
+        .await
+        .unwrap();
+    assert_eq!(response.kvs().len(), 1);
+
+    store.destroy(session).await.unwrap();
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::fallback::FallbackStore,
+    Session,
+};
+
+use common::{FailingStore, HitCounter};
+
+fn rocket() -> Rocket {
+    rocket::ignite()
+        .attach(
+            SessionFairing::<FallbackStore<FailingStore<HitCounter>>>::with_config(
+                SessionConfig::default(),
+            ),
+        )
+        .mount("/", routes![increment_count])
+}
+
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+#[test]
+fn sessions_survive_a_failing_primary() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/").dispatch();
+    assert!(response.cookies().get("session_id").is_some());
+    assert_eq!(response.into_string().unwrap(), "1");
+
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "2");
+}
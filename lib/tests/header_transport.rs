@@ -0,0 +1,70 @@
+mod common;
+
+use rocket::{get, http::Header, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{
+        config::{SessionConfig, Transport},
+        SessionFairing,
+    },
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+const HEADER: &str = "X-Session-Id";
+
+fn rocket() -> Rocket {
+    let config = SessionConfig {
+        transport: Transport::Header {
+            name: HEADER.to_string(),
+        },
+        ..SessionConfig::default()
+    };
+
+    rocket::ignite()
+        .attach(SessionFairing::<InMemory<HitCounter>>::with_config(config))
+        .mount("/", routes![increment_count])
+}
+
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+#[test]
+fn it_tracks_the_session_through_a_header() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/").dispatch();
+    assert!(response.cookies().iter().next().is_none());
+    let session_id = response.headers().get_one(HEADER).unwrap().to_string();
+    assert_eq!(response.into_string().unwrap(), "1");
+
+    let response = client
+        .get("/")
+        .header(Header::new(HEADER, session_id.clone()))
+        .dispatch();
+    assert_eq!(
+        response.headers().get_one(HEADER),
+        Some(session_id.as_str())
+    );
+    assert_eq!(response.into_string().unwrap(), "2");
+}
+
+#[test]
+fn it_starts_a_new_session_for_an_unknown_header() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+
+    let response = client
+        .get("/")
+        .header(Header::new(HEADER, "unknown"))
+        .dispatch();
+
+    assert_ne!(response.headers().get_one(HEADER), Some("unknown"));
+    assert_eq!(response.into_string().unwrap(), "1");
+}
@@ -0,0 +1,27 @@
+mod common;
+
+use turbopump::{fairing::SessionFairing, store::in_memory::InMemory};
+
+use common::HitCounter;
+
+type Fairing = SessionFairing<InMemory<HitCounter>>;
+
+// a single test, since it changes the process environment
+#[test]
+fn init_strict_requires_a_valid_session_config() {
+    std::env::remove_var("ROCKET_SESSION");
+    let error = Fairing::init_strict().err().expect("no session config");
+    assert!(error.to_string().contains("session"));
+
+    std::env::set_var(
+        "ROCKET_SESSION",
+        r#"{max_age=3600,same_site="bogus",http_only=true}"#,
+    );
+    assert!(Fairing::init_strict().is_err());
+
+    std::env::set_var(
+        "ROCKET_SESSION",
+        r#"{max_age=3600,same_site="lax",http_only=true}"#,
+    );
+    assert!(Fairing::init_strict().is_ok());
+}
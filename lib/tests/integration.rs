@@ -1,6 +1,15 @@
-use rocket::{get, http::Status, local::blocking::Client, response::content::Html, routes, Rocket};
+use rocket::{
+    get,
+    http::{SameSite as RSameSite, Status},
+    local::blocking::{Client, LocalResponse},
+    response::content::Html,
+    routes, Rocket,
+};
 use turbopump::{
-    fairing::config::SessionConfig, fairing::SessionFairing, store::in_memory::InMemory, Session,
+    fairing::config::{CookieOverride, SameSite, SessionConfig},
+    fairing::SessionFairing,
+    store::in_memory::InMemory,
+    Session, SessionData, SessionDataMut,
 };
 
 #[derive(Clone, Default)]
@@ -13,7 +22,17 @@ fn rocket() -> Rocket {
         .attach(SessionFairing::<InMemory<HitCounter>>::with_config(
             session_config(),
         ))
-        .mount("/", routes![test_route])
+        .mount(
+            "/",
+            routes![
+                test_route,
+                oauth_callback,
+                read_guard,
+                write_guard,
+                logout,
+                edit_then_logout
+            ],
+        )
 }
 
 fn session_config() -> SessionConfig {
@@ -33,11 +52,156 @@ fn test_route(s: &Session<HitCounter>) -> Html<String> {
     ))
 }
 
+#[get("/oauth/callback")]
+fn oauth_callback(s: &Session<HitCounter>) -> Html<String> {
+    s.set_cookie_override(CookieOverride {
+        same_site: Some(SameSite::None),
+        ..CookieOverride::default()
+    });
+
+    Html("<h1>Signed in</h1>".to_string())
+}
+
+#[get("/logout")]
+fn logout(s: &Session<HitCounter>) -> Html<String> {
+    s.invalidate();
+
+    Html("<h1>Signed out</h1>".to_string())
+}
+
+#[get("/logout/after-edit")]
+fn edit_then_logout(s: &Session<HitCounter>) -> Html<String> {
+    test_route(s);
+    s.set_lifespan(7200);
+
+    logout(s)
+}
+
+#[get("/guard/read")]
+fn read_guard(data: SessionData<'_, HitCounter>) -> String {
+    data.count.to_string()
+}
+
+#[get("/guard/write")]
+fn write_guard(mut data: SessionDataMut<'_, HitCounter>) -> String {
+    data.count += 10;
+    data.count.to_string()
+}
+
+/// Asserts the response's session cookie is encrypted: it decrypts with Rocket's secret key, and
+/// the session ID doesn't appear in the value sent.
+fn assert_encrypted(response: &LocalResponse<'_>) {
+    let sent = response
+        .cookies()
+        .get("session_id")
+        .unwrap()
+        .value()
+        .to_string();
+    let id = response.cookies().get_private("session_id").unwrap();
+    assert!(!sent.contains(id.value()));
+}
+
 #[test]
 fn it_sets_a_session_cookie() {
     let client = Client::tracked(rocket()).expect("valid rocket instance");
     let result = client.get("/").dispatch();
 
     assert_eq!(result.status(), Status::Ok);
-    assert!(result.cookies().get_private("session_id").is_some());
+    assert_encrypted(&result);
+}
+
+#[test]
+fn a_reissued_session_cookie_stays_encrypted() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+    client.get("/").dispatch();
+
+    // the override re-issues the cookie the client already holds from the response fairing
+    let result = client.get("/oauth/callback").dispatch();
+    assert_encrypted(&result);
+}
+
+#[test]
+fn it_overrides_cookie_attributes_for_a_single_response() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let result = client.get("/").dispatch();
+    let cookie = result.cookies().get_private("session_id").unwrap();
+    assert_eq!(cookie.same_site(), Some(RSameSite::Lax));
+
+    let result = client.get("/oauth/callback").dispatch();
+    let cookie = result.cookies().get_private("session_id").unwrap();
+    assert_eq!(cookie.same_site(), Some(RSameSite::None));
+}
+
+#[test]
+fn data_guards_share_state_with_the_session() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+    let get = |path| client.get(path).dispatch().into_string().unwrap();
+
+    get("/");
+    assert_eq!(get("/guard/read"), "1");
+    assert_eq!(get("/guard/write"), "11");
+    assert_eq!(get("/guard/read"), "11");
+    assert_eq!(get("/"), "<h1>You have visited this page 12 times</h1>");
+}
+
+#[test]
+fn logout_deletes_the_session() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+    let get = |path| client.get(path).dispatch().into_string().unwrap();
+
+    get("/");
+    get("/");
+    let result = client.get("/logout").dispatch();
+    let cookie = result.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.value(), "");
+    assert_eq!(cookie.max_age(), Some(time::Duration::zero()));
+    assert_eq!(cookie.path(), Some("/"));
+
+    assert_eq!(get("/"), "<h1>You have visited this page 1 times</h1>");
+}
+
+#[rocket::async_test]
+async fn a_session_edited_then_invalidated_is_destroyed() {
+    use rocket::local::asynchronous::Client;
+    use turbopump::store::SessionStore;
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+    client.get("/").dispatch().await;
+
+    let response = client.get("/logout/after-edit").dispatch().await;
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.value(), "");
+    assert_eq!(cookie.max_age(), Some(time::Duration::zero()));
+    let store = client
+        .rocket()
+        .state::<Box<InMemory<HitCounter>>>()
+        .unwrap();
+    assert_eq!(store.count().await.unwrap(), 0);
+}
+
+#[rocket::async_test]
+async fn admin_tidy_removes_expired_sessions() {
+    use rocket::{local::asynchronous::Client, State};
+    use turbopump::store::SessionStore;
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+    let store = State::<Box<InMemory<HitCounter>>>::from(client.rocket()).unwrap();
+    let valid = Session::new(3600);
+    store.store(Session::new(-1)).await.unwrap();
+    store.store(valid.clone()).await.unwrap();
+
+    let session_state = State::from(client.rocket()).unwrap();
+    let store_state = State::from(client.rocket()).unwrap();
+    turbopump::admin::tidy::<InMemory<HitCounter>>(store_state, session_state)
+        .await
+        .unwrap();
+
+    let remaining = store.export().await.unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].id(), valid.id());
 }
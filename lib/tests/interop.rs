@@ -0,0 +1,59 @@
+#![cfg(feature = "interop")]
+
+use rocket::{get, http::Cookie, local::asynchronous::Client, routes, Rocket};
+use serde::Deserialize;
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    interop::import_actix_session,
+    store::in_memory::InMemory,
+    Session,
+};
+
+type Store = InMemory<User>;
+
+// a session key and state as found in Redis after signing in with actix-session
+const SESSION_KEY: &str = "Xr3Ua1h8GdvyJ8Wwx0kwW6aKeD2pD9ZV0rvgqL1u8GSNd5JnYlG0M2eQkWQ7hTbF";
+const STATE: &str = r#"{"user_id":"42","name":"\"ferris\""}"#;
+
+#[derive(Clone, Default, Deserialize)]
+struct User {
+    user_id: u32,
+    name: String,
+}
+
+fn rocket() -> Rocket {
+    let config = SessionConfig::builder()
+        .legacy_cookie_name("id")
+        .try_finish()
+        .unwrap();
+
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(config))
+        .mount("/", routes![whoami])
+}
+
+#[get("/whoami")]
+fn whoami(s: &Session<User>) -> String {
+    s.read(|user| format!("{} {}", user.user_id, user.name))
+}
+
+#[rocket::async_test]
+async fn an_actix_session_cookie_resumes_the_imported_session() {
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+    import_actix_session(&**store, SESSION_KEY, STATE.as_bytes(), 600)
+        .await
+        .unwrap();
+
+    // actix-session's cookie is encrypted the same way as Rocket's private cookies
+    let response = client
+        .get("/whoami")
+        .private_cookie(Cookie::new("id", SESSION_KEY))
+        .dispatch()
+        .await;
+    let reissued = response.cookies().get("session_id").is_some();
+    assert_eq!(response.into_string().await.unwrap(), "42 ferris");
+    assert!(reissued);
+}
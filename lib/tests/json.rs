@@ -0,0 +1,57 @@
+#![cfg(feature = "json")]
+
+use rocket::{
+    get,
+    local::blocking::Client,
+    routes,
+    serde::{json::Value, Deserialize, Serialize},
+    Rocket,
+};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(crate = "rocket::serde")]
+struct User {
+    id: u32,
+    name: String,
+}
+
+fn rocket() -> Rocket {
+    rocket::ignite()
+        .attach(SessionFairing::<InMemory<Value>>::with_config(
+            SessionConfig::default(),
+        ))
+        .mount("/", routes![sign_in, whoami])
+}
+
+#[get("/sign_in")]
+fn sign_in(s: &Session<Value>) {
+    let user = User {
+        id: 42,
+        name: "Ferris".to_string(),
+    };
+
+    s.set_json("user", &user).unwrap();
+}
+
+#[get("/whoami")]
+fn whoami(s: &Session<Value>) -> String {
+    match s.get_json::<User>("user") {
+        Some(user) => format!("{} {}", user.id, user.name),
+        None => "anonymous".to_string(),
+    }
+}
+
+#[test]
+fn it_stores_and_retrieves_json_values() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+    let get = |path| client.get(path).dispatch().into_string().unwrap();
+
+    assert_eq!(get("/whoami"), "anonymous");
+    client.get("/sign_in").dispatch();
+    assert_eq!(get("/whoami"), "42 Ferris");
+}
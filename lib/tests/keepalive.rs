@@ -0,0 +1,55 @@
+use rocket::{http::Status, local::asynchronous::Client, post, routes, Rocket};
+use turbopump::{
+    fairing::{
+        config::{SessionConfig, StorePolicy},
+        SessionFairing,
+    },
+    store::in_memory::InMemory,
+    Session, SessionID, SessionStore,
+};
+
+type Store = InMemory<()>;
+
+fn rocket() -> Rocket {
+    let config = SessionConfig::builder()
+        .store_policy(StorePolicy::OnChange)
+        .try_finish()
+        .unwrap();
+
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(config))
+        .mount("/", routes![keepalive])
+}
+
+#[post("/keepalive")]
+fn keepalive(session: &Session<()>) -> Status {
+    session.keepalive();
+    Status::NoContent
+}
+
+#[rocket::async_test]
+async fn a_keepalive_renews_the_session() {
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+
+    let response = client.post("/keepalive").dispatch().await;
+    assert_eq!(response.status(), Status::NoContent);
+    let id = SessionID(
+        response
+            .cookies()
+            .get_private("session_id")
+            .unwrap()
+            .value()
+            .to_string(),
+    );
+    let first = store.expires_at(&id).await.unwrap().unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    let response = client.post("/keepalive").dispatch().await;
+    assert_eq!(response.status(), Status::NoContent);
+    let second = store.expires_at(&id).await.unwrap().unwrap();
+
+    assert!(second > first);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use rocket::{get, http::Cookie, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+fn rocket() -> Rocket {
+    let config = SessionConfig::builder()
+        .cookie_name("sid")
+        .legacy_cookie_name("session_id")
+        .try_finish()
+        .unwrap();
+
+    rocket::ignite()
+        .attach(SessionFairing::<InMemory<HitCounter>>::with_config(config))
+        .mount("/", routes![index])
+}
+
+#[get("/")]
+fn index(s: &Session<HitCounter>) -> String {
+    let count = s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    });
+
+    format!("{} {}", s.id(), count)
+}
+
+#[test]
+fn it_adopts_a_session_from_a_legacy_cookie() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let body = client.get("/").dispatch().into_string().unwrap();
+    let id = body.split(' ').next().unwrap().to_string();
+
+    // a client that still holds the cookie under its old name
+    let response = client
+        .get("/")
+        .private_cookie(Cookie::new("session_id", id.clone()))
+        .dispatch();
+
+    let cookies = response.cookies();
+    assert!(cookies.get("sid").is_some());
+    assert_eq!(cookies.get("session_id").map(Cookie::value), Some(""));
+    assert_eq!(response.into_string().unwrap(), format!("{} 2", id));
+}
+
+#[test]
+fn the_current_cookie_takes_precedence() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let body = client.get("/").dispatch().into_string().unwrap();
+    let id = body.split(' ').next().unwrap().to_string();
+
+    let response = client
+        .get("/")
+        .private_cookie(Cookie::new("sid", id.clone()))
+        .private_cookie(Cookie::new("session_id", "stale".to_string()))
+        .dispatch();
+
+    assert!(response.cookies().get("sid").is_none());
+    assert_eq!(response.into_string().unwrap(), format!("{} 2", id));
+}
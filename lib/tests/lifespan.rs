@@ -0,0 +1,53 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use time::Duration;
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+fn rocket() -> Rocket {
+    rocket::ignite()
+        .attach(SessionFairing::<InMemory<HitCounter>>::with_config(
+            SessionConfig::default(),
+        ))
+        .mount("/", routes![index, guest_checkout])
+}
+
+#[get("/")]
+fn index(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+#[get("/guest")]
+fn guest_checkout(s: &Session<HitCounter>) -> &'static str {
+    s.set_lifespan(60);
+    "Checking out as a guest"
+}
+
+#[test]
+fn the_cookie_reflects_a_session_lifespan() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/").dispatch();
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.max_age(), Some(Duration::seconds(3600)));
+
+    let response = client.get("/guest").dispatch();
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.max_age(), Some(Duration::seconds(60)));
+
+    // the lifespan sticks to the session on later requests
+    let response = client.get("/").dispatch();
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.max_age(), Some(Duration::seconds(60)));
+    assert_eq!(response.into_string().unwrap(), "2");
+}
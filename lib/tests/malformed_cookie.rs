@@ -0,0 +1,84 @@
+mod common;
+
+use rocket::{get, http::Cookie, local::blocking::Client, routes, Rocket};
+use std::sync::Mutex;
+use turbopump::{
+    fairing::SessionFairing,
+    store::{in_memory::InMemory, SessionStore},
+    Error, Session, SessionID,
+};
+
+use common::HitCounter;
+
+/// An `InMemory` store that records every ID it's asked to load.
+struct RecordingStore {
+    inner: InMemory<HitCounter>,
+    loaded: Mutex<Vec<SessionID>>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for RecordingStore {
+    type SessionData = HitCounter;
+
+    fn init() -> Self {
+        Self {
+            inner: InMemory::init(),
+            loaded: Mutex::new(Vec::new()),
+        }
+    }
+
+    async fn load(&self, session_id: SessionID) -> Result<Option<Session<HitCounter>>, Error> {
+        self.loaded.lock().unwrap().push(session_id.clone());
+        self.inner.load(session_id).await
+    }
+
+    async fn store(&self, session: Session<HitCounter>) -> Result<(), Error> {
+        self.inner.store(session).await
+    }
+
+    async fn clear(&self) -> Result<(), Error> {
+        self.inner.clear().await
+    }
+
+    async fn destroy(&self, session: Session<HitCounter>) -> Result<(), Error> {
+        self.inner.destroy(session).await
+    }
+
+    async fn tidy(&self, tolerance: i64) -> Result<(), Error> {
+        self.inner.tidy(tolerance).await
+    }
+}
+
+fn rocket() -> Rocket {
+    rocket::ignite()
+        .attach(SessionFairing::<RecordingStore>::init())
+        .mount("/", routes![index])
+}
+
+#[get("/")]
+fn index(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+#[test]
+fn a_garbage_session_cookie_starts_a_new_session_without_reaching_the_store() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let garbage: String = (0..10 * 1024)
+        .map(|i| (b'!' + (i % 90) as u8) as char)
+        .collect();
+
+    let response = client
+        .get("/")
+        .private_cookie(Cookie::new("session_id", garbage))
+        .dispatch();
+    let cookie = response.cookies().get_private("session_id").unwrap();
+    assert_eq!(response.into_string().unwrap(), "1");
+    assert!(SessionID::parse(cookie.value()).is_some());
+
+    let store = client.rocket().state::<Box<RecordingStore>>().unwrap();
+    assert!(store.loaded.lock().unwrap().is_empty());
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    MaybeSession,
+};
+
+use common::HitCounter;
+
+type Store = InMemory<HitCounter>;
+
+fn rocket(config: SessionConfig) -> Rocket {
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(config))
+        .mount("/", routes![maybe, excluded])
+}
+
+#[get("/")]
+fn maybe(s: MaybeSession<'_, HitCounter>) -> &'static str {
+    match s.0 {
+        Some(_) => "some",
+        None => "none",
+    }
+}
+
+#[get("/excluded")]
+fn excluded(s: MaybeSession<'_, HitCounter>) -> &'static str {
+    maybe(s)
+}
+
+#[test]
+fn a_normal_route_has_a_session() {
+    let client = Client::tracked(rocket(SessionConfig::default())).expect("valid rocket instance");
+
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "some");
+}
+
+#[test]
+fn an_excluded_route_has_none() {
+    let config = SessionConfig::builder()
+        .peek_path("/excluded")
+        .try_finish()
+        .unwrap();
+    let client = Client::tracked(rocket(config)).expect("valid rocket instance");
+
+    assert_eq!(
+        client.get("/excluded").dispatch().into_string().unwrap(),
+        "none"
+    );
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "some");
+}
+
+#[test]
+fn a_read_only_request_without_a_session_has_none() {
+    let config = SessionConfig {
+        read_only: true,
+        ..SessionConfig::default()
+    };
+    let client = Client::tracked(rocket(config)).expect("valid rocket instance");
+
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "none");
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes};
+use turbopump::{
+    fairing::{
+        config::{PathStrategy, SessionConfig},
+        SessionFairing,
+    },
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+#[get("/")]
+fn index(s: &Session<HitCounter>) -> &'static str {
+    s.tap(|counter| counter.count += 1);
+    "Hello, world!"
+}
+
+#[get("/settings")]
+fn settings(_s: &Session<HitCounter>) -> &'static str {
+    "Settings"
+}
+
+#[test]
+fn cookie_path_is_derived_from_the_mount_point() {
+    let config = SessionConfig::builder()
+        .path_strategy(PathStrategy::DeriveFromMount)
+        .try_finish()
+        .unwrap();
+    let rocket = rocket::ignite()
+        .attach(SessionFairing::<InMemory<HitCounter>>::with_config(config))
+        .mount("/app", routes![index])
+        .mount("/app/account", routes![settings]);
+    let client = Client::tracked(rocket).expect("valid rocket instance");
+
+    let response = client.get("/app").dispatch();
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.path(), Some("/app"));
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use rocket::{get, http::Cookie, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{
+        config::{SessionConfig, StoreErrorPolicy},
+        SessionFairing,
+    },
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::UnreachableStore;
+
+#[derive(Clone, Default)]
+struct Cart {
+    items: u32,
+}
+
+#[derive(Clone, Default)]
+struct Preferences {
+    dark_mode: bool,
+}
+
+// each fairing needs its own cookie names, or the two sessions would share one cookie
+fn configs() -> (SessionConfig, SessionConfig) {
+    let cart_config = SessionConfig::builder()
+        .cookie_name("cart_session")
+        .xsrf_cookie_name("cart_xsrf")
+        .on_store_error(StoreErrorPolicy::StripCookie)
+        .try_finish()
+        .unwrap();
+    let preferences_config = SessionConfig::builder()
+        .cookie_name("preferences_session")
+        .xsrf_cookie_name("preferences_xsrf")
+        .on_store_error(StoreErrorPolicy::StripCookie)
+        .try_finish()
+        .unwrap();
+
+    (cart_config, preferences_config)
+}
+
+fn rocket() -> Rocket {
+    let (cart_config, preferences_config) = configs();
+
+    rocket::ignite()
+        .attach(SessionFairing::<InMemory<Cart>>::with_config(cart_config))
+        .attach(SessionFairing::<InMemory<Preferences>>::with_config(
+            preferences_config,
+        ))
+        .mount("/", routes![index, cookie_names])
+}
+
+/// The cart's store is down; the preferences' isn't.
+fn partly_unreachable_rocket() -> Rocket {
+    let (cart_config, preferences_config) = configs();
+
+    rocket::ignite()
+        .attach(SessionFairing::<UnreachableStore<Cart>>::with_config(
+            cart_config,
+        ))
+        .attach(SessionFairing::<InMemory<Preferences>>::with_config(
+            preferences_config,
+        ))
+        .mount("/", routes![index])
+}
+
+#[get("/")]
+fn index(cart: &Session<Cart>, preferences: &Session<Preferences>) -> String {
+    let items = cart.tap(|cart| {
+        cart.items += 1;
+        cart.items
+    });
+    let dark_mode = preferences.tap(|preferences| {
+        preferences.dark_mode = !preferences.dark_mode;
+        preferences.dark_mode
+    });
+
+    format!("{} {} {}", items, dark_mode, cart.id() != preferences.id())
+}
+
+#[get("/cookie-names")]
+fn cookie_names(cart: &Session<Cart>, preferences: &Session<Preferences>) -> String {
+    format!(
+        "{} {} {} {}",
+        cart.cookie_value().0,
+        cart.token_value().0,
+        preferences.cookie_value().0,
+        preferences.token_value().0
+    )
+}
+
+#[test]
+fn sessions_of_different_types_coexist() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/").dispatch();
+    let cookies = response.cookies();
+    let cart_cookie = cookies.get_private("cart_session").unwrap();
+    let preferences_cookie = cookies.get_private("preferences_session").unwrap();
+    assert_ne!(cart_cookie.value(), preferences_cookie.value());
+    assert!(cookies.get_private("cart_xsrf").is_some());
+    assert!(cookies.get_private("preferences_xsrf").is_some());
+    assert_eq!(response.into_string().unwrap(), "1 true true");
+
+    let response = client.get("/").dispatch();
+    assert_eq!(response.into_string().unwrap(), "2 false true");
+}
+
+#[test]
+fn a_failed_load_only_affects_its_own_session() {
+    let client = Client::tracked(partly_unreachable_rocket()).expect("valid rocket instance");
+
+    let response = client
+        .get("/")
+        .private_cookie(Cookie::new("cart_session", "existing"))
+        .dispatch();
+    let cookies = response.cookies();
+    assert!(cookies.get("cart_session").is_none());
+    assert!(cookies.get_private("preferences_session").is_some());
+}
+
+#[test]
+fn sessions_report_their_own_fairings_cookie_names() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/cookie-names").dispatch();
+    assert_eq!(
+        response.into_string().unwrap(),
+        "cart_session cart_xsrf preferences_session preferences_xsrf"
+    );
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use rocket::{get, local::asynchronous::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::SessionStore,
+    PeekSession, Session, SessionID,
+};
+
+use common::{CountingStore, HitCounter};
+
+type Store = CountingStore<HitCounter>;
+
+fn rocket() -> Rocket {
+    let config = SessionConfig::builder()
+        .peek_path("/analytics")
+        .try_finish()
+        .unwrap();
+
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(config))
+        .mount("/", routes![visit, peek, lookalike])
+}
+
+#[get("/visit")]
+fn visit(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| counter.count += 1);
+    s.id_str().to_string()
+}
+
+#[get("/analytics/peek")]
+fn peek(s: PeekSession<'_, HitCounter>) -> String {
+    match s.0 {
+        Some(session) => session.read(|counter| counter.count).to_string(),
+        None => "none".to_string(),
+    }
+}
+
+#[get("/analytics-admin")]
+fn lookalike(s: &Session<HitCounter>) -> String {
+    s.id_str().to_string()
+}
+
+#[rocket::async_test]
+async fn peeking_neither_renews_nor_sets_cookies() {
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+
+    let response = client.get("/analytics/peek").dispatch().await;
+    assert!(response.cookies().iter().next().is_none());
+    assert_eq!(response.into_string().await.unwrap(), "none");
+    assert_eq!(store.writes(), 0);
+
+    let response = client.get("/visit").dispatch().await;
+    let id = SessionID(response.into_string().await.unwrap());
+    let expiration = store.expires_at(&id).await.unwrap();
+
+    let response = client.get("/analytics/peek").dispatch().await;
+    assert!(response.cookies().iter().next().is_none());
+    assert_eq!(response.into_string().await.unwrap(), "1");
+    assert_eq!(store.expires_at(&id).await.unwrap(), expiration);
+    assert_eq!(store.writes(), 1);
+    assert_eq!(store.renews(), 0);
+}
+
+#[rocket::async_test]
+async fn peek_paths_match_whole_segments() {
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client.get("/analytics-admin").dispatch().await;
+    assert!(response.cookies().get_private("session_id").is_some());
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use rocket::{get, http::Cookie, local::asynchronous::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session, SessionStore,
+};
+
+use common::HitCounter;
+
+type Store = InMemory<HitCounter>;
+
+fn rocket(accept_plaintext_cookies: bool) -> Rocket {
+    let config = SessionConfig::builder()
+        .accept_plaintext_cookies(accept_plaintext_cookies)
+        .try_finish()
+        .unwrap();
+
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(config))
+        .mount("/", routes![increment_count])
+}
+
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+/// Stores a session that has been visited once, as if before the switch to private cookies.
+async fn existing_session(client: &Client) -> String {
+    let session = Session::new(3600);
+    session.tap(|counter: &mut HitCounter| counter.count = 1);
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+    store.store(session.clone()).await.unwrap();
+
+    session.id_str().to_string()
+}
+
+#[rocket::async_test]
+async fn a_plaintext_cookie_is_upgraded_to_a_private_one() {
+    let client = Client::untracked(rocket(true))
+        .await
+        .expect("valid rocket instance");
+    let id = existing_session(&client).await;
+
+    let response = client
+        .get("/")
+        .cookie(Cookie::new("session_id", id.clone()))
+        .dispatch()
+        .await;
+    let sent = response
+        .cookies()
+        .get("session_id")
+        .unwrap()
+        .value()
+        .to_string();
+    let upgraded = response.cookies().get_private("session_id").unwrap();
+    assert_eq!(upgraded.value(), id);
+    assert!(!sent.contains(&id));
+    assert_eq!(response.into_string().await.unwrap(), "2");
+}
+
+#[rocket::async_test]
+async fn plaintext_cookies_are_ignored_outside_the_migration() {
+    let client = Client::untracked(rocket(false))
+        .await
+        .expect("valid rocket instance");
+    let id = existing_session(&client).await;
+
+    let response = client
+        .get("/")
+        .cookie(Cookie::new("session_id", id.clone()))
+        .dispatch()
+        .await;
+    let issued = response.cookies().get_private("session_id").unwrap();
+    assert_ne!(issued.value(), id);
+    assert_eq!(response.into_string().await.unwrap(), "1");
+}
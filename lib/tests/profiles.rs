@@ -0,0 +1,58 @@
+mod common;
+
+use rocket::{
+    figment::providers::{Format, Toml},
+    get,
+    http::SameSite,
+    local::blocking::Client,
+    routes, Config, Rocket,
+};
+use turbopump::{fairing::SessionFairing, store::in_memory::InMemory, Session};
+
+use common::HitCounter;
+
+const ROCKET_TOML: &str = r#"
+    [default.session]
+    max_age = 3600
+    http_only = true
+    same_site = "lax"
+    secure = false
+
+    [release]
+    secret_key = "mXuTV+z6aFfcVO60bBR3Mr2pBibBiJnXEUq34+D+ly1qc65zjezw/3CyOubyYQxhYVmBPYl8CAZDlAmQEr/tCg=="
+
+    [release.session]
+    same_site = "none"
+    secure = true
+"#;
+
+fn rocket(profile: &str) -> Rocket {
+    let figment = Config::figment()
+        .merge(Toml::string(ROCKET_TOML).nested())
+        .select(profile);
+
+    rocket::custom(figment)
+        .attach(SessionFairing::<InMemory<HitCounter>>::init())
+        .mount("/", routes![index])
+}
+
+#[get("/")]
+fn index(s: &Session<HitCounter>) -> &'static str {
+    s.tap(|counter| counter.count += 1);
+    "Hello, world!"
+}
+
+#[test]
+fn the_active_profile_selects_the_session_config() {
+    let client = Client::tracked(rocket("debug")).expect("valid rocket instance");
+    let response = client.get("/").dispatch();
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+    assert_eq!(cookie.secure(), Some(false));
+
+    let client = Client::tracked(rocket("release")).expect("valid rocket instance");
+    let response = client.get("/").dispatch();
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.same_site(), Some(SameSite::None));
+    assert_eq!(cookie.secure(), Some(true));
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+fn rocket(config: SessionConfig) -> Rocket {
+    rocket::ignite()
+        .attach(SessionFairing::<InMemory<HitCounter>>::with_config(config))
+        .mount("/", routes![increment_count])
+}
+
+/// Answers with the session ID, for the client to put in its next URL, and the hit count.
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    let count = s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    });
+
+    format!("{} {}", s.id(), count)
+}
+
+fn hit(client: &Client, uri: &str) -> (String, String) {
+    let body = client
+        .get(uri.to_string())
+        .dispatch()
+        .into_string()
+        .unwrap();
+    let mut parts = body.split(' ');
+    let id = parts.next().unwrap().to_string();
+    (id, parts.next().unwrap().to_string())
+}
+
+#[test]
+fn it_reads_the_session_id_from_the_query_parameter() {
+    let config = SessionConfig::builder()
+        .session_id_query_param("sid")
+        .try_finish()
+        .unwrap();
+    let client = Client::untracked(rocket(config)).expect("valid rocket instance");
+
+    let (id, count) = hit(&client, "/");
+    assert_eq!(count, "1");
+
+    let (resumed, count) = hit(&client, &format!("/?page=2&sid={}", id));
+    assert_eq!(resumed, id);
+    assert_eq!(count, "2");
+}
+
+#[test]
+fn it_ignores_the_query_parameter_unless_configured() {
+    let client =
+        Client::untracked(rocket(SessionConfig::default())).expect("valid rocket instance");
+
+    let (id, _) = hit(&client, "/");
+    let (other, count) = hit(&client, &format!("/?sid={}", id));
+    assert_ne!(other, id);
+    assert_eq!(count, "1");
+}
+
+#[test]
+fn the_session_cookie_takes_precedence_over_the_query_parameter() {
+    let config = SessionConfig::builder()
+        .session_id_query_param("sid")
+        .try_finish()
+        .unwrap();
+    let client = Client::tracked(rocket(config)).expect("valid rocket instance");
+
+    let (id, _) = hit(&client, "/");
+    let (resumed, count) = hit(&client, "/?sid=somebodyelse");
+    assert_eq!(resumed, id);
+    assert_eq!(count, "2");
+}
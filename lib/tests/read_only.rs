@@ -0,0 +1,47 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    Session,
+};
+
+use common::{CountingStore, HitCounter};
+
+type Store = CountingStore<HitCounter>;
+
+fn rocket() -> Rocket {
+    let config = SessionConfig {
+        read_only: true,
+        lottery: 1.0,
+        ..SessionConfig::default()
+    };
+
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(config))
+        .mount("/", routes![increment_count])
+}
+
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+#[test]
+fn read_only_fairing_never_writes() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    for _ in 0..3 {
+        let response = client.get("/").dispatch();
+        assert!(response.headers().get_one("Set-Cookie").is_none());
+        assert_eq!(response.into_string().unwrap(), "1");
+    }
+
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+    assert_eq!(store.writes(), 0);
+    assert_eq!(store.renews(), 0);
+}
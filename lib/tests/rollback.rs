@@ -0,0 +1,52 @@
+mod common;
+
+use rocket::{get, local::asynchronous::Client, routes, Rocket};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::SessionStore,
+    Session, SessionID,
+};
+
+use common::{CountingStore, HitCounter};
+
+type Store = CountingStore<HitCounter>;
+
+fn rocket() -> Rocket {
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(
+            SessionConfig::default(),
+        ))
+        .mount("/", routes![increment, failed_increment])
+}
+
+#[get("/increment")]
+fn increment(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| counter.count += 1);
+    s.id_str().to_string()
+}
+
+#[get("/failed_increment")]
+fn failed_increment(s: &Session<HitCounter>) -> Result<(), ()> {
+    s.checkpoint();
+    s.tap(|counter| counter.count += 1);
+
+    // something went wrong, so the change must not stick
+    s.rollback();
+    Err(())
+}
+
+#[rocket::async_test]
+async fn rolled_back_changes_are_not_persisted() {
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client.get("/increment").dispatch().await;
+    let session_id = SessionID(response.into_string().await.unwrap());
+
+    client.get("/failed_increment").dispatch().await;
+
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+    let session = store.load(session_id).await.unwrap().unwrap();
+    assert_eq!(session.read(|counter| counter.count), 1);
+}
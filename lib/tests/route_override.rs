@@ -0,0 +1,81 @@
+mod common;
+
+use rocket::{get, http::SameSite as RSameSite, local::blocking::Client, routes, Rocket};
+use time::Duration;
+use turbopump::{
+    fairing::{
+        config::{CookieOverride, SameSite, SessionConfig},
+        SessionFairing,
+    },
+    store::in_memory::InMemory,
+    RouteOverride, Session,
+};
+
+use common::HitCounter;
+
+fn rocket() -> Rocket {
+    rocket::ignite()
+        .attach(SessionFairing::<InMemory<HitCounter>>::with_config(
+            SessionConfig::default(),
+        ))
+        .mount("/", routes![index, admin, admin_sudo])
+}
+
+fn admin_override() -> CookieOverride {
+    CookieOverride {
+        max_age: Some(300),
+        same_site: Some(SameSite::Strict),
+        ..CookieOverride::default()
+    }
+}
+
+#[get("/")]
+fn index(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| counter.count += 1);
+    "Home".to_string()
+}
+
+#[get("/admin")]
+fn admin(route: RouteOverride<'_>, s: &Session<HitCounter>) -> String {
+    route.set(admin_override());
+    s.tap(|counter| counter.count += 1);
+    "Admin".to_string()
+}
+
+#[get("/admin/sudo")]
+fn admin_sudo(route: RouteOverride<'_>, s: &Session<HitCounter>) -> String {
+    route.set(admin_override());
+    s.set_cookie_override(CookieOverride {
+        max_age: Some(60),
+        ..CookieOverride::default()
+    });
+    "Sudo".to_string()
+}
+
+#[test]
+fn a_route_override_applies_to_its_own_responses() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/").dispatch();
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.max_age(), Some(Duration::seconds(3600)));
+
+    let response = client.get("/admin").dispatch();
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.max_age(), Some(Duration::seconds(300)));
+    assert_eq!(cookie.same_site(), Some(RSameSite::Strict));
+
+    // the override isn't persisted with the session, so other routes don't re-issue the cookie
+    let response = client.get("/").dispatch();
+    assert!(response.cookies().get("session_id").is_none());
+}
+
+#[test]
+fn the_session_override_takes_precedence_over_the_route_override() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/admin/sudo").dispatch();
+    let cookie = response.cookies().get("session_id").cloned().unwrap();
+    assert_eq!(cookie.max_age(), Some(Duration::seconds(60)));
+    assert_eq!(cookie.same_site(), Some(RSameSite::Strict));
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use rocket::{get, http::Cookie, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    codec::{CookieCodec, HmacCodec},
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+const KEY: &str = "a very secret signing key";
+
+fn rocket() -> Rocket {
+    let config = SessionConfig {
+        signing_key: Some(KEY.to_string()),
+        ..SessionConfig::default()
+    };
+
+    rocket::ignite()
+        .attach(SessionFairing::<InMemory<HitCounter>>::with_config(config))
+        .mount("/", routes![increment_count])
+}
+
+#[get("/")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+#[test]
+fn it_issues_a_signed_session_cookie() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/").dispatch();
+    let cookie = response.cookies().get("session_id").unwrap().clone();
+    assert!(HmacCodec::new(KEY).decode(cookie.value()).is_some());
+    assert_eq!(response.into_string().unwrap(), "1");
+
+    assert_eq!(client.get("/").dispatch().into_string().unwrap(), "2");
+}
+
+#[test]
+fn it_ignores_a_forged_session_cookie() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/").dispatch();
+    let session_id = HmacCodec::new(KEY)
+        .decode(response.cookies().get("session_id").unwrap().value())
+        .unwrap();
+
+    let forged = HmacCodec::new("not the key").encode(&session_id);
+    let response = client
+        .get("/")
+        .cookie(Cookie::new("session_id", forged))
+        .dispatch();
+
+    assert_eq!(response.into_string().unwrap(), "1");
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use rocket::{
+    get,
+    http::{Cookie, Status},
+    local::blocking::Client,
+    routes, Rocket,
+};
+use turbopump::{
+    fairing::{
+        config::{SessionConfig, StoreErrorPolicy},
+        SessionFairing,
+    },
+    Session,
+};
+
+use common::{CountingStore, FailingStore, HitCounter, UnreachableStore};
+
+fn rocket(on_store_error: StoreErrorPolicy) -> Rocket {
+    let config = SessionConfig {
+        on_store_error,
+        ..SessionConfig::default()
+    };
+
+    rocket::ignite()
+        .attach(SessionFairing::<FailingStore<HitCounter>>::with_config(
+            config,
+        ))
+        .mount("/", routes![index])
+}
+
+fn unreachable_rocket(on_store_error: StoreErrorPolicy) -> Rocket {
+    let config = SessionConfig {
+        on_store_error,
+        ..SessionConfig::default()
+    };
+
+    rocket::ignite()
+        .attach(SessionFairing::<UnreachableStore<HitCounter>>::with_config(
+            config,
+        ))
+        .mount("/", routes![index])
+}
+
+/// A store that stores sessions but has no `index`.
+fn unindexed_rocket(on_store_error: StoreErrorPolicy) -> Rocket {
+    let config = SessionConfig {
+        on_store_error,
+        ..SessionConfig::default()
+    };
+
+    rocket::ignite()
+        .attach(SessionFairing::<CountingStore<HitCounter>>::with_config(
+            config,
+        ))
+        .mount("/", routes![indexed])
+}
+
+#[get("/indexed")]
+fn indexed(s: &Session<HitCounter>) -> &'static str {
+    s.set_index("user:1");
+    "Hello, world!"
+}
+
+#[get("/")]
+fn index(s: &Session<HitCounter>) -> &'static str {
+    s.tap(|counter| counter.count += 1);
+    "Hello, world!"
+}
+
+#[test]
+fn optimistic_sends_the_cookie_anyway() {
+    let client = Client::tracked(rocket(StoreErrorPolicy::Optimistic)).expect("valid rocket");
+    let response = client.get("/").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.cookies().get("session_id").is_some());
+}
+
+#[test]
+fn strip_cookie_drops_the_session_cookie() {
+    let client = Client::tracked(rocket(StoreErrorPolicy::StripCookie)).expect("valid rocket");
+    let response = client.get("/").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.cookies().get("session_id").is_none());
+    assert_eq!(response.into_string().unwrap(), "Hello, world!");
+}
+
+#[test]
+fn service_unavailable_fails_the_response() {
+    let client =
+        Client::tracked(rocket(StoreErrorPolicy::ServiceUnavailable)).expect("valid rocket");
+    let response = client.get("/").dispatch();
+
+    assert_eq!(response.status(), Status::ServiceUnavailable);
+    assert!(response.cookies().get("session_id").is_none());
+}
+
+#[test]
+fn an_unsupported_index_is_a_store_error() {
+    let client =
+        Client::tracked(unindexed_rocket(StoreErrorPolicy::Optimistic)).expect("valid rocket");
+    let response = client.get("/indexed").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.cookies().get("session_id").is_some());
+
+    let client =
+        Client::tracked(unindexed_rocket(StoreErrorPolicy::StripCookie)).expect("valid rocket");
+    let response = client.get("/indexed").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.cookies().get("session_id").is_none());
+    assert_eq!(response.into_string().unwrap(), "Hello, world!");
+}
+
+#[test]
+fn a_failed_load_starts_a_new_session_when_optimistic() {
+    let client =
+        Client::tracked(unreachable_rocket(StoreErrorPolicy::Optimistic)).expect("valid rocket");
+    let response = client
+        .get("/")
+        .private_cookie(Cookie::new("session_id", "existing"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    let issued = response.cookies().get_private("session_id").unwrap();
+    assert_ne!(issued.value(), "existing");
+}
+
+#[test]
+fn a_failed_load_keeps_the_clients_cookie_otherwise() {
+    let client =
+        Client::tracked(unreachable_rocket(StoreErrorPolicy::StripCookie)).expect("valid rocket");
+    let response = client
+        .get("/")
+        .private_cookie(Cookie::new("session_id", "existing"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.cookies().get("session_id").is_none());
+
+    let client = Client::tracked(unreachable_rocket(StoreErrorPolicy::ServiceUnavailable))
+        .expect("valid rocket");
+    let response = client
+        .get("/")
+        .private_cookie(Cookie::new("session_id", "existing"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::ServiceUnavailable);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use rocket::{get, http::Status, local::blocking::Client, routes, Rocket};
+use turbopump::{admin::StoreHandle, fairing::SessionFairing, store::in_memory::InMemory, Session};
+
+use common::HitCounter;
+
+type Store = InMemory<HitCounter>;
+
+fn rocket() -> Rocket {
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(Default::default()))
+        .mount("/", routes![visit, count, wrong_store])
+}
+
+#[get("/")]
+fn visit(s: &Session<HitCounter>) {
+    s.tap(|counter| counter.count += 1);
+}
+
+#[get("/admin/count")]
+async fn count(store: StoreHandle<'_, Store>) -> Option<String> {
+    store.count().await.ok().map(|count| count.to_string())
+}
+
+#[get("/admin/wrong")]
+async fn wrong_store(store: StoreHandle<'_, InMemory<u32>>) -> Option<String> {
+    store.count().await.ok().map(|count| count.to_string())
+}
+
+#[test]
+fn a_route_can_count_sessions_through_the_store_handle() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    for _ in 0..3 {
+        client.get("/").dispatch();
+    }
+
+    // each untracked request started a session of its own
+    let response = client.get("/admin/count").dispatch();
+    assert_eq!(response.into_string().unwrap(), "3");
+}
+
+#[test]
+fn the_store_handle_fails_for_an_unmanaged_store() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+
+    let response = client.get("/admin/wrong").dispatch();
+    assert_eq!(response.status(), Status::InternalServerError);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use rocket::{get, local::blocking::Client, routes, Rocket};
+use turbopump::{
+    fairing::{
+        config::{SessionConfig, StorePolicy},
+        SessionFairing,
+    },
+    Session,
+};
+
+use common::{CountingStore, HitCounter};
+
+type Store = CountingStore<HitCounter>;
+
+fn rocket(store_policy: StorePolicy) -> Rocket {
+    let config = SessionConfig {
+        store_policy,
+        ..SessionConfig::default()
+    };
+
+    rocket::ignite()
+        .attach(SessionFairing::<Store>::with_config(config))
+        .mount("/", routes![read_count, increment_count, touch, rollback])
+}
+
+#[get("/read")]
+fn read_count(s: &Session<HitCounter>) -> String {
+    s.read(|counter| counter.count).to_string()
+}
+
+#[get("/increment")]
+fn increment_count(s: &Session<HitCounter>) -> String {
+    s.tap(|counter| {
+        counter.count += 1;
+        counter.count
+    })
+    .to_string()
+}
+
+#[get("/touch")]
+fn touch(s: &Session<HitCounter>) {
+    s.mark_dirty();
+}
+
+#[get("/rollback")]
+fn rollback(s: &Session<HitCounter>) {
+    s.tap(|counter| counter.count += 1);
+    s.tap(|counter| counter.count -= 1);
+    s.mark_clean();
+}
+
+fn writes(client: &Client) -> usize {
+    client.rocket().state::<Box<Store>>().unwrap().writes()
+}
+
+#[test]
+fn on_change_skips_writes_for_pure_reads() {
+    let client = Client::tracked(rocket(StorePolicy::OnChange)).expect("valid rocket instance");
+
+    // a new session is always written
+    client.get("/read").dispatch();
+    assert_eq!(writes(&client), 1);
+
+    client.get("/read").dispatch();
+    assert_eq!(writes(&client), 1);
+
+    client.get("/increment").dispatch();
+    assert_eq!(writes(&client), 2);
+}
+
+#[test]
+fn on_change_renews_pure_reads_without_writing() {
+    let client = Client::tracked(rocket(StorePolicy::OnChange)).expect("valid rocket instance");
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+
+    client.get("/read").dispatch();
+    assert_eq!(store.renews(), 0);
+
+    client.get("/read").dispatch();
+    assert_eq!(store.renews(), 1);
+    assert_eq!(store.writes(), 1);
+}
+
+#[test]
+fn on_change_honors_manual_dirty_marks() {
+    let client = Client::tracked(rocket(StorePolicy::OnChange)).expect("valid rocket instance");
+
+    client.get("/read").dispatch();
+    assert_eq!(writes(&client), 1);
+
+    client.get("/touch").dispatch();
+    assert_eq!(writes(&client), 2);
+
+    client.get("/rollback").dispatch();
+    assert_eq!(writes(&client), 2);
+}
+
+#[test]
+fn always_writes_every_request() {
+    let client = Client::tracked(rocket(StorePolicy::Always)).expect("valid rocket instance");
+
+    client.get("/read").dispatch();
+    client.get("/read").dispatch();
+
+    assert_eq!(writes(&client), 2);
+}
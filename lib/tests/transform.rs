@@ -0,0 +1,70 @@
+use rocket::{get, local::asynchronous::Client, routes, Rocket};
+use turbopump::{
+    fairing::SessionFairing, store::in_memory::InMemory, transform::DataTransform, Session,
+    SessionID, SessionStore,
+};
+
+#[derive(Clone, Default)]
+struct Data {
+    loads: u32,
+    temp: Option<String>,
+}
+
+type Store = InMemory<Data>;
+
+struct CountLoadsStripTemp;
+
+impl DataTransform<Data> for CountLoadsStripTemp {
+    fn on_load(&self, data: &mut Data) {
+        data.loads += 1;
+    }
+
+    fn before_store(&self, data: &mut Data) {
+        data.temp = None;
+    }
+}
+
+fn rocket() -> Rocket {
+    let fairing = SessionFairing::<Store>::builder()
+        .transform(CountLoadsStripTemp)
+        .build();
+
+    rocket::ignite().attach(fairing).mount("/", routes![index])
+}
+
+#[get("/")]
+fn index(session: &Session<Data>) -> String {
+    let (loads, had_temp) = session.read(|data| (data.loads, data.temp.is_some()));
+    session.tap(|data| data.temp = Some("scratch".to_string()));
+
+    format!("{}:{}", loads, had_temp)
+}
+
+#[rocket::async_test]
+async fn hooks_run_on_load_and_before_store() {
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+    let store = client.rocket().state::<Box<Store>>().unwrap();
+
+    let response = client.get("/").dispatch().await;
+    let id = SessionID(
+        response
+            .cookies()
+            .get_private("session_id")
+            .unwrap()
+            .value()
+            .to_string(),
+    );
+    // a new session isn't loaded, so `on_load` hasn't run yet
+    assert_eq!(response.into_string().await.unwrap(), "0:false");
+
+    for expected in &["1:false", "2:false"] {
+        let response = client.get("/").dispatch().await;
+        assert_eq!(response.into_string().await.unwrap(), *expected);
+    }
+
+    let stored = store.load(id).await.unwrap().unwrap();
+    assert_eq!(stored.read(|data| data.loads), 2);
+    assert!(stored.read(|data| data.temp.is_none()));
+}
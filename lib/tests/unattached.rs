@@ -0,0 +1,61 @@
+mod common;
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    get,
+    http::Status,
+    local::blocking::Client,
+    routes, Data, Request, Response, Rocket,
+};
+use turbopump::{
+    fairing::{config::SessionConfig, SessionFairing},
+    store::in_memory::InMemory,
+    Session,
+};
+
+use common::HitCounter;
+
+/// Runs the session fairing's request and response callbacks without ever attaching it, as if
+/// its `on_attach` never ran.
+struct Unattached(SessionFairing<InMemory<HitCounter>>);
+
+#[async_trait::async_trait]
+impl Fairing for Unattached {
+    fn info(&self) -> Info {
+        Info {
+            name: "Unattached session fairing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, data: &mut Data) {
+        self.0.on_request(req, data).await
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        self.0.on_response(req, res).await
+    }
+}
+
+#[get("/")]
+fn index(_s: &Session<HitCounter>) -> &'static str {
+    "Hello, world!"
+}
+
+fn rocket() -> Rocket {
+    let fairing = SessionFairing::with_config(SessionConfig::default());
+
+    rocket::ignite()
+        .attach(Unattached(fairing))
+        .mount("/", routes![index])
+}
+
+#[test]
+fn requests_go_ahead_without_a_managed_store() {
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+    let response = client.get("/").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.cookies().get("session_id").is_none());
+    assert_eq!(response.into_string().unwrap(), "Hello, world!");
+}